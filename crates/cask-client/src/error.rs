@@ -0,0 +1,95 @@
+use cask_types::SchemaViolation;
+use reqwest::StatusCode;
+
+/// Category of an error response, mirroring the server's `AppError`
+/// constructors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    NotFound,
+    Conflict,
+    Unauthorized,
+    Forbidden,
+    /// The file is damaged on disk and won't be served until it's
+    /// repaired or uploaded again.
+    Damaged,
+    PayloadTooLarge,
+    /// Metadata doesn't match its schema; see [`Error::violations`].
+    Unprocessable,
+    /// The server is low on or out of disk space.
+    InsufficientStorage,
+    /// A storage quota would be exceeded.
+    QuotaExceeded,
+    /// The server is in read-only mode.
+    Unavailable,
+    Internal,
+    /// Any status the server doesn't produce through `AppError`.
+    Other,
+}
+
+impl ErrorKind {
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::GONE => Self::Damaged,
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            StatusCode::UNPROCESSABLE_ENTITY => Self::Unprocessable,
+            StatusCode::INSUFFICIENT_STORAGE => Self::InsufficientStorage,
+            StatusCode::SERVICE_UNAVAILABLE => Self::Unavailable,
+            StatusCode::INTERNAL_SERVER_ERROR => Self::Internal,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The server answered with a non-2xx status.
+    #[error("server returned {status}: {message}")]
+    Api {
+        kind: ErrorKind,
+        status: StatusCode,
+        message: String,
+        violations: Vec<SchemaViolation>,
+    },
+
+    #[error("invalid server URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// The API error category, if this came from an error response.
+    pub fn kind(&self) -> Option<ErrorKind> {
+        match self {
+            Self::Api { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// Every way metadata failed its schemas, for `Unprocessable` errors.
+    pub fn violations(&self) -> &[SchemaViolation] {
+        match self {
+            Self::Api { violations, .. } => violations,
+            _ => &[],
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == Some(ErrorKind::NotFound)
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        self.kind() == Some(ErrorKind::Conflict)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,907 @@
+//! Typed async client for the cask HTTP API.
+//!
+//! ```no_run
+//! # async fn example() -> cask_client::Result<()> {
+//! let client = cask_client::Client::new("http://localhost:8080")?.with_token("cask_...");
+//! let artifact = client
+//!     .upload_file("myapp", "1.0.0", "target/myapp.tar.gz".as_ref(), None)
+//!     .await?;
+//! println!("uploaded {}", artifact.sha256);
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+mod retry;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use futures_util::{Stream, TryStreamExt};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Body, Method, RequestBuilder, Response, Url, header};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio_util::io::ReaderStream;
+
+pub use cask_types as types;
+pub use error::{Error, ErrorKind, Result};
+pub use retry::RetryPolicy;
+
+use types::{
+    ArtifactRow, Asset, AuditEntry, BreakdownQuery, ClientStats, CreateMetadataSchema,
+    CreateRetentionRule, CreateTokenRequest, CreateTokenResponse, ErrorResponse, FsckReport,
+    GcReport, ListQuery, Metadata, MetadataChange, MetadataSchema, PackageInfo, PackageSummary,
+    Quota, QuotaUsage, ReadOnlyMode, ReferrerStats, RetentionRule, SearchHit, SearchQuery,
+    SetQuotaRequest, StatsQuery, StatsResponse, TokenInfo, TopArtifact, TopQuery, UploadParams,
+};
+
+/// Sent with every request, so the server counts our downloads as `cask`.
+const USER_AGENT: &str = concat!("cask/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base: Url,
+    token: Option<String>,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Result<Self> {
+        let base = Url::parse(base_url).map_err(|_| Error::InvalidUrl(base_url.to_string()))?;
+        if base.cannot_be_a_base() {
+            return Err(Error::InvalidUrl(base_url.to_string()));
+        }
+
+        Ok(Self {
+            http: reqwest::Client::builder().user_agent(USER_AGENT).build()?,
+            base,
+            token: None,
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    /// Send `Authorization: Bearer <token>` with every request.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a preconfigured `reqwest::Client` (timeouts, proxies, TLS roots).
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn base_url(&self) -> &Url {
+        &self.base
+    }
+
+    // -- Artifacts --
+
+    /// Every artifact version, fetching page after page.
+    pub async fn list_artifacts(&self) -> Result<Vec<ArtifactRow>> {
+        self.list_all(None, &ListQuery::default()).await
+    }
+
+    /// Every version of `name`, newest first, fetching page after page.
+    pub async fn list_versions(&self, name: &str) -> Result<Vec<ArtifactRow>> {
+        self.list_all(Some(name), &ListQuery::default()).await
+    }
+
+    /// One page of artifact versions (of `name`, if given) matching `query`.
+    pub async fn list_page(
+        &self,
+        name: Option<&str>,
+        query: &ListQuery,
+    ) -> Result<Page<ArtifactRow>> {
+        let mut segments = vec!["v1", "artifacts"];
+        segments.extend(name);
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self
+                    .request(Method::GET, &segments)
+                    .query(query)
+                    .query(&query.meta_params()))
+            })
+            .await?;
+        let next_cursor = next_cursor(resp.headers());
+        Ok(Page {
+            items: resp.json().await?,
+            next_cursor,
+        })
+    }
+
+    /// Every artifact version (of `name`, if given) matching `query`,
+    /// starting at `query.cursor` and fetching page after page.
+    pub async fn list_all(
+        &self,
+        name: Option<&str>,
+        query: &ListQuery,
+    ) -> Result<Vec<ArtifactRow>> {
+        let mut query = query.clone();
+        let mut items = Vec::new();
+        loop {
+            let page = self.list_page(name, &query).await?;
+            items.extend(page.items);
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    /// Full-text search over names, filenames and metadata values, best
+    /// matches first.
+    pub async fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self.request(Method::GET, &["v1", "search"]).query(query))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Mark a version as yanked. It can still be downloaded.
+    pub async fn yank(&self, name: &str, version: &str) -> Result<ArtifactRow> {
+        let segments = ["v1", "artifacts", name, version, "yank"];
+        let resp = self
+            .execute(Method::PUT, || Ok(self.request(Method::PUT, &segments)))
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn unyank(&self, name: &str, version: &str) -> Result<ArtifactRow> {
+        let segments = ["v1", "artifacts", name, version, "yank"];
+        let resp = self
+            .execute(Method::DELETE, || {
+                Ok(self.request(Method::DELETE, &segments))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn upload_bytes(
+        &self,
+        name: &str,
+        version: &str,
+        filename: Option<&str>,
+        bytes: impl Into<Bytes>,
+    ) -> Result<ArtifactRow> {
+        let bytes = bytes.into();
+        let params = upload_params(filename);
+        let segments = ["v1", "artifacts", name, version];
+        let resp = self
+            .execute(Method::PUT, || {
+                Ok(self
+                    .request(Method::PUT, &segments)
+                    .query(&params)
+                    .body(bytes.clone()))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Upload a file, streaming it from disk. The file is reopened for each
+    /// retry.
+    pub async fn upload_file(
+        &self,
+        name: &str,
+        version: &str,
+        path: &Path,
+        filename: Option<&str>,
+    ) -> Result<ArtifactRow> {
+        self.upload_file_with_progress(name, version, path, filename, |_| {})
+            .await
+    }
+
+    /// Like `upload_file`, calling `progress` with the number of bytes sent
+    /// so far in the current attempt.
+    pub async fn upload_file_with_progress(
+        &self,
+        name: &str,
+        version: &str,
+        path: &Path,
+        filename: Option<&str>,
+        progress: impl Fn(u64) + Send + Sync + 'static,
+    ) -> Result<ArtifactRow> {
+        let segments = ["v1", "artifacts", name, version];
+        self.send_file(&segments, path, filename, None, progress)
+            .await
+    }
+
+    /// Like `upload_file_with_progress`, setting metadata in the same
+    /// request, so the version never exists without it.
+    pub async fn upload_file_with_metadata(
+        &self,
+        name: &str,
+        version: &str,
+        path: &Path,
+        filename: Option<&str>,
+        metadata: &serde_json::Map<String, serde_json::Value>,
+        progress: impl Fn(u64) + Send + Sync + 'static,
+    ) -> Result<ArtifactRow> {
+        let metadata = serde_json::Value::Object(metadata.clone()).to_string();
+        let segments = ["v1", "artifacts", name, version];
+        self.send_file(&segments, path, filename, Some(&metadata), progress)
+            .await
+    }
+
+    /// Stream a file as the request body, or as the `file` part of a form
+    /// alongside a `metadata` part if given.
+    async fn send_file<T: DeserializeOwned>(
+        &self,
+        segments: &[&str],
+        path: &Path,
+        filename: Option<&str>,
+        metadata: Option<&str>,
+        progress: impl Fn(u64) + Send + Sync + 'static,
+    ) -> Result<T> {
+        let params = upload_params(filename);
+        let progress = Arc::new(progress);
+
+        let resp = self
+            .execute(Method::PUT, || {
+                let file = std::fs::File::open(path)?;
+                let len = file.metadata()?.len();
+
+                let sent = Arc::new(AtomicU64::new(0));
+                let progress = Arc::clone(&progress);
+                progress(0);
+                let stream =
+                    ReaderStream::new(tokio::fs::File::from_std(file)).inspect_ok(move |chunk| {
+                        let total = sent.fetch_add(chunk.len() as u64, Ordering::Relaxed)
+                            + chunk.len() as u64;
+                        progress(total);
+                    });
+                let body = Body::wrap_stream(stream);
+
+                let request = self.request(Method::PUT, segments).query(&params);
+                Ok(match metadata {
+                    None => request.header(header::CONTENT_LENGTH, len).body(body),
+                    Some(metadata) => {
+                        let mut file = Part::stream_with_length(body, len);
+                        if let Some(file_name) = path.file_name() {
+                            file = file.file_name(file_name.to_string_lossy().into_owned());
+                        }
+                        let form = Form::new().part("file", file).part(
+                            "metadata",
+                            Part::text(metadata.to_string()).mime_str("application/json")?,
+                        );
+                        request.multipart(form)
+                    }
+                })
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Upload from an arbitrary byte stream. Streams can't be replayed, so
+    /// this is never retried.
+    pub async fn upload_stream<S, E>(
+        &self,
+        name: &str,
+        version: &str,
+        filename: Option<&str>,
+        content_length: Option<u64>,
+        stream: S,
+    ) -> Result<ArtifactRow>
+    where
+        S: Stream<Item = std::result::Result<Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+    {
+        let mut req = self
+            .request(Method::PUT, &["v1", "artifacts", name, version])
+            .query(&upload_params(filename))
+            .body(Body::wrap_stream(stream));
+        if let Some(len) = content_length {
+            req = req.header(header::CONTENT_LENGTH, len);
+        }
+        let resp = check(req.send().await?).await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Start a download. The body is streamed from the returned `Download`;
+    /// only the request itself is retried.
+    pub async fn download(&self, name: &str, version: &str) -> Result<Download> {
+        let segments = ["v1", "artifacts", name, version];
+        let response = self
+            .execute(Method::GET, || Ok(self.request(Method::GET, &segments)))
+            .await?;
+        Ok(Download::new(response))
+    }
+
+    pub async fn delete_artifact(&self, name: &str, version: &str) -> Result<()> {
+        self.delete(&["v1", "artifacts", name, version]).await
+    }
+
+    // -- Assets --
+
+    /// The files of a version, primary first.
+    pub async fn list_assets(&self, name: &str, version: &str) -> Result<Vec<Asset>> {
+        self.get_json(&["v1", "artifacts", name, version, "files"])
+            .await
+    }
+
+    /// Add a file to a version, creating the version with it as the primary
+    /// file if there's none yet. `progress` is called as for
+    /// `upload_file_with_progress`.
+    pub async fn upload_asset(
+        &self,
+        name: &str,
+        version: &str,
+        filename: &str,
+        path: &Path,
+        progress: impl Fn(u64) + Send + Sync + 'static,
+    ) -> Result<Asset> {
+        let segments = ["v1", "artifacts", name, version, "files", filename];
+        self.send_file(&segments, path, None, None, progress).await
+    }
+
+    pub async fn download_asset(
+        &self,
+        name: &str,
+        version: &str,
+        filename: &str,
+    ) -> Result<Download> {
+        let segments = ["v1", "artifacts", name, version, "files", filename];
+        let response = self
+            .execute(Method::GET, || Ok(self.request(Method::GET, &segments)))
+            .await?;
+        Ok(Download::new(response))
+    }
+
+    /// Delete a file of a version other than its primary one.
+    pub async fn delete_asset(&self, name: &str, version: &str, filename: &str) -> Result<()> {
+        self.delete(&["v1", "artifacts", name, version, "files", filename])
+            .await
+    }
+
+    // -- Metadata --
+
+    pub async fn get_metadata(&self, name: &str, version: &str) -> Result<Metadata> {
+        self.get_json(&["v1", "artifacts", name, version, "meta"])
+            .await
+    }
+
+    /// Upsert keys; keys not mentioned are left alone. Values may be any
+    /// JSON.
+    pub async fn set_metadata<T: Serialize + ?Sized>(
+        &self,
+        name: &str,
+        version: &str,
+        values: &T,
+    ) -> Result<()> {
+        let segments = ["v1", "artifacts", name, version, "meta"];
+        self.execute(Method::PUT, || {
+            Ok(self.request(Method::PUT, &segments).json(values))
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Apply a JSON merge patch: `null` removes a key and objects merge
+    /// into existing object values.
+    pub async fn patch_metadata(
+        &self,
+        name: &str,
+        version: &str,
+        patch: &serde_json::Value,
+    ) -> Result<()> {
+        let segments = ["v1", "artifacts", name, version, "meta"];
+        self.execute(Method::PATCH, || {
+            Ok(self
+                .request(Method::PATCH, &segments)
+                .header(header::CONTENT_TYPE, "application/merge-patch+json")
+                .body(patch.to_string()))
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_metadata(&self, name: &str, version: &str, key: &str) -> Result<()> {
+        self.delete(&["v1", "artifacts", name, version, "meta", key])
+            .await
+    }
+
+    /// The latest `limit` changes to a version's custom keys, newest first,
+    /// optionally only those to `key`.
+    pub async fn metadata_history(
+        &self,
+        name: &str,
+        version: &str,
+        key: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<MetadataChange>> {
+        let segments = ["v1", "artifacts", name, version, "history"];
+        let resp = self
+            .execute(Method::GET, || {
+                let mut req = self
+                    .request(Method::GET, &segments)
+                    .query(&[("limit", limit)]);
+                if let Some(key) = key {
+                    req = req.query(&[("key", key)]);
+                }
+                Ok(req)
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    // -- Packages --
+
+    /// One page of artifact names with their latest version, description
+    /// and downloads. `query.grouped` is implied.
+    pub async fn list_packages(&self, query: &ListQuery) -> Result<Page<PackageSummary>> {
+        let query = ListQuery {
+            grouped: true,
+            ..query.clone()
+        };
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self
+                    .request(Method::GET, &["v1", "artifacts"])
+                    .query(&query))
+            })
+            .await?;
+        let next_cursor = next_cursor(resp.headers());
+        Ok(Page {
+            items: resp.json().await?,
+            next_cursor,
+        })
+    }
+
+    pub async fn package(&self, name: &str) -> Result<PackageInfo> {
+        self.get_json(&["v1", "packages", name]).await
+    }
+
+    /// Upsert name-level key/value pairs; keys not mentioned are left alone.
+    pub async fn set_package_metadata<T: Serialize + ?Sized>(
+        &self,
+        name: &str,
+        values: &T,
+    ) -> Result<()> {
+        let segments = ["v1", "packages", name, "meta"];
+        self.execute(Method::PUT, || {
+            Ok(self.request(Method::PUT, &segments).json(values))
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_package_metadata(&self, name: &str, key: &str) -> Result<()> {
+        self.delete(&["v1", "packages", name, "meta", key]).await
+    }
+
+    /// The package's README (Markdown).
+    pub async fn readme(&self, name: &str) -> Result<String> {
+        let segments = ["v1", "packages", name, "readme"];
+        let resp = self
+            .execute(Method::GET, || Ok(self.request(Method::GET, &segments)))
+            .await?;
+        Ok(resp.text().await?)
+    }
+
+    pub async fn set_readme(&self, name: &str, readme: &str) -> Result<()> {
+        let segments = ["v1", "packages", name, "readme"];
+        self.execute(Method::PUT, || {
+            Ok(self
+                .request(Method::PUT, &segments)
+                .header(header::CONTENT_TYPE, "text/markdown; charset=utf-8")
+                .body(readme.to_string()))
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_readme(&self, name: &str) -> Result<()> {
+        self.delete(&["v1", "packages", name, "readme"]).await
+    }
+
+    // -- Tokens --
+
+    pub async fn create_token(&self, req: &CreateTokenRequest) -> Result<CreateTokenResponse> {
+        let resp = self
+            .execute(Method::POST, || {
+                Ok(self.request(Method::POST, &["v1", "tokens"]).json(req))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn list_tokens(&self) -> Result<Vec<TokenInfo>> {
+        self.get_json(&["v1", "tokens"]).await
+    }
+
+    pub async fn revoke_token(&self, id: &str) -> Result<()> {
+        self.delete(&["v1", "tokens", id]).await
+    }
+
+    // -- Stats --
+
+    pub async fn version_stats(&self, name: &str, version: &str) -> Result<StatsResponse> {
+        self.get_json(&["v1", "artifacts", name, version, "stats"])
+            .await
+    }
+
+    pub async fn artifact_stats(&self, name: &str) -> Result<StatsResponse> {
+        self.get_json(&["v1", "artifacts", name, "stats"]).await
+    }
+
+    /// Download stats for `name` (or one `version` of it) filtered and
+    /// broken down as described by `query`.
+    pub async fn stats(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        query: &StatsQuery,
+    ) -> Result<StatsResponse> {
+        let mut segments = vec!["v1", "artifacts", name];
+        segments.extend(version);
+        segments.push("stats");
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self.request(Method::GET, &segments).query(query))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// The most downloaded artifacts, most downloaded first.
+    pub async fn top_artifacts(&self, query: &TopQuery) -> Result<Vec<TopArtifact>> {
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self
+                    .request(Method::GET, &["v1", "stats", "top"])
+                    .query(query))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Downloads grouped by client family, most downloaded first.
+    pub async fn client_stats(&self, query: &BreakdownQuery) -> Result<Vec<ClientStats>> {
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self
+                    .request(Method::GET, &["v1", "stats", "clients"])
+                    .query(query))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Downloads grouped by referring host, most downloaded first.
+    pub async fn referrer_stats(&self, query: &BreakdownQuery) -> Result<Vec<ReferrerStats>> {
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self
+                    .request(Method::GET, &["v1", "stats", "referrers"])
+                    .query(query))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    // -- Admin --
+
+    /// Verify stored files against the database; with `repair`, quarantine
+    /// orphans and mark broken artifacts. Requires an admin token.
+    pub async fn fsck(&self, repair: bool) -> Result<FsckReport> {
+        let resp = self
+            .execute(Method::POST, || {
+                Ok(self
+                    .request(Method::POST, &["v1", "admin", "fsck"])
+                    .query(&[("repair", repair)]))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn create_retention_rule(&self, rule: &CreateRetentionRule) -> Result<RetentionRule> {
+        let resp = self
+            .execute(Method::POST, || {
+                Ok(self
+                    .request(Method::POST, &["v1", "admin", "retention"])
+                    .json(rule))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn list_retention_rules(&self) -> Result<Vec<RetentionRule>> {
+        self.get_json(&["v1", "admin", "retention"]).await
+    }
+
+    pub async fn delete_retention_rule(&self, id: &str) -> Result<()> {
+        self.delete(&["v1", "admin", "retention", id]).await
+    }
+
+    /// Register a metadata schema. Requires an admin token.
+    pub async fn create_schema(&self, schema: &CreateMetadataSchema) -> Result<MetadataSchema> {
+        let resp = self
+            .execute(Method::POST, || {
+                Ok(self
+                    .request(Method::POST, &["v1", "admin", "schemas"])
+                    .json(schema))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn list_schemas(&self) -> Result<Vec<MetadataSchema>> {
+        self.get_json(&["v1", "admin", "schemas"]).await
+    }
+
+    pub async fn delete_schema(&self, id: &str) -> Result<()> {
+        self.delete(&["v1", "admin", "schemas", id]).await
+    }
+
+    /// Delete the versions selected by the retention rules, or with
+    /// `dry_run` only report them. Requires an admin token.
+    pub async fn gc(&self, dry_run: bool) -> Result<GcReport> {
+        let resp = self
+            .execute(Method::POST, || {
+                Ok(self
+                    .request(Method::POST, &["v1", "admin", "gc"])
+                    .query(&[("dry_run", dry_run)]))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// The most recent `limit` audit log entries, newest first.
+    pub async fn audit_log(&self, limit: u32) -> Result<Vec<AuditEntry>> {
+        let resp = self
+            .execute(Method::GET, || {
+                Ok(self
+                    .request(Method::GET, &["v1", "admin", "audit"])
+                    .query(&[("limit", limit)]))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    /// Create or replace the quota for `req.scope` and `req.target`.
+    pub async fn set_quota(&self, req: &SetQuotaRequest) -> Result<Quota> {
+        let resp = self
+            .execute(Method::POST, || {
+                Ok(self
+                    .request(Method::POST, &["v1", "admin", "quotas"])
+                    .json(req))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn list_quotas(&self) -> Result<Vec<Quota>> {
+        self.get_json(&["v1", "admin", "quotas"]).await
+    }
+
+    pub async fn delete_quota(&self, id: &str) -> Result<()> {
+        self.delete(&["v1", "admin", "quotas", id]).await
+    }
+
+    /// Consumption against each quota visible to the current token.
+    pub async fn usage(&self) -> Result<Vec<QuotaUsage>> {
+        self.get_json(&["v1", "usage"]).await
+    }
+
+    pub async fn read_only(&self) -> Result<ReadOnlyMode> {
+        self.get_json(&["v1", "admin", "read-only"]).await
+    }
+
+    /// Turn read-only (maintenance) mode on or off. Requires an admin token.
+    pub async fn set_read_only(&self, mode: &ReadOnlyMode) -> Result<ReadOnlyMode> {
+        let resp = self
+            .execute(Method::PUT, || {
+                Ok(self
+                    .request(Method::PUT, &["v1", "admin", "read-only"])
+                    .json(mode))
+            })
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    pub async fn health(&self) -> Result<()> {
+        self.execute(Method::GET, || Ok(self.request(Method::GET, &["health"])))
+            .await?;
+        Ok(())
+    }
+
+    // -- Plumbing --
+
+    /// Build a URL from path segments, percent-encoding each one.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("base URL validated in Client::new")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        let req = self.http.request(method, self.url(segments));
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    /// Send the request built by `build`, rebuilding and resending it
+    /// according to the retry policy.
+    async fn execute(
+        &self,
+        method: Method,
+        build: impl Fn() -> Result<RequestBuilder>,
+    ) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let retries_left = attempt <= self.retry.max_retries;
+
+            match build()?.send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(resp),
+                Ok(resp) => {
+                    if retries_left
+                        && let Some(after) = self.retry.should_retry_response(&method, &resp)
+                    {
+                        tokio::time::sleep(after.unwrap_or_else(|| self.retry.backoff(attempt)))
+                            .await;
+                        continue;
+                    }
+                    return Err(api_error(resp).await);
+                }
+                Err(e) => {
+                    if retries_left && self.retry.should_retry_error(&method, &e) {
+                        tokio::time::sleep(self.retry.backoff(attempt)).await;
+                        continue;
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T> {
+        let resp = self
+            .execute(Method::GET, || Ok(self.request(Method::GET, segments)))
+            .await?;
+        Ok(resp.json().await?)
+    }
+
+    async fn delete(&self, segments: &[&str]) -> Result<()> {
+        self.execute(
+            Method::DELETE,
+            || Ok(self.request(Method::DELETE, segments)),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// One page of a paginated listing.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next page, or `None` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// The `cursor` parameter of the `rel="next"` target in a `Link` header.
+fn next_cursor(headers: &header::HeaderMap) -> Option<String> {
+    let link = headers.get(header::LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (target, params) = part.trim().split_once(';')?;
+        if !params.split(';').any(|p| p.trim() == "rel=\"next\"") {
+            return None;
+        }
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let (_, query) = target.split_once('?')?;
+        query
+            .split('&')
+            .find_map(|p| p.strip_prefix("cursor="))
+            .map(str::to_string)
+    })
+}
+
+/// An in-progress artifact download.
+pub struct Download {
+    response: Response,
+    filename: Option<String>,
+    sha256: Option<String>,
+}
+
+impl Download {
+    fn new(response: Response) -> Self {
+        let filename = attachment_filename(response.headers());
+        let sha256 = response
+            .headers()
+            .get(types::SHA256_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        Self {
+            response,
+            filename,
+            sha256,
+        }
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.response.content_length()
+    }
+
+    /// Filename the server suggested, reduced to its last path component.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// SHA-256 (hex) the server recorded for the file it is sending, to
+    /// check the body against.
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    /// Next chunk of the body, or `None` once it's complete.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        Ok(self.response.chunk().await?)
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes>> {
+        self.response.bytes_stream().map_err(Error::from)
+    }
+}
+
+fn upload_params(filename: Option<&str>) -> UploadParams {
+    UploadParams {
+        filename: filename.map(str::to_string),
+    }
+}
+
+async fn check(resp: Response) -> Result<Response> {
+    if resp.status().is_success() {
+        Ok(resp)
+    } else {
+        Err(api_error(resp).await)
+    }
+}
+
+/// Turn an error response into `Error::Api`, using the server's
+/// `{"error": ...}` message when there is one.
+async fn api_error(resp: Response) -> Error {
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    let (message, code, violations) = match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(e) => (e.error, e.code, e.violations),
+        Err(_) => (body, None, Vec::new()),
+    };
+    let kind = match code.as_deref() {
+        Some(types::QUOTA_EXCEEDED) => ErrorKind::QuotaExceeded,
+        _ => ErrorKind::from_status(status),
+    };
+    Error::Api {
+        kind,
+        status,
+        message,
+        violations,
+    }
+}
+
+fn attachment_filename(headers: &header::HeaderMap) -> Option<String> {
+    let value = headers.get(header::CONTENT_DISPOSITION)?.to_str().ok()?;
+    let filename = value.split("filename=").nth(1)?.trim_matches('"');
+    let filename = filename.rsplit(['/', '\\']).next()?;
+    if filename.is_empty() || filename == "." || filename == ".." {
+        return None;
+    }
+    Some(filename.to_string())
+}
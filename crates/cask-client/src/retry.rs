@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use reqwest::{Method, Response, StatusCode, header};
+
+/// Exponential backoff for transient failures.
+///
+/// Connection errors are retried for every request since nothing reached the
+/// server. Timeouts are retried only for idempotent methods (`GET`, `HEAD`,
+/// `PUT`, `DELETE`), as the server may have received the request and acted
+/// on it. `429`, `502`, `503` and `504` responses are retried for all
+/// methods except `POST`, which isn't safe to repeat. A `Retry-After` longer
+/// than `max_backoff` (e.g. a server in read-only mode) is not waited out;
+/// the error is returned instead.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 1).
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    pub(crate) fn should_retry_error(&self, method: &Method, err: &reqwest::Error) -> bool {
+        err.is_connect() || (err.is_timeout() && is_idempotent(method))
+    }
+
+    /// Whether to retry a response, and how long the server asked us to wait.
+    pub(crate) fn should_retry_response(
+        &self,
+        method: &Method,
+        resp: &Response,
+    ) -> Option<Option<Duration>> {
+        if *method == Method::POST {
+            return None;
+        }
+        match resp.status() {
+            StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => match retry_after(resp) {
+                Some(after) if after > self.max_backoff => None,
+                after => Some(after),
+            },
+            _ => None,
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    [Method::GET, Method::HEAD, Method::PUT, Method::DELETE].contains(method)
+}
+
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let secs: u64 = resp
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs))
+}
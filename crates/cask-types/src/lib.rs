@@ -0,0 +1,622 @@
+//! Request and response bodies of the cask HTTP API, shared by the server
+//! and its clients.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// [`ErrorResponse::code`] of a `413` for an upload that would go over a
+/// quota, as opposed to one over the maximum upload size.
+pub const QUOTA_EXCEEDED: &str = "quota_exceeded";
+
+/// Response header of a download holding the SHA-256 (hex) the server
+/// recorded for the file being sent.
+pub const SHA256_HEADER: &str = "x-cask-sha256";
+
+/// Body of every non-2xx response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// Machine-readable reason where the status alone is ambiguous, e.g.
+    /// [`QUOTA_EXCEEDED`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Every schema check that failed, for `422` responses to metadata
+    /// changes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<SchemaViolation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct ArtifactRow {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub filename: String,
+    pub sha256: String,
+    pub size: i64,
+    pub created_at: String,
+    /// When the version was yanked, if it is. Yanked versions can still be
+    /// downloaded but are marked as not for new use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yanked_at: Option<String>,
+    /// Every file of the version, the primary one (described by `filename`,
+    /// `sha256` and `size`) first. Filled in by listings and uploads.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(feature = "sqlx", sqlx(skip))]
+    pub assets: Vec<Asset>,
+}
+
+/// A named file of a version, as listed by
+/// `GET /v1/artifacts/{name}/{version}/files`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Asset {
+    pub filename: String,
+    pub sha256: String,
+    pub size: i64,
+    pub created_at: String,
+    /// Whether this is the file served at `/v1/artifacts/{name}/{version}`.
+    /// It can only be removed with the version.
+    pub primary: bool,
+}
+
+/// Query string of `GET /v1/artifacts` and `GET /v1/artifacts/{name}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ListQuery {
+    /// Page size (default 100, at most 1000).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Opaque cursor from the previous page's `Link: <...>; rel="next"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<ListSort>,
+    /// Defaults to `asc` for `name` and `desc` otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+    /// Only names starting with this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// Only versions created at or after this date or UTC timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<String>,
+    /// Only versions created before this date or UTC timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<i64>,
+    /// Only yanked (`true`) or not yanked (`false`) versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub yanked: Option<bool>,
+    /// Only versions with this metadata key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tagged: Option<String>,
+    /// Metadata predicates as `(key, value)`, sent as `meta.<key>=<value>`
+    /// (see [`ListQuery::meta_params`]). Versions must match every key; a key
+    /// given several times matches any of its values. A value of `>N`,
+    /// `>=N`, `<N` or `<=N` compares numerically instead, and must hold
+    /// alongside the key's other predicates.
+    #[serde(skip)]
+    pub meta: Vec<(String, String)>,
+    /// Return one [`PackageSummary`] per name instead of versions. Only
+    /// `prefix`, `limit`, `cursor` and `order` apply.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub grouped: bool,
+}
+
+impl ListQuery {
+    /// `meta` as query string pairs.
+    pub fn meta_params(&self) -> Vec<(String, &str)> {
+        self.meta
+            .iter()
+            .map(|(key, value)| (format!("meta.{}", key), value.as_str()))
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListSort {
+    /// By name, newest version first within a name (the default).
+    Name,
+    Created,
+    Size,
+    /// By name, then by semantic version. Versions that aren't semver sort
+    /// before those that are.
+    Semver,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// One artifact name, as listed by `GET /v1/artifacts?grouped=true`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct PackageSummary {
+    pub name: String,
+    /// Highest non-yanked semver version, or the newest non-yanked version if
+    /// none are semver.
+    pub latest_version: Option<String>,
+    /// The package's `description` metadata.
+    pub description: Option<String>,
+    pub versions: i64,
+    /// Downloads across all versions.
+    pub downloads: i64,
+    /// When the newest version was uploaded.
+    pub updated_at: Option<String>,
+}
+
+/// `GET /v1/packages/{name}`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PackageInfo {
+    #[serde(flatten)]
+    pub summary: PackageSummary,
+    /// Name-level metadata, e.g. `description`, `homepage`, `owner` and
+    /// `license`. Values are arbitrary JSON, like version metadata.
+    pub metadata: BTreeMap<String, serde_json::Value>,
+    pub has_readme: bool,
+}
+
+/// Query string of `GET /v1/search`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// Words to look for; each must match the start of a word in the name,
+    /// a filename or a metadata value. Words like `tests>=100` instead compare
+    /// a numeric metadata value.
+    pub q: String,
+    /// Number of hits to return (default 20, at most 100).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Hits to skip, for paging.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+/// An artifact version matching a search, best matches first.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct SearchHit {
+    #[serde(flatten)]
+    #[cfg_attr(feature = "sqlx", sqlx(flatten))]
+    pub artifact: ArtifactRow,
+    /// Relevance; higher is better.
+    pub score: f64,
+    /// HTML-escaped excerpt of the best matching field, with matches wrapped
+    /// in `<mark>` tags.
+    pub snippet: String,
+}
+
+/// Query string of `PUT /v1/artifacts/{name}/{version}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UploadParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+/// `sha256` and `created_at` plus any custom keys, as returned by
+/// `GET /v1/artifacts/{name}/{version}/meta`. Custom values are arbitrary
+/// JSON.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Metadata {
+    pub sha256: String,
+    pub created_at: String,
+    #[serde(flatten)]
+    pub custom: BTreeMap<String, serde_json::Value>,
+}
+
+/// One change to a custom metadata key, as returned by
+/// `GET /v1/artifacts/{name}/{version}/history`. `old_value` is null
+/// when the key was added and `new_value` is null when it was removed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetadataChange {
+    pub id: i64,
+    pub at: String,
+    /// ID of the token that made the change.
+    pub actor: String,
+    pub key: String,
+    pub old_value: Option<serde_json::Value>,
+    pub new_value: Option<serde_json::Value>,
+}
+
+/// Query string of the stats endpoints. `from` and `to` are ISO 8601 dates
+/// or UTC timestamps; `from` is inclusive and `to` exclusive.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StatsQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Also return counts bucketed by this interval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<StatsInterval>,
+    /// Also return counts per version (artifact-level stats only).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub by_version: bool,
+    /// Also return counts per asset (version-level stats only).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub by_asset: bool,
+    /// Leave out downloads by known bots and health checkers.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_bots: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsInterval {
+    Hour,
+    Day,
+    /// Weeks start on Monday.
+    Week,
+    Month,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StatsResponse {
+    pub downloads: i64,
+    #[serde(default)]
+    pub unique_ips: i64,
+    /// Buckets with at least one download, oldest first. Present when an
+    /// `interval` was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series: Option<Vec<StatsBucket>>,
+    /// Most downloaded first. Present when `by_version` was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Vec<VersionStats>>,
+    /// Most downloaded first. Present when `by_asset` was requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Vec<AssetStats>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct StatsBucket {
+    /// Start of the bucket, `YYYY-MM-DD HH:MM:SS` UTC.
+    pub start: String,
+    pub downloads: i64,
+    pub unique_ips: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct VersionStats {
+    pub version: String,
+    pub downloads: i64,
+    pub unique_ips: i64,
+}
+
+/// Downloads of an asset that the version still has.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct AssetStats {
+    pub filename: String,
+    pub downloads: i64,
+    pub unique_ips: i64,
+}
+
+/// Query string of `GET /v1/stats/top`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TopQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Number of artifacts to return (default 10, at most 100).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Leave out downloads by known bots and health checkers.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_bots: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct TopArtifact {
+    pub name: String,
+    pub downloads: i64,
+    pub unique_ips: i64,
+}
+
+/// Query string of `GET /v1/stats/clients` and `GET /v1/stats/referrers`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BreakdownQuery {
+    /// Only count downloads of this artifact name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Only count downloads of this version (requires `name`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Leave out downloads by known bots and health checkers.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_bots: bool,
+}
+
+/// Downloads by one client family, e.g. `curl`, `pip` or `browser`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct ClientStats {
+    pub client: String,
+    /// Whether the family is a known bot or health checker.
+    pub bot: bool,
+    pub downloads: i64,
+    pub unique_ips: i64,
+}
+
+/// Downloads referred from one host; `None` for downloads without a
+/// `Referer`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct ReferrerStats {
+    pub referrer: Option<String>,
+    pub downloads: i64,
+    pub unique_ips: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateTokenRequest {
+    pub label: String,
+    #[serde(default)]
+    pub is_admin: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateTokenResponse {
+    pub id: String,
+    pub token: String,
+    pub label: String,
+    pub is_admin: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct TokenInfo {
+    pub id: String,
+    pub label: String,
+    pub is_admin: bool,
+    pub expires_at: Option<String>,
+    pub created_at: String,
+}
+
+/// Result of an integrity check of stored artifacts against the database.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FsckReport {
+    /// Number of assets checked.
+    pub checked: usize,
+    pub problems: Vec<FsckProblem>,
+    /// Whether repairs (quarantine, marking broken) were applied.
+    pub repaired: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FsckProblem {
+    pub kind: FsckProblemKind,
+    /// Asset ID, which is also its file's name in `artifacts/`.
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The asset's filename, unless the file is an orphan.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckProblemKind {
+    /// The asset's file does not exist.
+    Missing,
+    /// The file's size differs from `assets.size`.
+    SizeMismatch,
+    /// The file's SHA-256 differs from `assets.sha256`.
+    ChecksumMismatch,
+    /// A file in `artifacts/` with no matching row.
+    Orphan,
+}
+
+/// A retention rule for artifacts whose name matches `pattern` (a glob).
+///
+/// A version is deleted when every set deletion criterion selects it (it is
+/// not among the newest `keep_last`, it is older than `max_age_days`) and no
+/// protection applies (downloaded within `keep_downloaded_days`, has the
+/// metadata key `keep_tagged`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct RetentionRule {
+    pub id: String,
+    pub pattern: String,
+    pub keep_last: Option<i64>,
+    pub max_age_days: Option<i64>,
+    pub keep_downloaded_days: Option<i64>,
+    pub keep_tagged: Option<String>,
+    pub created_at: String,
+}
+
+/// Body of `POST /v1/admin/retention`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CreateRetentionRule {
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_downloaded_days: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_tagged: Option<String>,
+}
+
+/// Result of a garbage collection run, or what one would delete.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub deleted: Vec<GcCandidate>,
+    /// Total size of `deleted` in bytes.
+    pub freed_bytes: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GcCandidate {
+    pub name: String,
+    pub version: String,
+    pub size: i64,
+    /// The rule that selected this version.
+    pub rule_id: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct AuditEntry {
+    pub id: String,
+    pub at: String,
+    /// Token ID, or `gc` for scheduled garbage collection.
+    pub actor: String,
+    pub action: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(rename_all = "snake_case"))]
+pub enum QuotaScope {
+    /// Artifacts whose name starts with the target.
+    Prefix,
+    /// Artifacts uploaded with the target token ID.
+    Token,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Quota {
+    pub id: String,
+    pub scope: QuotaScope,
+    pub target: String,
+    pub max_bytes: Option<i64>,
+    pub max_artifacts: Option<i64>,
+    pub created_at: String,
+}
+
+/// Body of `POST /v1/admin/quotas`. Replaces any quota with the same scope
+/// and target.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SetQuotaRequest {
+    pub scope: QuotaScope,
+    pub target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_artifacts: Option<i64>,
+}
+
+/// A quota and current consumption against it, as returned by
+/// `GET /v1/usage`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuotaUsage {
+    #[serde(flatten)]
+    pub quota: Quota,
+    pub used_bytes: i64,
+    pub used_artifacts: i64,
+}
+
+/// A metadata schema, enforced on versions of names matching `pattern`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetadataSchema {
+    pub id: String,
+    pub pattern: String,
+    /// A JSON Schema for the object of custom metadata keys.
+    pub schema: serde_json::Value,
+    pub created_at: String,
+}
+
+/// Body of `POST /v1/admin/schemas`: a JSON Schema in `schema`, or a key
+/// spec in `keys` that is turned into one.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CreateMetadataSchema {
+    /// Artifact name glob, e.g. `myapp-*`.
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeySpec>,
+    /// With `keys`, reject keys that aren't listed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub closed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct KeySpec {
+    /// Any type if unset.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<KeyType>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+}
+
+/// One way in which metadata fails a schema.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub schema_id: String,
+    pub pattern: String,
+    /// The offending key, unless the failure is about the metadata as a
+    /// whole.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// JSON Pointer to the failing value within the metadata.
+    pub path: String,
+    pub message: String,
+}
+
+/// Read-only (maintenance) mode, as set by `PUT /v1/admin/read-only`. While
+/// enabled, mutating requests are refused with `503` and `Retry-After`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReadOnlyMode {
+    pub enabled: bool,
+    /// Shown in the error returned to refused requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Seconds clients are asked to wait before retrying.
+    #[serde(default = "default_retry_after")]
+    pub retry_after: u64,
+}
+
+impl Default for ReadOnlyMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reason: None,
+            retry_after: default_retry_after(),
+        }
+    }
+}
+
+fn default_retry_after() -> u64 {
+    60
+}
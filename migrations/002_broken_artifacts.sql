@@ -0,0 +1,4 @@
+-- Set by `cask fsck --repair` when an artifact's file is missing or fails
+-- its checksum; downloads of broken artifacts are refused.
+ALTER TABLE artifacts ADD COLUMN broken_at TEXT;
+ALTER TABLE artifacts ADD COLUMN broken_reason TEXT;
@@ -0,0 +1,10 @@
+-- Latest background scrub result per artifact.
+CREATE TABLE IF NOT EXISTS scrub_results (
+    artifact_id TEXT PRIMARY KEY REFERENCES artifacts(id) ON DELETE CASCADE,
+    checked_at  TEXT NOT NULL DEFAULT (datetime('now')),
+    ok          INTEGER NOT NULL,
+    problem     TEXT,
+    detail      TEXT
+);
+
+CREATE INDEX idx_scrub_results_checked ON scrub_results(checked_at);
@@ -0,0 +1,26 @@
+-- Retention rules enforced by the garbage collector. A version of an
+-- artifact whose name matches `pattern` is deleted when every set deletion
+-- criterion (`keep_last`, `max_age_days`) selects it and no protection
+-- (`keep_downloaded_days`, `keep_tagged`) applies.
+CREATE TABLE IF NOT EXISTS retention_rules (
+    id                   TEXT PRIMARY KEY,
+    pattern              TEXT NOT NULL,
+    keep_last            INTEGER,
+    max_age_days         INTEGER,
+    keep_downloaded_days INTEGER,
+    keep_tagged          TEXT,
+    created_at           TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Record of destructive operations. Rows outlive the artifacts they refer to.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id      TEXT PRIMARY KEY,
+    at      TEXT NOT NULL DEFAULT (datetime('now')),
+    actor   TEXT NOT NULL,
+    action  TEXT NOT NULL,
+    name    TEXT,
+    version TEXT,
+    detail  TEXT
+);
+
+CREATE INDEX idx_audit_log_at ON audit_log(at);
@@ -0,0 +1,17 @@
+-- Token that uploaded each artifact, for per-token quotas. NULL for
+-- artifacts uploaded before this column existed.
+ALTER TABLE artifacts ADD COLUMN uploaded_by TEXT;
+
+CREATE INDEX idx_artifacts_uploaded_by ON artifacts(uploaded_by);
+
+-- Byte and artifact-count limits on artifact names starting with `target`
+-- (scope 'prefix') or on uploads made by token `target` (scope 'token').
+CREATE TABLE IF NOT EXISTS quotas (
+    id            TEXT PRIMARY KEY,
+    scope         TEXT NOT NULL CHECK (scope IN ('prefix', 'token')),
+    target        TEXT NOT NULL,
+    max_bytes     INTEGER,
+    max_artifacts INTEGER,
+    created_at    TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE(scope, target)
+);
@@ -0,0 +1,21 @@
+-- Daily per-artifact aggregates of download_stats rows older than the
+-- rollup age. `unique_ips` counts distinct IPs within the day only.
+CREATE TABLE IF NOT EXISTS download_stats_daily (
+    artifact_id TEXT NOT NULL REFERENCES artifacts(id) ON DELETE CASCADE,
+    day         TEXT NOT NULL,
+    downloads   INTEGER NOT NULL,
+    unique_ips  INTEGER NOT NULL,
+    PRIMARY KEY (artifact_id, day)
+);
+
+CREATE INDEX idx_download_stats_downloaded_at ON download_stats(downloaded_at);
+
+-- Raw and rolled-up downloads in one shape, for stats queries. Rolled-up
+-- days appear as one row at midnight; their IPs are not known individually,
+-- so their unique counts are carried in `rolled_unique_ips`.
+CREATE VIEW download_events AS
+    SELECT artifact_id, downloaded_at, 1 AS downloads, ip, 0 AS rolled_unique_ips
+    FROM download_stats
+    UNION ALL
+    SELECT artifact_id, day || ' 00:00:00', downloads, NULL, unique_ips
+    FROM download_stats_daily;
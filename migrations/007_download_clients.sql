@@ -0,0 +1,38 @@
+-- Who fetched an artifact: the raw user-agent, the client family it was
+-- normalized to, whether that family is a known bot or health checker, and
+-- the host of the referring page.
+ALTER TABLE download_stats ADD COLUMN user_agent TEXT;
+ALTER TABLE download_stats ADD COLUMN client TEXT NOT NULL DEFAULT 'unknown';
+ALTER TABLE download_stats ADD COLUMN bot INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE download_stats ADD COLUMN referrer TEXT;
+
+-- Rolled-up days keep the client family and referrer, so the daily table is
+-- rebuilt with them in its key. No referrer is stored as '' to keep the key
+-- free of NULLs.
+DROP VIEW download_events;
+
+CREATE TABLE download_stats_daily_new (
+    artifact_id TEXT NOT NULL REFERENCES artifacts(id) ON DELETE CASCADE,
+    day         TEXT NOT NULL,
+    client      TEXT NOT NULL DEFAULT 'unknown',
+    bot         INTEGER NOT NULL DEFAULT 0,
+    referrer    TEXT NOT NULL DEFAULT '',
+    downloads   INTEGER NOT NULL,
+    unique_ips  INTEGER NOT NULL,
+    PRIMARY KEY (artifact_id, day, client, referrer)
+);
+
+INSERT INTO download_stats_daily_new (artifact_id, day, downloads, unique_ips)
+    SELECT artifact_id, day, downloads, unique_ips FROM download_stats_daily;
+
+DROP TABLE download_stats_daily;
+ALTER TABLE download_stats_daily_new RENAME TO download_stats_daily;
+
+CREATE VIEW download_events AS
+    SELECT artifact_id, downloaded_at, 1 AS downloads, ip, 0 AS rolled_unique_ips,
+           client, bot, referrer
+    FROM download_stats
+    UNION ALL
+    SELECT artifact_id, day || ' 00:00:00', downloads, NULL, unique_ips,
+           client, bot, NULLIF(referrer, '')
+    FROM download_stats_daily;
@@ -0,0 +1,10 @@
+ALTER TABLE artifacts ADD COLUMN yanked_at TEXT;
+
+-- Sortable form of the version for semver ordering. Computed by the server
+-- (`artifacts::version_key`) on upload and for older rows at startup.
+ALTER TABLE artifacts ADD COLUMN version_key TEXT;
+
+CREATE INDEX idx_artifacts_name_created ON artifacts(name, created_at);
+CREATE INDEX idx_artifacts_name_version_key ON artifacts(name, version_key);
+CREATE INDEX idx_artifacts_created ON artifacts(created_at);
+CREATE INDEX idx_artifacts_size ON artifacts(size);
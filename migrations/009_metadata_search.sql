@@ -0,0 +1,2 @@
+-- Finding artifacts by metadata value, e.g. the build of a commit.
+CREATE INDEX idx_artifact_metadata_key_value ON artifact_metadata(key, value);
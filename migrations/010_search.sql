@@ -0,0 +1,60 @@
+-- Full-text index over artifact names, filenames and metadata values, one
+-- row per artifact version, kept in sync by the triggers below. Search
+-- snippets mark matches with the control characters U+0002 and U+0003, so
+-- the index drops them from the text it stores. Otherwise one in a name,
+-- filename or metadata value would turn into a stray `<mark>` tag.
+CREATE VIRTUAL TABLE artifact_search USING fts5(
+    name,
+    filename,
+    metadata,
+    artifact_id UNINDEXED,
+    prefix = '2 3'
+);
+
+INSERT INTO artifact_search (name, filename, metadata, artifact_id)
+    SELECT replace(replace(a.name, char(2), ''), char(3), ''),
+           replace(replace(a.filename, char(2), ''), char(3), ''),
+           replace(replace(COALESCE((SELECT group_concat(m.value, ' ') FROM artifact_metadata m
+                                     WHERE m.artifact_id = a.id), ''), char(2), ''), char(3), ''),
+           a.id
+    FROM artifacts a;
+
+CREATE TRIGGER artifacts_search_insert AFTER INSERT ON artifacts BEGIN
+    INSERT INTO artifact_search (name, filename, metadata, artifact_id)
+        VALUES (replace(replace(new.name, char(2), ''), char(3), ''),
+                replace(replace(new.filename, char(2), ''), char(3), ''),
+                '', new.id);
+END;
+
+CREATE TRIGGER artifacts_search_update AFTER UPDATE OF name, filename ON artifacts BEGIN
+    UPDATE artifact_search
+        SET name = replace(replace(new.name, char(2), ''), char(3), ''),
+            filename = replace(replace(new.filename, char(2), ''), char(3), '')
+        WHERE artifact_id = new.id;
+END;
+
+CREATE TRIGGER artifacts_search_delete AFTER DELETE ON artifacts BEGIN
+    DELETE FROM artifact_search WHERE artifact_id = old.id;
+END;
+
+-- Metadata changes rebuild the artifact's whole metadata column.
+CREATE TRIGGER metadata_search_insert AFTER INSERT ON artifact_metadata BEGIN
+    UPDATE artifact_search SET metadata = replace(replace(COALESCE(
+        (SELECT group_concat(value, ' ') FROM artifact_metadata
+         WHERE artifact_id = new.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = new.artifact_id;
+END;
+
+CREATE TRIGGER metadata_search_update AFTER UPDATE ON artifact_metadata BEGIN
+    UPDATE artifact_search SET metadata = replace(replace(COALESCE(
+        (SELECT group_concat(value, ' ') FROM artifact_metadata
+         WHERE artifact_id = new.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = new.artifact_id;
+END;
+
+CREATE TRIGGER metadata_search_delete AFTER DELETE ON artifact_metadata BEGIN
+    UPDATE artifact_search SET metadata = replace(replace(COALESCE(
+        (SELECT group_concat(value, ' ') FROM artifact_metadata
+         WHERE artifact_id = old.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = old.artifact_id;
+END;
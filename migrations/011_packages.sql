@@ -0,0 +1,17 @@
+-- Name-level (package) data describing every version of a name: free-form
+-- metadata such as description, homepage, owner and license, and a README.
+-- A row is created the first time either is set. Metadata values are JSON,
+-- like artifact_metadata.value.
+CREATE TABLE IF NOT EXISTS packages (
+    name       TEXT PRIMARY KEY,
+    readme     TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS package_metadata (
+    name  TEXT NOT NULL REFERENCES packages(name) ON DELETE CASCADE,
+    key   TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (name, key)
+);
@@ -0,0 +1,30 @@
+-- Metadata values become JSON documents. The search index keeps strings as
+-- their text rather than their JSON encoding, so snippets don't carry quotes,
+-- and still drops match marks as in 010.
+DROP TRIGGER metadata_search_insert;
+DROP TRIGGER metadata_search_update;
+DROP TRIGGER metadata_search_delete;
+
+CREATE TRIGGER metadata_search_insert AFTER INSERT ON artifact_metadata BEGIN
+    UPDATE artifact_search SET metadata = replace(replace(COALESCE(
+        (SELECT group_concat(CASE json_type(value) WHEN 'text' THEN value ->> '$' ELSE value END, ' ')
+         FROM artifact_metadata WHERE artifact_id = new.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = new.artifact_id;
+END;
+
+CREATE TRIGGER metadata_search_update AFTER UPDATE ON artifact_metadata BEGIN
+    UPDATE artifact_search SET metadata = replace(replace(COALESCE(
+        (SELECT group_concat(CASE json_type(value) WHEN 'text' THEN value ->> '$' ELSE value END, ' ')
+         FROM artifact_metadata WHERE artifact_id = new.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = new.artifact_id;
+END;
+
+CREATE TRIGGER metadata_search_delete AFTER DELETE ON artifact_metadata BEGIN
+    UPDATE artifact_search SET metadata = replace(replace(COALESCE(
+        (SELECT group_concat(CASE json_type(value) WHEN 'text' THEN value ->> '$' ELSE value END, ' ')
+         FROM artifact_metadata WHERE artifact_id = old.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = old.artifact_id;
+END;
+
+-- Existing values are strings. Converting them runs the triggers above.
+UPDATE artifact_metadata SET value = json_quote(value);
@@ -0,0 +1,8 @@
+-- JSON Schemas that custom metadata of artifacts whose name matches
+-- `pattern` must satisfy. Every matching schema applies.
+CREATE TABLE IF NOT EXISTS metadata_schemas (
+    id         TEXT PRIMARY KEY,
+    pattern    TEXT NOT NULL,
+    schema     TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
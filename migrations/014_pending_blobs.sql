@@ -0,0 +1,8 @@
+-- Artifact files that may be on disk without a row: written before an
+-- upload's row is inserted, or about to be removed after a deletion. The
+-- server deletes the files of rows left here with no matching artifact at
+-- startup.
+CREATE TABLE IF NOT EXISTS pending_blobs (
+    artifact_id TEXT PRIMARY KEY,
+    created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+);
@@ -0,0 +1,18 @@
+-- Append-only log of changes to artifacts' custom metadata keys. Values are
+-- JSON like artifact_metadata.value: old_value is NULL when the key was
+-- added and new_value is NULL when it was removed. History survives its
+-- version's deletion, so it records the name and version it belonged to
+-- instead of cascading from artifacts.
+CREATE TABLE IF NOT EXISTS metadata_history (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    artifact_id TEXT NOT NULL,
+    name        TEXT NOT NULL,
+    version     TEXT NOT NULL,
+    key         TEXT NOT NULL,
+    old_value   TEXT,
+    new_value   TEXT,
+    actor       TEXT NOT NULL,
+    at          TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX idx_metadata_history_version ON metadata_history(name, version, key);
@@ -0,0 +1,117 @@
+-- A version is a release of one or more named files (assets). Each existing
+-- version's file becomes its primary asset, with the version's ID so the
+-- stored file keeps its name. The version's filename, sha256 and size go on
+-- describing its primary asset.
+CREATE TABLE IF NOT EXISTS assets (
+    id            TEXT PRIMARY KEY,
+    artifact_id   TEXT NOT NULL REFERENCES artifacts(id) ON DELETE CASCADE,
+    filename      TEXT NOT NULL,
+    sha256        TEXT NOT NULL,
+    size          INTEGER NOT NULL,
+    uploaded_by   TEXT,
+    created_at    TEXT NOT NULL DEFAULT (datetime('now')),
+    broken_at     TEXT,
+    broken_reason TEXT,
+    UNIQUE (artifact_id, filename)
+);
+
+CREATE INDEX idx_assets_uploaded_by ON assets(uploaded_by);
+
+INSERT INTO assets
+    (id, artifact_id, filename, sha256, size, uploaded_by, created_at, broken_at, broken_reason)
+    SELECT id, id, filename, sha256, size, uploaded_by, created_at, broken_at, broken_reason
+    FROM artifacts;
+
+-- The search index's filename column holds the filenames of all of a
+-- version's assets, not just its primary one, kept in sync by triggers on
+-- assets. Match marks are stripped as in 010.
+DROP TRIGGER artifacts_search_insert;
+DROP TRIGGER artifacts_search_update;
+
+CREATE TRIGGER artifacts_search_insert AFTER INSERT ON artifacts BEGIN
+    INSERT INTO artifact_search (name, filename, metadata, artifact_id)
+        VALUES (replace(replace(new.name, char(2), ''), char(3), ''), '', '', new.id);
+END;
+
+CREATE TRIGGER artifacts_search_update AFTER UPDATE OF name ON artifacts BEGIN
+    UPDATE artifact_search SET name = replace(replace(new.name, char(2), ''), char(3), '')
+        WHERE artifact_id = new.id;
+END;
+
+CREATE TRIGGER assets_search_insert AFTER INSERT ON assets BEGIN
+    UPDATE artifact_search SET filename = replace(replace(COALESCE(
+        (SELECT group_concat(filename, ' ') FROM assets
+         WHERE artifact_id = new.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = new.artifact_id;
+END;
+
+CREATE TRIGGER assets_search_update AFTER UPDATE OF filename ON assets BEGIN
+    UPDATE artifact_search SET filename = replace(replace(COALESCE(
+        (SELECT group_concat(filename, ' ') FROM assets
+         WHERE artifact_id = new.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = new.artifact_id;
+END;
+
+CREATE TRIGGER assets_search_delete AFTER DELETE ON assets BEGIN
+    UPDATE artifact_search SET filename = replace(replace(COALESCE(
+        (SELECT group_concat(filename, ' ') FROM assets
+         WHERE artifact_id = old.artifact_id), ''), char(2), ''), char(3), '')
+        WHERE artifact_id = old.artifact_id;
+END;
+
+-- Files are verified, marked broken and scrubbed one asset at a time.
+ALTER TABLE artifacts DROP COLUMN broken_at;
+ALTER TABLE artifacts DROP COLUMN broken_reason;
+
+CREATE TABLE scrub_results_new (
+    asset_id   TEXT PRIMARY KEY REFERENCES assets(id) ON DELETE CASCADE,
+    checked_at TEXT NOT NULL DEFAULT (datetime('now')),
+    ok         INTEGER NOT NULL,
+    problem    TEXT,
+    detail     TEXT
+);
+
+INSERT INTO scrub_results_new (asset_id, checked_at, ok, problem, detail)
+    SELECT artifact_id, checked_at, ok, problem, detail FROM scrub_results;
+
+DROP TABLE scrub_results;
+ALTER TABLE scrub_results_new RENAME TO scrub_results;
+CREATE INDEX idx_scrub_results_checked ON scrub_results(checked_at);
+
+ALTER TABLE pending_blobs RENAME COLUMN artifact_id TO asset_id;
+
+-- Downloads are counted per asset. The asset isn't a foreign key, so the
+-- downloads of a deleted asset still count towards its version.
+ALTER TABLE download_stats ADD COLUMN asset_id TEXT;
+UPDATE download_stats SET asset_id = artifact_id;
+
+DROP VIEW download_events;
+
+CREATE TABLE download_stats_daily_new (
+    artifact_id TEXT NOT NULL REFERENCES artifacts(id) ON DELETE CASCADE,
+    asset_id    TEXT NOT NULL,
+    day         TEXT NOT NULL,
+    client      TEXT NOT NULL DEFAULT 'unknown',
+    bot         INTEGER NOT NULL DEFAULT 0,
+    referrer    TEXT NOT NULL DEFAULT '',
+    downloads   INTEGER NOT NULL,
+    unique_ips  INTEGER NOT NULL,
+    PRIMARY KEY (artifact_id, asset_id, day, client, referrer)
+);
+
+INSERT INTO download_stats_daily_new
+    (artifact_id, asset_id, day, client, bot, referrer, downloads, unique_ips)
+    SELECT artifact_id, artifact_id, day, client, bot, referrer, downloads, unique_ips
+    FROM download_stats_daily;
+
+DROP TABLE download_stats_daily;
+ALTER TABLE download_stats_daily_new RENAME TO download_stats_daily;
+
+CREATE VIEW download_events AS
+    SELECT artifact_id, asset_id, downloaded_at, 1 AS downloads, ip, 0 AS rolled_unique_ips,
+           client, bot, referrer
+    FROM download_stats
+    UNION ALL
+    SELECT artifact_id, asset_id, day || ' 00:00:00', downloads, NULL, unique_ips,
+           client, bot, NULLIF(referrer, '')
+    FROM download_stats_daily;
@@ -0,0 +1,6 @@
+-- Salt for `--ip-mode hash`, one per UTC day. Only the current day's row is
+-- kept, so earlier hashes can't be recomputed.
+CREATE TABLE IF NOT EXISTS ip_salts (
+    day  TEXT PRIMARY KEY,
+    salt TEXT NOT NULL
+);
@@ -0,0 +1,344 @@
+//! Operations on stored artifacts shared by request handlers and background
+//! tasks.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use cask_types::{ArtifactRow, Asset};
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, SqlitePool};
+
+use crate::audit;
+use crate::db;
+use crate::state::AppState;
+use crate::storage;
+
+/// Columns to select into an [`ArtifactRow`].
+pub const COLUMNS: &str = "id, name, version, filename, sha256, size, created_at, yanked_at";
+
+/// Columns of `assets` to select into an [`Asset`].
+pub const ASSET_COLUMNS: &str =
+    "filename, sha256, size, created_at, id = artifact_id AS \"primary\"";
+
+/// Fill in the `assets` of each row, primary first and then in upload
+/// order.
+pub async fn attach_assets(db: &SqlitePool, rows: &mut [ArtifactRow]) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    #[derive(sqlx::FromRow)]
+    struct Row {
+        artifact_id: String,
+        #[sqlx(flatten)]
+        asset: Asset,
+    }
+
+    let mut sql = QueryBuilder::<Sqlite>::new(format!(
+        "SELECT artifact_id, {} FROM assets WHERE artifact_id IN (",
+        ASSET_COLUMNS
+    ));
+    let mut ids = sql.separated(", ");
+    for row in rows.iter() {
+        ids.push_bind(row.id.clone());
+    }
+    sql.push(") ORDER BY \"primary\" DESC, created_at, rowid");
+
+    let mut assets: HashMap<String, Vec<Asset>> = HashMap::new();
+    for row in sql.build_query_as::<Row>().fetch_all(db).await? {
+        assets.entry(row.artifact_id).or_default().push(row.asset);
+    }
+    for row in rows {
+        row.assets = assets.remove(&row.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// Delete an artifact version's row (cascading to its assets, metadata and
+/// stats) and its files, and record who did it in the audit log. The files
+/// are marked pending along with the row's deletion, so they're removed at
+/// the next startup if deleting them now fails; that is logged rather than
+/// failing the already committed deletion.
+///
+/// Returns `false`, recording nothing, if the version was already gone.
+pub async fn delete(
+    state: &AppState,
+    artifact: &ArtifactRow,
+    actor: &str,
+    detail: Option<&str>,
+) -> Result<bool> {
+    let mut tx = db::begin_write(&state.db).await?;
+    let asset_ids = sqlx::query_scalar::<_, String>("SELECT id FROM assets WHERE artifact_id = ?")
+        .bind(&artifact.id)
+        .fetch_all(&mut *tx)
+        .await?;
+    let deleted = sqlx::query("DELETE FROM artifacts WHERE id = ?")
+        .bind(&artifact.id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Ok(false);
+    }
+    for asset_id in &asset_ids {
+        add_pending(&mut *tx, asset_id).await?;
+    }
+    audit::record(
+        &mut *tx,
+        actor,
+        "delete",
+        Some((&artifact.name, &artifact.version)),
+        detail,
+    )
+    .await?;
+    tx.commit().await?;
+
+    for asset_id in &asset_ids {
+        remove_file(state, asset_id).await;
+    }
+    Ok(true)
+}
+
+/// Delete one asset of a version other than its primary one, like
+/// [`delete`] does for the whole version.
+pub async fn delete_asset(
+    state: &AppState,
+    artifact: &ArtifactRow,
+    asset_id: &str,
+    filename: &str,
+    actor: &str,
+) -> Result<()> {
+    let mut tx = db::begin_write(&state.db).await?;
+    sqlx::query("DELETE FROM assets WHERE id = ?")
+        .bind(asset_id)
+        .execute(&mut *tx)
+        .await?;
+    add_pending(&mut *tx, asset_id).await?;
+    audit::record(
+        &mut *tx,
+        actor,
+        "delete_asset",
+        Some((&artifact.name, &artifact.version)),
+        Some(filename),
+    )
+    .await?;
+    tx.commit().await?;
+
+    remove_file(state, asset_id).await;
+    Ok(())
+}
+
+/// Delete the file of an asset whose row is gone. On failure it stays
+/// pending for the next startup.
+async fn remove_file(state: &AppState, asset_id: &str) {
+    let removed = match storage::delete(&state.data_dir, asset_id).await {
+        Ok(()) => remove_pending(&state.db, asset_id).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = removed {
+        tracing::warn!(
+            "{:#}; file {} will be removed at the next startup",
+            e,
+            asset_id
+        );
+    }
+}
+
+/// Record that `asset_id`'s file may be on disk without a row, before
+/// writing it or deleting the row.
+pub async fn add_pending(db: impl SqliteExecutor<'_>, asset_id: &str) -> Result<()> {
+    sqlx::query("INSERT OR IGNORE INTO pending_blobs (asset_id) VALUES (?)")
+        .bind(asset_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_pending(db: impl SqliteExecutor<'_>, asset_id: &str) -> Result<()> {
+    sqlx::query("DELETE FROM pending_blobs WHERE asset_id = ?")
+        .bind(asset_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Delete files left by uploads and deletions that were interrupted: those
+/// of pending blobs with no asset row. Run at startup, before any requests
+/// are served. Returns the number of blobs cleaned up; files that can't be
+/// removed are logged and tried again next time.
+pub async fn clean_pending(db: &SqlitePool, data_dir: &Path) -> Result<usize> {
+    let orphaned = sqlx::query_scalar::<_, String>(
+        "SELECT asset_id FROM pending_blobs WHERE asset_id NOT IN (SELECT id FROM assets)",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut removed = 0;
+    for asset_id in &orphaned {
+        // Kept pending, to be retried at the next startup.
+        if let Err(e) = storage::delete(data_dir, asset_id).await {
+            tracing::warn!("{:#}", e);
+            continue;
+        }
+        remove_pending(db, asset_id).await?;
+        removed += 1;
+    }
+    // The rest belong to uploads whose rows were inserted.
+    sqlx::query("DELETE FROM pending_blobs WHERE asset_id IN (SELECT id FROM assets)")
+        .execute(db)
+        .await?;
+
+    Ok(removed)
+}
+
+/// A string that sorts like `version` under semantic versioning, stored as
+/// `artifacts.version_key`. A leading `v`, a missing minor or patch number
+/// and build metadata are tolerated. Anything else that isn't semver sorts
+/// before all semver versions, alphabetically.
+pub fn version_key(version: &str) -> String {
+    semver_key(version).unwrap_or_else(|| format!("!{}", version))
+}
+
+fn semver_key(version: &str) -> Option<String> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let version = version.split_once('+').map_or(version, |(v, _)| v);
+    let (core, pre) = match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    };
+
+    let parts: Vec<&str> = core.split('.').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    // Fixed-width numbers compare correctly as strings.
+    let mut key = String::new();
+    for i in 0..3 {
+        let n = match parts.get(i) {
+            Some(part) => numeric(part)?,
+            None => 0,
+        };
+        write!(key, "{:020}.", n).unwrap();
+    }
+
+    // A release sorts after its pre-releases ('~' > '-'). Pre-release
+    // identifiers are separated by ' ', which sorts below every identifier
+    // character, and numeric identifiers sort below alphanumeric ones.
+    let Some(pre) = pre else {
+        key.push('~');
+        return Some(key);
+    };
+    key.push('-');
+    for (i, ident) in pre.split('.').enumerate() {
+        if i > 0 {
+            key.push(' ');
+        }
+        if let Some(n) = numeric(ident) {
+            write!(key, "0{:020}", n).unwrap();
+        } else if !ident.is_empty()
+            && ident
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        {
+            key.push('1');
+            key.push_str(ident);
+        } else {
+            return None;
+        }
+    }
+    Some(key)
+}
+
+fn numeric(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Compute `version_key` for artifacts stored before it existed.
+pub async fn fill_version_keys(db: &SqlitePool) -> Result<()> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT id, version FROM artifacts WHERE version_key IS NULL",
+    )
+    .fetch_all(db)
+    .await?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let mut tx = db.begin().await?;
+    for (id, version) in &rows {
+        sqlx::query("UPDATE artifacts SET version_key = ? WHERE id = ?")
+            .bind(version_key(version))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    tracing::info!("computed sort keys for {} artifact version(s)", rows.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::version_key;
+
+    fn assert_ascending(versions: &[&str]) {
+        for pair in versions.windows(2) {
+            assert!(
+                version_key(pair[0]) < version_key(pair[1]),
+                "{} should sort before {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn pre_releases_sort_before_their_release() {
+        assert_ascending(&[
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1-alpha",
+        ]);
+    }
+
+    #[test]
+    fn numbers_compare_numerically() {
+        assert_ascending(&["1.2.9", "1.2.10", "1.10.0", "2.0.0", "10.0.0"]);
+        // Numeric identifiers sort before alphanumeric ones.
+        assert_ascending(&["1.0.0-2", "1.0.0-10", "1.0.0-1a", "1.0.0-a"]);
+    }
+
+    #[test]
+    fn tolerated_variations_share_a_key() {
+        assert_eq!(version_key("v1.2.3"), version_key("1.2.3"));
+        assert_eq!(version_key("1.2"), version_key("1.2.0"));
+        assert_eq!(version_key("1"), version_key("1.0.0"));
+        assert_eq!(version_key("1.2.3+build.5"), version_key("1.2.3"));
+        assert_eq!(version_key("v1.2.3-rc.1+abc"), version_key("1.2.3-rc.1"));
+    }
+
+    #[test]
+    fn non_semver_sorts_first_alphabetically() {
+        for version in [
+            "latest",
+            "1.2.3.4",
+            "1.x",
+            "1.0.0-",
+            "1.0.0-a..b",
+            "nightly-2024",
+        ] {
+            assert_eq!(version_key(version), format!("!{}", version));
+        }
+        assert_ascending(&["1.2.3.4", "latest", "nightly", "0.0.0-0", "0.0.1"]);
+    }
+}
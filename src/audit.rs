@@ -0,0 +1,31 @@
+//! Append-only record of deletions and yanks, listed by
+//! `GET /v1/admin/audit`.
+
+use anyhow::Result;
+use sqlx::SqliteExecutor;
+use uuid::Uuid;
+
+/// Actor recorded for deletions made by the scheduled garbage collector.
+pub const GC_ACTOR: &str = "gc";
+
+pub async fn record(
+    db: impl SqliteExecutor<'_>,
+    actor: &str,
+    action: &str,
+    target: Option<(&str, &str)>,
+    detail: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO audit_log (id, actor, action, name, version, detail) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(actor)
+    .bind(action)
+    .bind(target.map(|(name, _)| name))
+    .bind(target.map(|(_, version)| version))
+    .bind(detail)
+    .execute(db)
+    .await?;
+    Ok(())
+}
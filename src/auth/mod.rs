@@ -15,7 +15,10 @@ pub struct RequireToken {
 
 pub struct RequireAdmin(pub RequireToken);
 
-pub async fn validate_token(headers: &HeaderMap, db: &SqlitePool) -> Result<RequireToken, AppError> {
+pub async fn validate_token(
+    headers: &HeaderMap,
+    db: &SqlitePool,
+) -> Result<RequireToken, AppError> {
     let header = headers
         .get("authorization")
         .and_then(|v| v.to_str().ok())
@@ -35,7 +38,7 @@ pub async fn validate_token(headers: &HeaderMap, db: &SqlitePool) -> Result<Requ
     .bind(&token_hash)
     .fetch_optional(db)
     .await
-    .map_err(|e| AppError::internal(e))?;
+    .map_err(AppError::internal)?;
 
     let row = row.ok_or_else(|| AppError::unauthorized("invalid or expired token"))?;
 
@@ -1,5 +1,9 @@
-use clap::{Parser, Subcommand};
+use cask_types::{KeyType, ListSort, QuotaScope, StatsInterval};
+use clap::{Parser, Subcommand, ValueEnum};
+use ipnet::IpNet;
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "cask", about = "Lightweight artifact hosting server")]
@@ -24,6 +28,78 @@ pub enum Command {
 
     /// Show recent log output
     Log(LogOpts),
+
+    /// Write a consistent snapshot of the database and artifacts to an archive
+    Backup(BackupOpts),
+
+    /// Replace a data directory with the contents of a backup archive
+    Restore(RestoreOpts),
+
+    /// Verify stored artifact files against the database
+    Fsck(FsckOpts),
+
+    /// Upload a file as a new artifact version
+    Push(PushOpts),
+
+    /// Download an artifact version and verify its checksum
+    Pull(PullOpts),
+
+    /// List artifacts, or the versions of one artifact
+    Ls(LsOpts),
+
+    /// Delete an artifact version
+    Rm(RmOpts),
+
+    /// List the files of an artifact version
+    Assets(AssetsOpts),
+
+    /// Mark an artifact version as yanked, or undo that
+    Yank(YankOpts),
+
+    /// Read and modify artifact metadata
+    Meta(MetaOpts),
+
+    /// Get or set name-level package metadata
+    Package(PackageOpts),
+
+    /// Get or set a package's README
+    Readme(ReadmeOpts),
+
+    /// Show download counts
+    Stats(StatsOpts),
+
+    /// Show the most downloaded artifacts
+    Top(TopOpts),
+
+    /// Show downloads by client (curl, pip, browser, ...) or referrer
+    Clients(ClientsOpts),
+
+    /// Search artifact names, filenames and metadata
+    Search(SearchOpts),
+
+    /// Manage API tokens
+    Tokens(TokensOpts),
+
+    /// Manage retention rules
+    Retention(RetentionOpts),
+
+    /// Manage metadata schemas
+    Schemas(SchemasOpts),
+
+    /// Delete versions selected by the retention rules
+    Gc(GcOpts),
+
+    /// Show the audit log of deletions and yanks
+    Audit(AuditOpts),
+
+    /// Manage storage quotas
+    Quotas(QuotasOpts),
+
+    /// Show consumption against storage quotas
+    Usage(UsageOpts),
+
+    /// Turn read-only (maintenance) mode on or off
+    ReadOnly(ReadOnlyOpts),
 }
 
 #[derive(Parser, Clone)]
@@ -44,9 +120,96 @@ pub struct ServerOpts {
     #[arg(long, default_value_t = 100 * 1024 * 1024)]
     pub max_upload_size: usize,
 
+    /// Refuse uploads that would leave less than this many bytes free on disk (0 disables)
+    #[arg(long, default_value_t = 0)]
+    pub min_free_space: u64,
+
     /// Log level (trace, debug, info, warn, error)
     #[arg(long, default_value = "info")]
     pub log_level: String,
+
+    /// Rotate the daemon log once it reaches this many bytes (0 disables)
+    #[arg(long, default_value_t = 0)]
+    pub log_max_size: u64,
+
+    /// Rotate the daemon log on a fixed schedule
+    #[arg(long, value_enum, default_value_t = LogRotation::Never)]
+    pub log_rotate: LogRotation,
+
+    /// Number of rotated log files to keep
+    #[arg(long, default_value_t = 5)]
+    pub log_keep: usize,
+
+    /// Gzip rotated log files
+    #[arg(long)]
+    pub log_compress: bool,
+
+    /// Log line format
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Read budget of the background scrubber in bytes per second (0 disables scrubbing)
+    #[arg(long, default_value_t = 0)]
+    pub scrub_rate: u64,
+
+    /// Pause between scrub passes over all artifacts (e.g. `24h`)
+    #[arg(long, default_value = "24h", value_parser = humantime::parse_duration)]
+    pub scrub_interval: Duration,
+
+    /// How often to enforce retention rules (0s disables)
+    #[arg(long, default_value = "1h", value_parser = humantime::parse_duration)]
+    pub gc_interval: Duration,
+
+    /// What to store of client IPs in download stats
+    #[arg(long, value_enum, default_value_t = IpMode::Full)]
+    pub ip_mode: IpMode,
+
+    /// Clear stored client IPs once they are this old (0s keeps them)
+    #[arg(long, default_value = "0s", value_parser = humantime::parse_duration)]
+    pub ip_retention: Duration,
+
+    /// Comma-separated proxy addresses or CIDR ranges whose X-Forwarded-For is trusted
+    #[arg(long, value_delimiter = ',', value_parser = parse_ip_net)]
+    pub trusted_proxies: Vec<IpNet>,
+
+    /// Download stat events to queue for writing before new ones are dropped
+    #[arg(long, default_value_t = 10_000)]
+    pub stats_queue_size: usize,
+
+    /// Compact raw download stats older than this into daily totals (0s disables)
+    #[arg(long, default_value = "30days", value_parser = humantime::parse_duration)]
+    pub stats_rollup_after: Duration,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum IpMode {
+    /// Store the address as-is
+    Full,
+    /// Keep only the /24 (IPv4) or /48 (IPv6) network
+    Truncate,
+    /// Store a hash salted with a secret that changes daily
+    Hash,
+    /// Don't store addresses
+    None,
+}
+
+fn parse_ip_net(s: &str) -> Result<IpNet, String> {
+    s.parse::<IpNet>()
+        .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("invalid IP address or CIDR range: {}", s))
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    Daily,
 }
 
 #[derive(Parser, Clone)]
@@ -69,4 +232,779 @@ pub struct LogOpts {
     /// Follow the log (like tail -f)
     #[arg(short)]
     pub f: bool,
+
+    /// Only show entries at this level or more severe
+    #[arg(long)]
+    pub level: Option<tracing::Level>,
+
+    /// Only show entries at or after this time (e.g. `1h`, `30m`, or an RFC 3339 timestamp)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only show entries before this time (same formats as --since)
+    #[arg(long)]
+    pub until: Option<String>,
+
+    /// Only show lines matching this regular expression
+    #[arg(long)]
+    pub grep: Option<String>,
+
+    /// Print entries as JSON objects (JSON log lines are passed through as-is)
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct BackupOpts {
+    /// Directory for database, logs, PID file, and artifacts
+    #[arg(long, default_value = "./data")]
+    pub data_dir: PathBuf,
+
+    /// Archive to write (zstd-compressed tar)
+    #[arg(long)]
+    pub out: PathBuf,
+}
+
+#[derive(Parser, Clone)]
+pub struct RestoreOpts {
+    /// Directory for database, logs, PID file, and artifacts
+    #[arg(long, default_value = "./data")]
+    pub data_dir: PathBuf,
+
+    /// Archive created by `cask backup`
+    pub archive: PathBuf,
+
+    /// Replace a non-empty data directory (it is kept as `<data-dir>.old`)
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct FsckOpts {
+    /// Directory for database, logs, PID file, and artifacts
+    #[arg(long, default_value = "./data")]
+    pub data_dir: PathBuf,
+
+    /// Move orphaned files to `quarantine/` and mark broken artifacts so
+    /// downloads refuse them
+    #[arg(long)]
+    pub repair: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Connection options shared by the client subcommands.
+#[derive(Parser, Clone)]
+pub struct ClientOpts {
+    /// Server URL [default: profile value or http://127.0.0.1:8080]
+    #[arg(long, env = "CASK_URL")]
+    pub url: Option<String>,
+
+    /// API token [default: profile value]
+    #[arg(long, env = "CASK_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Profile to read from the client config file
+    #[arg(long, env = "CASK_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Print raw JSON instead of human-readable output
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct PushOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Artifact name
+    pub name: String,
+
+    /// Artifact version
+    pub version: String,
+
+    /// File to upload
+    pub file: PathBuf,
+
+    /// Filename served on download [default: the uploaded file's name]
+    #[arg(long)]
+    pub filename: Option<String>,
+
+    /// Metadata to set with the upload, as `key=value` strings or
+    /// `key:=<json>` values
+    #[arg(long = "meta", value_name = "KEY=VALUE")]
+    pub meta: Vec<String>,
+
+    /// Add the file to the version's assets, creating the version if it
+    /// doesn't exist yet
+    #[arg(long, conflicts_with = "meta")]
+    pub asset: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct PullOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Artifact name
+    pub name: String,
+
+    /// Artifact version
+    pub version: String,
+
+    /// Where to write the file [default: the artifact's filename]
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Download this asset instead of the version's primary file
+    #[arg(long, value_name = "FILENAME")]
+    pub asset: Option<String>,
+}
+
+#[derive(Parser, Clone)]
+pub struct LsOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Only list versions of this artifact
+    pub name: Option<String>,
+
+    /// Show one page of this many versions instead of all of them
+    #[arg(long)]
+    pub limit: Option<u32>,
+
+    /// Continue from the cursor printed after a previous page
+    #[arg(long)]
+    pub cursor: Option<String>,
+
+    /// Sort order [default: name]
+    #[arg(long, value_enum)]
+    pub sort: Option<ListSortArg>,
+
+    /// Reverse the sort order
+    #[arg(long, short)]
+    pub reverse: bool,
+
+    /// Only names starting with this
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    /// Only versions created at or after this date or UTC timestamp
+    #[arg(long)]
+    pub created_after: Option<String>,
+
+    /// Only versions created before this date or UTC timestamp
+    #[arg(long)]
+    pub created_before: Option<String>,
+
+    /// Only versions of at least this many bytes
+    #[arg(long)]
+    pub min_size: Option<i64>,
+
+    /// Only versions of at most this many bytes
+    #[arg(long)]
+    pub max_size: Option<i64>,
+
+    /// Only yanked (true) or not yanked (false) versions
+    #[arg(long)]
+    pub yanked: Option<bool>,
+
+    /// Only versions with this metadata key
+    #[arg(long)]
+    pub tagged: Option<String>,
+
+    /// Only versions with metadata `key=value`; repeat a key to match any of
+    /// several values. `key>N`, `key>=N`, `key<N` and `key<=N` compare
+    /// numbers
+    #[arg(long = "meta", value_name = "KEY=VALUE", value_parser = parse_meta_filter)]
+    pub meta: Vec<(String, String)>,
+
+    /// List one row per name with its latest version, description and
+    /// downloads
+    #[arg(long, conflicts_with = "name")]
+    pub grouped: bool,
+}
+
+fn parse_meta_filter(s: &str) -> Result<(String, String), String> {
+    // `key>=N` is sent as `meta.key=>=N`.
+    match s.find(['=', '<', '>']) {
+        Some(0) | None => Err(format!("expected key=value, got '{}'", s)),
+        Some(i) if s[i..].starts_with('=') => Ok((s[..i].to_string(), s[i + 1..].to_string())),
+        Some(i) => Ok((s[..i].to_string(), s[i..].to_string())),
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ListSortArg {
+    /// By name, newest version first
+    Name,
+    /// Newest first
+    Created,
+    /// Largest first
+    Size,
+    /// By name, highest version first
+    Semver,
+}
+
+impl From<ListSortArg> for ListSort {
+    fn from(arg: ListSortArg) -> Self {
+        match arg {
+            ListSortArg::Name => ListSort::Name,
+            ListSortArg::Created => ListSort::Created,
+            ListSortArg::Size => ListSort::Size,
+            ListSortArg::Semver => ListSort::Semver,
+        }
+    }
+}
+
+#[derive(Parser, Clone)]
+pub struct SearchOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Words to search for, or numeric comparisons like `tests>=100`;
+    /// versions matching all of them are shown
+    #[arg(required = true)]
+    pub words: Vec<String>,
+
+    /// Number of results to show
+    #[arg(short, default_value_t = 20)]
+    pub n: u32,
+
+    /// Skip this many results
+    #[arg(long, default_value_t = 0)]
+    pub offset: u32,
+}
+
+#[derive(Parser, Clone)]
+pub struct YankOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Artifact name
+    pub name: String,
+
+    /// Version to yank
+    pub version: String,
+
+    /// Un-yank the version instead
+    #[arg(long)]
+    pub undo: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct RmOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Artifact name
+    pub name: String,
+
+    /// Artifact version
+    pub version: String,
+
+    /// Only delete this asset of the version
+    #[arg(long, value_name = "FILENAME")]
+    pub asset: Option<String>,
+}
+
+#[derive(Parser, Clone)]
+pub struct AssetsOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Artifact name
+    pub name: String,
+
+    /// Artifact version
+    pub version: String,
+}
+
+#[derive(Parser, Clone)]
+pub struct MetaOpts {
+    #[command(subcommand)]
+    pub command: MetaCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum MetaCommand {
+    /// Show all metadata for a version
+    Get {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        version: String,
+    },
+
+    /// Set one or more `key=value` string pairs, or `key:=<json>` for
+    /// numbers, booleans, lists and objects
+    Set {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        version: String,
+        #[arg(required = true)]
+        pairs: Vec<String>,
+    },
+
+    /// Apply a JSON merge patch (`null` removes a key)
+    Patch {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        version: String,
+        /// The patch, or `-` to read it from stdin
+        patch: String,
+    },
+
+    /// Remove a key
+    Rm {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        version: String,
+        key: String,
+    },
+
+    /// Show who changed which keys, and their old and new values
+    History {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        version: String,
+        /// Only show changes to this key
+        #[arg(long)]
+        key: Option<String>,
+        /// Number of changes to show
+        #[arg(short, default_value_t = 20)]
+        n: u32,
+    },
+}
+
+#[derive(Parser, Clone)]
+pub struct PackageOpts {
+    #[command(subcommand)]
+    pub command: PackageCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum PackageCommand {
+    /// Show a package's summary and name-level metadata
+    Get {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+    },
+
+    /// Set one or more `key=value` string pairs (e.g. description,
+    /// homepage, owner, license), or `key:=<json>` for other values
+    Set {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        #[arg(required = true)]
+        pairs: Vec<String>,
+    },
+
+    /// Remove a key
+    Rm {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        key: String,
+    },
+}
+
+#[derive(Parser, Clone)]
+pub struct ReadmeOpts {
+    #[command(subcommand)]
+    pub command: ReadmeCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ReadmeCommand {
+    /// Print a package's README
+    Get {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+    },
+
+    /// Replace a package's README with the contents of a file (`-` for stdin)
+    Set {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+        file: PathBuf,
+    },
+
+    /// Remove a package's README
+    Rm {
+        #[command(flatten)]
+        client: ClientOpts,
+        name: String,
+    },
+}
+
+#[derive(Parser, Clone)]
+pub struct StatsOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Artifact name
+    pub name: String,
+
+    /// Only count downloads of this version
+    pub version: Option<String>,
+
+    /// Only count downloads at or after this date or UTC timestamp (e.g. 2024-01-31)
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Only count downloads before this date or UTC timestamp
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Also show downloads per hour, day, week or month
+    #[arg(long, value_enum)]
+    pub interval: Option<StatsIntervalArg>,
+
+    /// Also show downloads per version
+    #[arg(long)]
+    pub by_version: bool,
+
+    /// Also show downloads per asset (with a version)
+    #[arg(long, requires = "version")]
+    pub by_asset: bool,
+
+    /// Leave out downloads by known bots and health checkers
+    #[arg(long)]
+    pub exclude_bots: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum StatsIntervalArg {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl From<StatsIntervalArg> for StatsInterval {
+    fn from(arg: StatsIntervalArg) -> Self {
+        match arg {
+            StatsIntervalArg::Hour => StatsInterval::Hour,
+            StatsIntervalArg::Day => StatsInterval::Day,
+            StatsIntervalArg::Week => StatsInterval::Week,
+            StatsIntervalArg::Month => StatsInterval::Month,
+        }
+    }
+}
+
+#[derive(Parser, Clone)]
+pub struct TopOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Number of artifacts to show
+    #[arg(short, default_value_t = 10)]
+    pub n: u32,
+
+    /// Only count downloads at or after this date or UTC timestamp
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Only count downloads before this date or UTC timestamp
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Leave out downloads by known bots and health checkers
+    #[arg(long)]
+    pub exclude_bots: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct ClientsOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Only count downloads of this artifact name
+    pub name: Option<String>,
+
+    /// Only count downloads of this version
+    pub version: Option<String>,
+
+    /// Only count downloads at or after this date or UTC timestamp
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Only count downloads before this date or UTC timestamp
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Leave out downloads by known bots and health checkers
+    #[arg(long)]
+    pub exclude_bots: bool,
+
+    /// Group by referring host instead of client
+    #[arg(long)]
+    pub referrers: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct TokensOpts {
+    #[command(subcommand)]
+    pub command: TokensCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum TokensCommand {
+    /// Create a token (prints the secret once)
+    Create {
+        #[command(flatten)]
+        client: ClientOpts,
+        label: String,
+        /// Grant admin access
+        #[arg(long)]
+        admin: bool,
+        /// Expiry timestamp, e.g. 2025-01-01T00:00:00
+        #[arg(long)]
+        expires_at: Option<String>,
+    },
+
+    /// List tokens
+    Ls {
+        #[command(flatten)]
+        client: ClientOpts,
+    },
+
+    /// Revoke a token by ID
+    Revoke {
+        #[command(flatten)]
+        client: ClientOpts,
+        id: String,
+    },
+}
+
+#[derive(Parser, Clone)]
+pub struct RetentionOpts {
+    #[command(subcommand)]
+    pub command: RetentionCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum RetentionCommand {
+    /// Add a rule for artifact names matching a glob
+    Add {
+        #[command(flatten)]
+        client: ClientOpts,
+        /// Artifact name glob, e.g. `myapp-*`
+        pattern: String,
+        /// Delete versions beyond the newest N
+        #[arg(long)]
+        keep_last: Option<i64>,
+        /// Delete versions older than this many days
+        #[arg(long)]
+        max_age_days: Option<i64>,
+        /// Never delete versions downloaded within this many days
+        #[arg(long)]
+        keep_downloaded_days: Option<i64>,
+        /// Never delete versions that have this metadata key
+        #[arg(long)]
+        keep_tagged: Option<String>,
+    },
+
+    /// List rules
+    Ls {
+        #[command(flatten)]
+        client: ClientOpts,
+    },
+
+    /// Remove a rule by ID
+    Rm {
+        #[command(flatten)]
+        client: ClientOpts,
+        id: String,
+    },
+}
+
+#[derive(Parser, Clone)]
+pub struct SchemasOpts {
+    #[command(subcommand)]
+    pub command: SchemasCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum SchemasCommand {
+    /// Add a schema for artifact names matching a glob, from a JSON Schema
+    /// file or from `--key` and `--require`
+    Add {
+        #[command(flatten)]
+        client: ClientOpts,
+        /// Artifact name glob, e.g. `myapp-*`
+        pattern: String,
+        /// JSON Schema for the metadata object, or `-` to read it from stdin
+        #[arg(long, conflicts_with_all = ["keys", "required", "closed"])]
+        schema: Option<PathBuf>,
+        /// An optional key, with its type (string, number, integer, boolean,
+        /// array or object) if given
+        #[arg(long = "key", value_name = "KEY[:TYPE]", value_parser = parse_key_spec)]
+        keys: Vec<(String, Option<KeyType>)>,
+        /// A required key, with its type if given
+        #[arg(long = "require", value_name = "KEY[:TYPE]", value_parser = parse_key_spec)]
+        required: Vec<(String, Option<KeyType>)>,
+        /// Reject keys not given with `--key` or `--require`
+        #[arg(long)]
+        closed: bool,
+    },
+
+    /// List schemas
+    Ls {
+        #[command(flatten)]
+        client: ClientOpts,
+    },
+
+    /// Remove a schema by ID
+    Rm {
+        #[command(flatten)]
+        client: ClientOpts,
+        id: String,
+    },
+}
+
+fn parse_key_spec(s: &str) -> Result<(String, Option<KeyType>), String> {
+    let (key, kind) = match s.rsplit_once(':') {
+        Some((key, kind)) => (key, Some(kind)),
+        None => (s, None),
+    };
+    if key.is_empty() {
+        return Err(format!("expected KEY[:TYPE], got '{}'", s));
+    }
+    let kind = match kind {
+        None => None,
+        Some("string") => Some(KeyType::String),
+        Some("number") => Some(KeyType::Number),
+        Some("integer") => Some(KeyType::Integer),
+        Some("boolean") => Some(KeyType::Boolean),
+        Some("array") => Some(KeyType::Array),
+        Some("object") => Some(KeyType::Object),
+        Some(other) => return Err(format!("unknown type '{}'", other)),
+    };
+    Ok((key.to_string(), kind))
+}
+
+#[derive(Parser, Clone)]
+pub struct GcOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Only list what would be deleted
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Parser, Clone)]
+pub struct AuditOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+
+    /// Number of entries to show
+    #[arg(short, default_value_t = 20)]
+    pub n: u32,
+}
+
+#[derive(Parser, Clone)]
+pub struct QuotasOpts {
+    #[command(subcommand)]
+    pub command: QuotasCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum QuotasCommand {
+    /// Create or replace a quota
+    Set {
+        #[command(flatten)]
+        client: ClientOpts,
+        /// What the quota applies to
+        #[arg(value_enum)]
+        scope: QuotaScopeArg,
+        /// Artifact name prefix, or token ID
+        target: String,
+        /// Maximum total size in bytes
+        #[arg(long)]
+        max_bytes: Option<i64>,
+        /// Maximum number of artifact versions
+        #[arg(long)]
+        max_artifacts: Option<i64>,
+    },
+
+    /// List quotas
+    Ls {
+        #[command(flatten)]
+        client: ClientOpts,
+    },
+
+    /// Remove a quota by ID
+    Rm {
+        #[command(flatten)]
+        client: ClientOpts,
+        id: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum QuotaScopeArg {
+    Prefix,
+    Token,
+}
+
+impl From<QuotaScopeArg> for QuotaScope {
+    fn from(arg: QuotaScopeArg) -> Self {
+        match arg {
+            QuotaScopeArg::Prefix => QuotaScope::Prefix,
+            QuotaScopeArg::Token => QuotaScope::Token,
+        }
+    }
+}
+
+#[derive(Parser, Clone)]
+pub struct UsageOpts {
+    #[command(flatten)]
+    pub client: ClientOpts,
+}
+
+#[derive(Parser, Clone)]
+pub struct ReadOnlyOpts {
+    #[command(subcommand)]
+    pub command: ReadOnlyCommand,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ReadOnlyCommand {
+    /// Refuse uploads, deletes and other changes; downloads keep working
+    On {
+        #[command(flatten)]
+        client: ClientOpts,
+        /// Shown to clients whose requests are refused
+        #[arg(long)]
+        reason: Option<String>,
+        /// Seconds clients are asked to wait before retrying
+        #[arg(long, default_value_t = 60)]
+        retry_after: u64,
+    },
+
+    /// Accept changes again
+    Off {
+        #[command(flatten)]
+        client: ClientOpts,
+    },
+
+    /// Show whether read-only mode is on
+    Status {
+        #[command(flatten)]
+        client: ClientOpts,
+    },
 }
@@ -0,0 +1,38 @@
+mod output;
+mod profile;
+
+pub use output::{print_json, print_table, progress_bar};
+
+use anyhow::{Context, Result};
+use cask_client::Client;
+
+use crate::cli::ClientOpts;
+
+const DEFAULT_URL: &str = "http://127.0.0.1:8080";
+
+/// Build an API client from command-line flags, falling back to environment
+/// variables (handled by clap) and then the selected config profile.
+pub fn connect(opts: &ClientOpts) -> Result<Client> {
+    let profile = profile::load(opts.profile.as_deref())?;
+
+    let url = opts
+        .url
+        .clone()
+        .or(profile.url)
+        .unwrap_or_else(|| DEFAULT_URL.to_string());
+    let client = Client::new(&url)?;
+
+    Ok(match opts.token.clone().or(profile.token) {
+        Some(token) => client.with_token(token),
+        None => client,
+    })
+}
+
+/// Run a client command to completion on a single-threaded runtime.
+pub fn block_on<F: Future>(future: F) -> Result<F::Output> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("failed to create tokio runtime")?;
+    Ok(rt.block_on(future))
+}
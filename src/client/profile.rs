@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+const DEFAULT_PROFILE: &str = "default";
+
+/// One `[name]` table in the client config file.
+#[derive(Deserialize, Default)]
+pub struct Profile {
+    pub url: Option<String>,
+    pub token: Option<String>,
+}
+
+/// `$CASK_CONFIG`, else `$XDG_CONFIG_HOME/cask/config.toml`, else
+/// `~/.config/cask/config.toml`.
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CASK_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("cask").join("config.toml"))
+}
+
+/// Load a profile from the config file. Asking for a profile by name when
+/// it (or the file) doesn't exist is an error; the implicit default profile
+/// is allowed to be missing.
+pub fn load(name: Option<&str>) -> Result<Profile> {
+    let path = config_path();
+    let contents = match &path {
+        Some(path) if path.exists() => fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?,
+        _ => String::new(),
+    };
+
+    let mut profiles: HashMap<String, Profile> = toml::from_str(&contents).with_context(|| {
+        format!(
+            "failed to parse {}",
+            path.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+        )
+    })?;
+
+    match name {
+        Some(name) => match profiles.remove(name) {
+            Some(profile) => Ok(profile),
+            None => bail!("profile '{}' not found in client config", name),
+        },
+        None => Ok(profiles.remove(DEFAULT_PROFILE).unwrap_or_default()),
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+
+use crate::cli::AssetsOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: AssetsOpts) -> Result<()> {
+    client::block_on(assets(opts))?
+}
+
+async fn assets(opts: AssetsOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let assets = client.list_assets(&opts.name, &opts.version).await?;
+
+    if opts.client.json {
+        print_json(&assets);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = assets
+        .into_iter()
+        .map(|a| {
+            vec![
+                a.filename,
+                a.size.to_string(),
+                a.sha256,
+                a.created_at,
+                if a.primary { "yes" } else { "" }.to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["FILENAME", "SIZE", "SHA256", "CREATED", "PRIMARY"], &rows);
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+
+use crate::cli::AuditOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: AuditOpts) -> Result<()> {
+    client::block_on(audit(opts))?
+}
+
+async fn audit(opts: AuditOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let entries = client.audit_log(opts.n).await?;
+
+    if opts.client.json {
+        print_json(&entries);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = entries
+        .into_iter()
+        .map(|e| {
+            let target = match (e.name, e.version) {
+                (Some(name), Some(version)) => format!("{}/{}", name, version),
+                _ => "-".to_string(),
+            };
+            vec![
+                e.at,
+                e.actor,
+                e.action,
+                target,
+                e.detail.unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    print_table(&["TIME", "ACTOR", "ACTION", "ARTIFACT", "DETAIL"], &rows);
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, bail};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+
+use crate::cli::BackupOpts;
+use crate::db::DB_FILE_NAME;
+
+/// Version of the archive layout, checked by `cask restore`.
+pub const BACKUP_FORMAT: u32 = 1;
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+pub fn execute(opts: BackupOpts) -> Result<()> {
+    let db_path = opts.data_dir.join(DB_FILE_NAME);
+    if !db_path.exists() {
+        bail!("no database found at {}", db_path.display());
+    }
+
+    let staging = tempfile::Builder::new()
+        .prefix(".cask-backup")
+        .tempdir_in(&opts.data_dir)
+        .context("failed to create staging directory")?;
+    let snapshot = staging.path().join(DB_FILE_NAME);
+
+    let rt = tokio::runtime::Runtime::new().context("failed to create tokio runtime")?;
+    let asset_ids = rt.block_on(snapshot_db(&db_path, &snapshot))?;
+
+    // Write next to the destination and rename at the end so a failed
+    // backup never leaves a truncated archive behind.
+    let mut partial = opts.out.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let file = File::create(&partial)
+        .with_context(|| format!("failed to create {}", partial.display()))?;
+    let encoder = zstd::Encoder::new(file, 3).context("failed to start compression")?;
+    let mut archive = tar::Builder::new(encoder);
+
+    // Asset files never change once written, so copying them after the
+    // snapshot is consistent. A file can only be missing if its asset was
+    // deleted in the meantime; those rows are dropped from the snapshot.
+    let mut missing = Vec::new();
+    for id in &asset_ids {
+        let path = opts.data_dir.join("artifacts").join(id);
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                missing.push(id.clone());
+                continue;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to open {}", path.display()));
+            }
+        };
+        archive
+            .append_file(format!("artifacts/{}", id), &mut file)
+            .with_context(|| format!("failed to archive {}", path.display()))?;
+    }
+
+    if !missing.is_empty() {
+        eprintln!(
+            "{} file(s) were deleted during the backup and will not be included",
+            missing.len()
+        );
+        rt.block_on(drop_rows(&snapshot, &missing))?;
+    }
+
+    archive
+        .append_path_with_name(&snapshot, DB_FILE_NAME)
+        .context("failed to archive database snapshot")?;
+
+    let manifest = serde_json::json!({
+        "format": BACKUP_FORMAT,
+        "created_at": humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        "files": asset_ids.len() - missing.len(),
+    });
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive
+        .append_data(&mut header, MANIFEST_NAME, manifest.as_slice())
+        .context("failed to write manifest")?;
+
+    let mut file = archive
+        .into_inner()
+        .context("failed to finish archive")?
+        .finish()
+        .context("failed to finish compression")?;
+    file.flush()?;
+    file.sync_all()?;
+
+    fs::rename(&partial, &opts.out)
+        .with_context(|| format!("failed to write {}", opts.out.display()))?;
+
+    eprintln!(
+        "Backed up {} file(s) to {}",
+        asset_ids.len() - missing.len(),
+        opts.out.display()
+    );
+    Ok(())
+}
+
+/// Copy the live database into `snapshot` with `VACUUM INTO`, which is safe
+/// while the server is writing, and return the asset IDs it contains.
+async fn snapshot_db(db_path: &Path, snapshot: &Path) -> Result<Vec<String>> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(db_path)
+        .connect()
+        .await
+        .with_context(|| format!("failed to open database at {}", db_path.display()))?;
+
+    sqlx::query("VACUUM INTO ?")
+        .bind(snapshot.to_string_lossy())
+        .execute(&mut conn)
+        .await
+        .context("failed to snapshot database")?;
+    conn.close().await?;
+
+    let mut conn = SqliteConnectOptions::new()
+        .filename(snapshot)
+        .connect()
+        .await
+        .context("failed to open database snapshot")?;
+    let ids = sqlx::query_scalar::<_, String>("SELECT id FROM assets ORDER BY id")
+        .fetch_all(&mut conn)
+        .await?;
+    conn.close().await?;
+
+    Ok(ids)
+}
+
+/// Drop the rows of deleted assets. A primary asset is only deleted with
+/// its version, so a version sharing the ID is dropped too.
+async fn drop_rows(snapshot: &Path, ids: &[String]) -> Result<()> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(snapshot)
+        .foreign_keys(true)
+        .connect()
+        .await
+        .context("failed to open database snapshot")?;
+
+    for id in ids {
+        for table in ["assets", "artifacts"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE id = ?", table))
+                .bind(id)
+                .execute(&mut conn)
+                .await?;
+        }
+    }
+    conn.close().await?;
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use cask_types::BreakdownQuery;
+
+use crate::cli::ClientsOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: ClientsOpts) -> Result<()> {
+    client::block_on(clients(opts))?
+}
+
+async fn clients(opts: ClientsOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let query = BreakdownQuery {
+        name: opts.name,
+        version: opts.version,
+        from: opts.from,
+        to: opts.to,
+        exclude_bots: opts.exclude_bots,
+    };
+
+    if opts.referrers {
+        let referrers = client.referrer_stats(&query).await?;
+        if opts.client.json {
+            print_json(&referrers);
+            return Ok(());
+        }
+        let rows: Vec<Vec<String>> = referrers
+            .into_iter()
+            .map(|r| {
+                vec![
+                    r.referrer.unwrap_or_else(|| "-".to_string()),
+                    r.downloads.to_string(),
+                    r.unique_ips.to_string(),
+                ]
+            })
+            .collect();
+        print_table(&["REFERRER", "DOWNLOADS", "UNIQUE IPS"], &rows);
+        return Ok(());
+    }
+
+    let clients = client.client_stats(&query).await?;
+    if opts.client.json {
+        print_json(&clients);
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = clients
+        .into_iter()
+        .map(|c| {
+            vec![
+                c.client,
+                if c.bot { "yes" } else { "no" }.to_string(),
+                c.downloads.to_string(),
+                c.unique_ips.to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["CLIENT", "BOT", "DOWNLOADS", "UNIQUE IPS"], &rows);
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result, bail};
+use cask_types::FsckProblemKind;
+
+use crate::cli::FsckOpts;
+use crate::client::{print_json, print_table};
+use crate::db;
+use crate::fsck;
+
+pub fn execute(opts: FsckOpts) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new().context("failed to create tokio runtime")?;
+    let report = rt.block_on(async {
+        let pool = db::open_existing(&opts.data_dir, opts.repair).await?;
+        fsck::run(&pool, &opts.data_dir, opts.repair).await
+    })?;
+
+    if opts.json {
+        print_json(&report);
+    } else if !report.problems.is_empty() {
+        let rows: Vec<Vec<String>> = report
+            .problems
+            .iter()
+            .map(|p| {
+                let artifact = match (&p.name, &p.version, &p.filename) {
+                    (Some(name), Some(version), Some(filename)) => {
+                        format!("{}/{} {}", name, version, filename)
+                    }
+                    (Some(name), Some(version), None) => format!("{}/{}", name, version),
+                    _ => "-".to_string(),
+                };
+                vec![
+                    kind_label(p.kind).to_string(),
+                    p.id.clone(),
+                    artifact,
+                    p.detail.clone(),
+                ]
+            })
+            .collect();
+        print_table(&["PROBLEM", "ID", "ARTIFACT", "DETAIL"], &rows);
+    }
+
+    eprintln!(
+        "Checked {} file(s), {} problem(s) found{}",
+        report.checked,
+        report.problems.len(),
+        if report.repaired && !report.problems.is_empty() {
+            "; broken artifacts marked and orphans moved to quarantine/"
+        } else {
+            ""
+        }
+    );
+
+    if !report.problems.is_empty() && !report.repaired {
+        bail!("integrity check failed; rerun with --repair to quarantine and mark");
+    }
+    Ok(())
+}
+
+fn kind_label(kind: FsckProblemKind) -> &'static str {
+    match kind {
+        FsckProblemKind::Missing => "missing",
+        FsckProblemKind::SizeMismatch => "size mismatch",
+        FsckProblemKind::ChecksumMismatch => "checksum mismatch",
+        FsckProblemKind::Orphan => "orphan",
+    }
+}
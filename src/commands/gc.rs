@@ -0,0 +1,46 @@
+use anyhow::Result;
+
+use crate::cli::GcOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: GcOpts) -> Result<()> {
+    client::block_on(gc(opts))?
+}
+
+async fn gc(opts: GcOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let report = client.gc(opts.dry_run).await?;
+
+    if opts.client.json {
+        print_json(&report);
+        return Ok(());
+    }
+
+    if !report.deleted.is_empty() {
+        let rows: Vec<Vec<String>> = report
+            .deleted
+            .iter()
+            .map(|c| {
+                vec![
+                    format!("{}/{}", c.name, c.version),
+                    c.size.to_string(),
+                    c.rule_id.clone(),
+                    c.reason.clone(),
+                ]
+            })
+            .collect();
+        print_table(&["ARTIFACT", "SIZE", "RULE", "REASON"], &rows);
+    }
+
+    eprintln!(
+        "{} {} version(s), {} bytes",
+        if report.dry_run {
+            "Would delete"
+        } else {
+            "Deleted"
+        },
+        report.deleted.len(),
+        report.freed_bytes
+    );
+    Ok(())
+}
@@ -1,50 +1,557 @@
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use regex::Regex;
+use tracing::Level;
 
 use crate::cli::LogOpts;
+use crate::logfile::{LOG_FILE_NAME, rotated_path};
+
+/// How much to read per step when scanning a file backwards.
+const CHUNK_SIZE: u64 = 64 * 1024;
 
 pub fn execute(opts: LogOpts) -> Result<()> {
-    let log_path = opts.data_dir.join("cask.log");
+    let log_path = opts.data_dir.join(LOG_FILE_NAME);
     if !log_path.exists() {
         bail!("no log file found at {}", log_path.display());
     }
 
-    let file = File::open(&log_path)
-        .with_context(|| format!("failed to open {}", log_path.display()))?;
-    let reader = BufReader::new(&file);
+    let filter = Filter {
+        level: opts.level,
+        since: opts.since.as_deref().map(parse_time).transpose()?,
+        until: opts.until.as_deref().map(parse_time).transpose()?,
+        grep: opts
+            .grep
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("invalid --grep pattern")?,
+    };
 
-    // Read all lines, then take last N
-    let lines: Vec<String> = reader.lines().collect::<io::Result<Vec<_>>>()?;
-    let start = lines.len().saturating_sub(opts.n);
-    for line in &lines[start..] {
-        println!("{}", line);
+    // Collect matches newest-first, walking back through rotated files
+    // until we have N or run past --since.
+    let mut selected = Vec::new();
+    let file =
+        File::open(&log_path).with_context(|| format!("failed to open {}", log_path.display()))?;
+    let mut exhausted = collect(ReverseLines::new(file)?, &filter, opts.n, &mut selected)?;
+
+    let mut index = 1;
+    while !exhausted && selected.len() < opts.n {
+        let Some(older) = open_rotated(&log_path, index)? else {
+            break;
+        };
+        exhausted = collect(older, &filter, opts.n, &mut selected)?;
+        index += 1;
+    }
+
+    for line in selected.iter().rev() {
+        print_line(line, opts.json);
     }
 
     if opts.f {
-        // Seek to end and follow
-        let mut file = File::open(&log_path)?;
-        file.seek(SeekFrom::End(0))?;
-        let mut reader = BufReader::new(file);
-        let mut line = String::new();
+        follow(&log_path, &filter, opts.json)?;
+    }
+
+    Ok(())
+}
+
+struct Filter {
+    level: Option<Level>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+    grep: Option<Regex>,
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry) -> bool {
+        if let Some(max) = self.level
+            && entry.level.is_none_or(|l| l > max)
+        {
+            return false;
+        }
+        if let Some(since) = self.since
+            && entry.timestamp.is_none_or(|t| t < since)
+        {
+            return false;
+        }
+        if let Some(until) = self.until
+            && entry.timestamp.is_none_or(|t| t >= until)
+        {
+            return false;
+        }
+        if let Some(re) = &self.grep
+            && !re.is_match(entry.line)
+        {
+            return false;
+        }
+        true
+    }
+
+    /// True once a backwards scan has reached entries older than `--since`.
+    fn before_window(&self, entry: &Entry) -> bool {
+        matches!((self.since, entry.timestamp), (Some(since), Some(t)) if t < since)
+    }
+}
+
+/// A log line with the fields we can filter on. Both the text and JSON
+/// formats written by `tracing_subscriber::fmt` are understood; anything
+/// else (panic output, wrapped lines) has no timestamp or level.
+struct Entry<'a> {
+    line: &'a str,
+    json: bool,
+    timestamp: Option<SystemTime>,
+    level: Option<Level>,
+    message: &'a str,
+}
+
+impl<'a> Entry<'a> {
+    fn parse(line: &'a str) -> Self {
+        if line.starts_with('{')
+            && let Ok(value) = serde_json::from_str::<serde_json::Value>(line)
+        {
+            return Self {
+                line,
+                json: true,
+                timestamp: value["timestamp"]
+                    .as_str()
+                    .and_then(|t| humantime::parse_rfc3339(t).ok()),
+                level: value["level"].as_str().and_then(|l| l.parse().ok()),
+                message: line,
+            };
+        }
+
+        // `<timestamp> <level> <message>`, with the level right-aligned.
+        let (first, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let (second, rest) = rest.trim_start().split_once(' ').unwrap_or((rest, ""));
+        let timestamp = humantime::parse_rfc3339(first).ok();
+        let level = second.parse().ok();
+        let message = match (timestamp, level) {
+            (Some(_), Some(_)) => rest,
+            _ => line,
+        };
+
+        Self {
+            line,
+            json: false,
+            timestamp,
+            level,
+            message,
+        }
+    }
+}
+
+fn print_line(line: &str, json: bool) {
+    let entry = Entry::parse(line);
+    if !json || entry.json {
+        println!("{}", line);
+        return;
+    }
+
+    let timestamp = entry
+        .timestamp
+        .map(|t| humantime::format_rfc3339_micros(t).to_string());
+    let level = entry.level.map(|l| l.to_string());
+    let object = serde_json::json!({
+        "timestamp": timestamp,
+        "level": level,
+        "message": entry.message,
+    });
+    println!("{}", object);
+}
+
+/// Parse `--since`/`--until`: either a duration before now or a timestamp.
+fn parse_time(value: &str) -> Result<SystemTime> {
+    if let Ok(ago) = humantime::parse_duration(value) {
+        return SystemTime::now()
+            .checked_sub(ago)
+            .context("duration is too far in the past");
+    }
+    humantime::parse_rfc3339_weak(value).with_context(|| {
+        format!(
+            "invalid time '{}': expected e.g. 1h or 2024-01-01T00:00:00Z",
+            value
+        )
+    })
+}
+
+/// Push matching lines from a newest-first iterator into `out` until it holds
+/// `want` lines. Returns true if the scan reached entries older than `--since`,
+/// meaning older files need not be read.
+fn collect(
+    lines: impl Iterator<Item = io::Result<String>>,
+    filter: &Filter,
+    want: usize,
+    out: &mut Vec<String>,
+) -> Result<bool> {
+    for line in lines {
+        if out.len() >= want {
+            return Ok(false);
+        }
+        let line = line?;
+        let entry = Entry::parse(&line);
+        if filter.before_window(&entry) {
+            return Ok(true);
+        }
+        if filter.matches(&entry) {
+            out.push(line);
+        }
+    }
+    Ok(false)
+}
+
+/// Newest-first lines of the `index`-th rotated file, if it exists.
+fn open_rotated(
+    log_path: &Path,
+    index: usize,
+) -> Result<Option<Box<dyn Iterator<Item = io::Result<String>>>>> {
+    let plain = rotated_path(log_path, index, false);
+    if plain.exists() {
+        let file =
+            File::open(&plain).with_context(|| format!("failed to open {}", plain.display()))?;
+        return Ok(Some(Box::new(ReverseLines::new(file)?)));
+    }
+
+    // Compressed files can't be read backwards; they are bounded by the
+    // rotation policy, so decompressing one fully is acceptable.
+    let gz = rotated_path(log_path, index, true);
+    if gz.exists() {
+        let file = File::open(&gz).with_context(|| format!("failed to open {}", gz.display()))?;
+        let lines = read_lines(GzDecoder::new(file))?;
+        return Ok(Some(Box::new(lines.into_iter().rev().map(Ok))));
+    }
+
+    Ok(None)
+}
+
+fn read_lines(reader: impl Read) -> Result<Vec<String>> {
+    let lines = BufReader::new(reader)
+        .lines()
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(lines)
+}
+
+/// Iterates a file's lines from the end towards the start, reading it in
+/// fixed-size chunks so large logs never have to fit in memory.
+struct ReverseLines {
+    file: File,
+    /// Offset of the first byte held in `pending`.
+    pos: u64,
+    /// Bytes from `pos` onwards that have been read but not yet returned.
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl ReverseLines {
+    fn new(mut file: File) -> io::Result<Self> {
+        let mut pos = file.metadata()?.len();
+
+        // A trailing newline terminates the last line rather than starting
+        // an empty one.
+        if pos > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::Start(pos - 1))?;
+            file.read_exact(&mut last)?;
+            if last[0] == b'\n' {
+                pos -= 1;
+            }
+        }
+
+        Ok(Self {
+            file,
+            pos,
+            pending: Vec::new(),
+            done: false,
+        })
+    }
+
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let start = self.pos.saturating_sub(CHUNK_SIZE);
+        let mut chunk = vec![0u8; (self.pos - start) as usize];
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&self.pending);
+        self.pending = chunk;
+        self.pos = start;
+        Ok(())
+    }
+}
+
+impl Iterator for ReverseLines {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
         loop {
-            line.clear();
-            match reader.read_line(&mut line) {
-                Ok(0) => {
-                    thread::sleep(Duration::from_millis(100));
+            if self.done {
+                return None;
+            }
+            if let Some(i) = self.pending.iter().rposition(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&self.pending[i + 1..]).into_owned();
+                self.pending.truncate(i);
+                return Some(Ok(line));
+            }
+            if self.pos == 0 {
+                self.done = true;
+                if self.pending.is_empty() {
+                    return None;
                 }
-                Ok(_) => {
-                    print!("{}", line);
+                let line = String::from_utf8_lossy(&self.pending).into_owned();
+                return Some(Ok(line));
+            }
+            if let Err(e) = self.read_chunk() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Print matching lines as they are appended. The log is reopened when it
+/// is rotated away and re-read from the start when it is truncated.
+fn follow(log_path: &Path, filter: &Filter, json: bool) -> Result<()> {
+    let mut file = File::open(log_path)?;
+    file.seek(SeekFrom::End(0))?;
+    let mut inode = file.metadata()?.ino();
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => {
+                if let Ok(meta) = fs::metadata(log_path) {
+                    if meta.ino() != inode {
+                        let file = File::open(log_path)?;
+                        inode = file.metadata()?.ino();
+                        reader = BufReader::new(file);
+                        continue;
+                    }
+                    if meta.len() < reader.stream_position()? {
+                        eprintln!("cask log: file truncated");
+                        reader.seek(SeekFrom::Start(0))?;
+                        continue;
+                    }
                 }
-                Err(e) => {
-                    bail!("error reading log: {}", e);
+                thread::sleep(Duration::from_millis(100));
+            }
+            Ok(_) => {
+                let text = line.trim_end_matches('\n');
+                if filter.matches(&Entry::parse(text)) {
+                    print_line(text, json);
                 }
             }
+            Err(e) => {
+                bail!("error reading log: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn reverse(contents: &[u8]) -> Vec<String> {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(contents).unwrap();
+        ReverseLines::new(file)
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    fn forward_reversed(text: &str) -> Vec<String> {
+        text.lines().rev().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn reverse_lines_of_small_files() {
+        assert!(reverse(b"").is_empty());
+        assert_eq!(reverse(b"one\ntwo\n"), ["two", "one"]);
+        assert_eq!(reverse(b"one\n\nthree\n"), ["three", "", "one"]);
+    }
+
+    #[test]
+    fn reverse_lines_keeps_a_truncated_last_line() {
+        assert_eq!(reverse(b"one\ntwo\nthr"), ["thr", "two", "one"]);
+        assert_eq!(reverse(b"partial"), ["partial"]);
+    }
+
+    #[test]
+    fn reverse_lines_across_chunk_boundaries() {
+        // Lines of varying length, so several of them straddle a multiple
+        // of the chunk size.
+        let mut text = String::new();
+        for i in 0..20_000 {
+            text.push_str(&format!("line {} {}\n", i, "x".repeat(i % 17)));
         }
+        assert!(text.len() as u64 > 3 * CHUNK_SIZE);
+        assert_eq!(reverse(text.as_bytes()), forward_reversed(&text));
     }
 
-    Ok(())
+    #[test]
+    fn reverse_lines_with_a_boundary_on_a_newline() {
+        let middle = "a".repeat(CHUNK_SIZE as usize - 5);
+        let text = format!("{}\n{}\nlast\n", "b".repeat(10), middle);
+        // The chunk read first starts exactly after the first line's newline.
+        assert_eq!(text.len() as u64 - 1 - CHUNK_SIZE, 11);
+        assert_eq!(reverse(text.as_bytes()), forward_reversed(&text));
+    }
+
+    #[test]
+    fn reverse_lines_longer_than_a_chunk() {
+        let long = "y".repeat(3 * CHUNK_SIZE as usize + 5);
+        let text = format!("first\n{}\nlast", long);
+        assert_eq!(reverse(text.as_bytes()), ["last", long.as_str(), "first"]);
+    }
+
+    #[test]
+    fn reverse_lines_keeps_characters_split_by_a_chunk() {
+        // 'é' is two bytes; put one across the boundary of the last chunk.
+        let tail = format!("é{}", "z".repeat(CHUNK_SIZE as usize - 1));
+        let text = format!("before\n{}\n", tail);
+        assert_eq!(reverse(text.as_bytes()), [tail.as_str(), "before"]);
+    }
+
+    const TEXT_LINE: &str = "2024-05-01T12:00:00.000000Z  WARN cask::scrub: disk is slow";
+    const JSON_LINE: &str = r#"{"timestamp":"2024-05-01T13:00:00.000000Z","level":"ERROR","fields":{"message":"boom"}}"#;
+
+    fn at(timestamp: &str) -> SystemTime {
+        humantime::parse_rfc3339(timestamp).unwrap()
+    }
+
+    #[test]
+    fn parses_text_entries() {
+        let entry = Entry::parse(TEXT_LINE);
+        assert!(!entry.json);
+        assert_eq!(entry.timestamp, Some(at("2024-05-01T12:00:00Z")));
+        assert_eq!(entry.level, Some(Level::WARN));
+        assert_eq!(entry.message, "cask::scrub: disk is slow");
+    }
+
+    #[test]
+    fn parses_json_entries() {
+        let entry = Entry::parse(JSON_LINE);
+        assert!(entry.json);
+        assert_eq!(entry.timestamp, Some(at("2024-05-01T13:00:00Z")));
+        assert_eq!(entry.level, Some(Level::ERROR));
+    }
+
+    #[test]
+    fn other_lines_have_no_fields() {
+        for line in ["thread 'main' panicked at src/main.rs:1:1", "", "{not json"] {
+            let entry = Entry::parse(line);
+            assert_eq!(entry.timestamp, None);
+            assert_eq!(entry.level, None);
+            assert_eq!(entry.message, line);
+        }
+    }
+
+    fn filter() -> Filter {
+        Filter {
+            level: None,
+            since: None,
+            until: None,
+            grep: None,
+        }
+    }
+
+    #[test]
+    fn filters_by_level() {
+        let warn = Filter {
+            level: Some(Level::WARN),
+            ..filter()
+        };
+        assert!(warn.matches(&Entry::parse(TEXT_LINE)));
+        assert!(warn.matches(&Entry::parse(JSON_LINE)));
+        let info = TEXT_LINE.replace(" WARN", " INFO");
+        assert!(!warn.matches(&Entry::parse(&info)));
+        assert!(!warn.matches(&Entry::parse("no level here")));
+    }
+
+    #[test]
+    fn filters_by_time_window() {
+        let window = Filter {
+            since: Some(at("2024-05-01T12:00:00Z")),
+            until: Some(at("2024-05-01T13:00:00Z")),
+            ..filter()
+        };
+        // `since` is inclusive and `until` exclusive.
+        assert!(window.matches(&Entry::parse(TEXT_LINE)));
+        assert!(!window.matches(&Entry::parse(JSON_LINE)));
+        assert!(!window.matches(&Entry::parse("no timestamp")));
+
+        let earlier = TEXT_LINE.replace("12:00:00", "11:59:59");
+        assert!(window.before_window(&Entry::parse(&earlier)));
+        assert!(!window.before_window(&Entry::parse(TEXT_LINE)));
+        assert!(!window.before_window(&Entry::parse("no timestamp")));
+    }
+
+    #[test]
+    fn filters_by_pattern_on_the_whole_line() {
+        let grep = Filter {
+            grep: Some(Regex::new("scrub|boom").unwrap()),
+            ..filter()
+        };
+        assert!(grep.matches(&Entry::parse(TEXT_LINE)));
+        assert!(grep.matches(&Entry::parse(JSON_LINE)));
+        assert!(!grep.matches(&Entry::parse("unrelated")));
+    }
+
+    #[test]
+    fn collect_stops_before_since() {
+        let lines = [
+            "2024-05-01T12:00:02Z  INFO c",
+            "2024-05-01T12:00:01Z  INFO b",
+            "2024-05-01T11:00:00Z  INFO old",
+            "2024-05-01T12:30:00Z  INFO out of order",
+        ];
+        let since = Filter {
+            since: Some(at("2024-05-01T12:00:00Z")),
+            ..filter()
+        };
+        let mut out = Vec::new();
+        let exhausted = collect(
+            lines.iter().map(|l| Ok(l.to_string())),
+            &since,
+            10,
+            &mut out,
+        )
+        .unwrap();
+        assert!(exhausted);
+        assert_eq!(out, &lines[..2]);
+
+        let mut out = Vec::new();
+        let exhausted = collect(
+            lines.iter().map(|l| Ok(l.to_string())),
+            &filter(),
+            1,
+            &mut out,
+        )
+        .unwrap();
+        assert!(!exhausted);
+        assert_eq!(out, &lines[..1]);
+    }
+
+    #[test]
+    fn parses_since_and_until() {
+        assert_eq!(
+            parse_time("2024-05-01T12:00:00Z").unwrap(),
+            at("2024-05-01T12:00:00Z")
+        );
+        assert_eq!(
+            parse_time("2024-05-01 12:00:00").unwrap(),
+            at("2024-05-01T12:00:00Z")
+        );
+        let hour_ago = parse_time("1h").unwrap();
+        let elapsed = SystemTime::now().duration_since(hour_ago).unwrap();
+        assert!(elapsed >= Duration::from_secs(3600) && elapsed < Duration::from_secs(3660));
+        assert!(parse_time("yesterday").is_err());
+    }
 }
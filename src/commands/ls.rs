@@ -0,0 +1,118 @@
+use anyhow::Result;
+use cask_client::Client;
+use cask_types::{ListQuery, ListSort, SortOrder};
+
+use crate::cli::LsOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: LsOpts) -> Result<()> {
+    client::block_on(ls(opts))?
+}
+
+async fn ls(opts: LsOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+
+    let sort = opts.sort.map(ListSort::from).unwrap_or(ListSort::Name);
+    // The server sorts names ascending and everything else descending.
+    let order = match (sort, opts.reverse) {
+        (_, false) => None,
+        (ListSort::Name, true) => Some(SortOrder::Desc),
+        (_, true) => Some(SortOrder::Asc),
+    };
+    let query = ListQuery {
+        limit: opts.limit,
+        cursor: opts.cursor,
+        sort: opts.sort.map(Into::into),
+        order,
+        prefix: opts.prefix,
+        created_after: opts.created_after,
+        created_before: opts.created_before,
+        min_size: opts.min_size,
+        max_size: opts.max_size,
+        yanked: opts.yanked,
+        tagged: opts.tagged,
+        meta: opts.meta,
+        grouped: false,
+    };
+
+    if opts.grouped {
+        return ls_grouped(&client, &query, opts.client.json).await;
+    }
+
+    let (rows, next_cursor) = if opts.limit.is_some() {
+        let page = client.list_page(opts.name.as_deref(), &query).await?;
+        (page.items, page.next_cursor)
+    } else {
+        (client.list_all(opts.name.as_deref(), &query).await?, None)
+    };
+
+    if opts.client.json {
+        print_json(&rows);
+    } else {
+        let rows: Vec<Vec<String>> = rows
+            .into_iter()
+            .map(|a| {
+                // Other assets are only counted; `cask assets` lists them.
+                let filename = match a.assets.len() {
+                    0 | 1 => a.filename,
+                    n => format!("{} (+{})", a.filename, n - 1),
+                };
+                vec![
+                    a.name,
+                    a.version,
+                    filename,
+                    a.size.to_string(),
+                    a.created_at,
+                    if a.yanked_at.is_some() { "yes" } else { "" }.to_string(),
+                ]
+            })
+            .collect();
+        print_table(
+            &["NAME", "VERSION", "FILENAME", "SIZE", "CREATED", "YANKED"],
+            &rows,
+        );
+    }
+    if let Some(cursor) = next_cursor {
+        eprintln!("More results: --cursor {}", cursor);
+    }
+    Ok(())
+}
+
+/// One row per name; every page unless a limit was given.
+async fn ls_grouped(client: &Client, query: &ListQuery, json: bool) -> Result<()> {
+    let mut query = query.clone();
+    let mut packages = Vec::new();
+    let next_cursor = loop {
+        let page = client.list_packages(&query).await?;
+        packages.extend(page.items);
+        match page.next_cursor {
+            Some(cursor) if query.limit.is_none() => query.cursor = Some(cursor),
+            next => break next,
+        }
+    };
+
+    if json {
+        print_json(&packages);
+    } else {
+        let rows: Vec<Vec<String>> = packages
+            .into_iter()
+            .map(|p| {
+                vec![
+                    p.name,
+                    p.latest_version.unwrap_or_default(),
+                    p.versions.to_string(),
+                    p.downloads.to_string(),
+                    p.description.unwrap_or_default(),
+                ]
+            })
+            .collect();
+        print_table(
+            &["NAME", "LATEST", "VERSIONS", "DOWNLOADS", "DESCRIPTION"],
+            &rows,
+        );
+    }
+    if let Some(cursor) = next_cursor {
+        eprintln!("More results: --cursor {}", cursor);
+    }
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use crate::cli::{MetaCommand, MetaOpts};
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: MetaOpts) -> Result<()> {
+    client::block_on(meta(opts.command))?
+}
+
+async fn meta(command: MetaCommand) -> Result<()> {
+    match command {
+        MetaCommand::Get {
+            client,
+            name,
+            version,
+        } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let meta = client.get_metadata(&name, &version).await?;
+
+            if json {
+                print_json(&meta);
+                return Ok(());
+            }
+
+            let mut rows = vec![
+                vec!["sha256".to_string(), meta.sha256],
+                vec!["created_at".to_string(), meta.created_at],
+            ];
+            rows.extend(
+                meta.custom
+                    .into_iter()
+                    .map(|(k, v)| vec![k, display_value(Some(v))]),
+            );
+            print_table(&["KEY", "VALUE"], &rows);
+        }
+
+        MetaCommand::Set {
+            client,
+            name,
+            version,
+            pairs,
+        } => {
+            let body = parse_values(&pairs)?;
+            let client = client::connect(&client)?;
+            client.set_metadata(&name, &version, &body).await?;
+        }
+
+        MetaCommand::Patch {
+            client,
+            name,
+            version,
+            patch,
+        } => {
+            let patch = if patch == "-" {
+                let mut text = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut text)
+                    .context("failed to read patch from stdin")?;
+                text
+            } else {
+                patch
+            };
+            let patch: Value = serde_json::from_str(&patch).context("patch is not valid JSON")?;
+            let client = client::connect(&client)?;
+            client.patch_metadata(&name, &version, &patch).await?;
+        }
+
+        MetaCommand::Rm {
+            client,
+            name,
+            version,
+            key,
+        } => {
+            let client = client::connect(&client)?;
+            client.delete_metadata(&name, &version, &key).await?;
+        }
+
+        MetaCommand::History {
+            client,
+            name,
+            version,
+            key,
+            n,
+        } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let changes = client
+                .metadata_history(&name, &version, key.as_deref(), n)
+                .await?;
+
+            if json {
+                print_json(&changes);
+                return Ok(());
+            }
+
+            let rows: Vec<Vec<String>> = changes
+                .into_iter()
+                .map(|c| {
+                    vec![
+                        c.at,
+                        c.actor,
+                        c.key,
+                        display_value(c.old_value),
+                        display_value(c.new_value),
+                    ]
+                })
+                .collect();
+            print_table(&["TIME", "ACTOR", "KEY", "OLD", "NEW"], &rows);
+        }
+    }
+
+    Ok(())
+}
+
+/// Strings unquoted, other values as JSON, and `-` for none.
+pub(crate) fn display_value(value: Option<Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s,
+        Some(v) => v.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+/// `key=value` pairs as strings, and `key:=<json>` pairs as typed values.
+pub(crate) fn parse_values(pairs: &[String]) -> Result<HashMap<String, Value>> {
+    pairs
+        .iter()
+        .map(|pair| match pair.split_once('=') {
+            Some((key, json)) if key.len() > 1 && key.ends_with(':') => {
+                let value = serde_json::from_str(json)
+                    .with_context(|| format!("invalid JSON value in '{}'", pair))?;
+                Ok((key[..key.len() - 1].to_string(), value))
+            }
+            Some((key, value)) if !key.is_empty() => {
+                Ok((key.to_string(), Value::String(value.to_string())))
+            }
+            _ => bail!("expected key=value or key:=json, got '{}'", pair),
+        })
+        .collect()
+}
@@ -1,5 +1,29 @@
+pub mod assets;
+pub mod audit;
+pub mod backup;
+pub mod clients;
+pub mod fsck;
+pub mod gc;
 pub mod log;
+pub mod ls;
+pub mod meta;
+pub mod package;
 pub mod pid;
+pub mod pull;
+pub mod push;
+pub mod quotas;
+pub mod read_only;
+pub mod readme;
+pub mod restore;
+pub mod retention;
+pub mod rm;
 pub mod run;
+pub mod schemas;
+pub mod search;
 pub mod start;
+pub mod stats;
 pub mod stop;
+pub mod tokens;
+pub mod top;
+pub mod usage;
+pub mod yank;
@@ -0,0 +1,67 @@
+use anyhow::Result;
+
+use crate::cli::{PackageCommand, PackageOpts};
+use crate::client::{self, print_json, print_table};
+use crate::commands::meta::{display_value, parse_values};
+
+pub fn execute(opts: PackageOpts) -> Result<()> {
+    client::block_on(package(opts.command))?
+}
+
+async fn package(command: PackageCommand) -> Result<()> {
+    match command {
+        PackageCommand::Get { client, name } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let package = client.package(&name).await?;
+
+            if json {
+                print_json(&package);
+                return Ok(());
+            }
+
+            let summary = package.summary;
+            let mut rows = vec![
+                vec!["name".to_string(), summary.name],
+                vec![
+                    "latest_version".to_string(),
+                    summary.latest_version.unwrap_or_default(),
+                ],
+                vec!["versions".to_string(), summary.versions.to_string()],
+                vec!["downloads".to_string(), summary.downloads.to_string()],
+                vec![
+                    "updated_at".to_string(),
+                    summary.updated_at.unwrap_or_default(),
+                ],
+                vec![
+                    "readme".to_string(),
+                    if package.has_readme { "yes" } else { "no" }.to_string(),
+                ],
+            ];
+            rows.extend(
+                package
+                    .metadata
+                    .into_iter()
+                    .map(|(k, v)| vec![k, display_value(Some(v))]),
+            );
+            print_table(&["KEY", "VALUE"], &rows);
+        }
+
+        PackageCommand::Set {
+            client,
+            name,
+            pairs,
+        } => {
+            let body = parse_values(&pairs)?;
+            let client = client::connect(&client)?;
+            client.set_package_metadata(&name, &body).await?;
+        }
+
+        PackageCommand::Rm { client, name, key } => {
+            let client = client::connect(&client)?;
+            client.delete_package_metadata(&name, &key).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -13,10 +13,7 @@ pub fn execute(opts: DataDirOpt) -> Result<()> {
     }
 
     let contents = fs::read_to_string(&pid_path).context("failed to read PID file")?;
-    let pid: i32 = contents
-        .trim()
-        .parse()
-        .context("invalid PID in PID file")?;
+    let pid: i32 = contents.trim().parse().context("invalid PID in PID file")?;
 
     // Verify the process is alive
     if signal::kill(Pid::from_raw(pid), None).is_err() {
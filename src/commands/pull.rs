@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::cli::PullOpts;
+use crate::client::{self, print_json, progress_bar};
+
+pub fn execute(opts: PullOpts) -> Result<()> {
+    client::block_on(pull(opts))?
+}
+
+async fn pull(opts: PullOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let mut download = match &opts.asset {
+        Some(filename) => {
+            client
+                .download_asset(&opts.name, &opts.version, filename)
+                .await?
+        }
+        None => client.download(&opts.name, &opts.version).await?,
+    };
+
+    let output = match opts.output {
+        Some(path) => path,
+        None => PathBuf::from(
+            download
+                .filename()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{}-{}", opts.name, opts.version)),
+        ),
+    };
+
+    // Write to a sibling temp file so a failed or corrupt download never
+    // replaces an existing file.
+    let mut partial = output.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let progress = progress_bar(download.content_length(), opts.client.json);
+    let mut file = fs::File::create(&partial)
+        .await
+        .with_context(|| format!("failed to create {}", partial.display()))?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = download.chunk().await.context("download interrupted")? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        progress.inc(chunk.len() as u64);
+    }
+    file.flush().await?;
+    progress.finish_and_clear();
+
+    let sha256: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let Some(expected) = download.sha256() else {
+        let _ = fs::remove_file(&partial).await;
+        bail!(
+            "server did not send a checksum for {}/{}",
+            opts.name,
+            opts.version
+        );
+    };
+    if sha256 != expected {
+        let _ = fs::remove_file(&partial).await;
+        bail!(
+            "checksum mismatch for {}/{}: expected {}, got {}",
+            opts.name,
+            opts.version,
+            expected,
+            sha256
+        );
+    }
+
+    fs::rename(&partial, &output)
+        .await
+        .with_context(|| format!("failed to write {}", output.display()))?;
+
+    if opts.client.json {
+        print_json(&serde_json::json!({
+            "path": output,
+            "sha256": sha256,
+        }));
+    } else {
+        println!(
+            "Pulled {}/{} to {}",
+            opts.name,
+            opts.version,
+            output.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+
+use crate::cli::PushOpts;
+use crate::client::{self, print_json, progress_bar};
+use crate::commands::meta::parse_values;
+
+pub fn execute(opts: PushOpts) -> Result<()> {
+    client::block_on(push(opts))?
+}
+
+async fn push(opts: PushOpts) -> Result<()> {
+    let metadata = parse_values(&opts.meta)?;
+    let client = client::connect(&opts.client)?;
+
+    let len = tokio::fs::metadata(&opts.file)
+        .await
+        .with_context(|| format!("failed to open {}", opts.file.display()))?
+        .len();
+
+    let filename = match opts.filename {
+        Some(filename) => filename,
+        None => opts
+            .file
+            .file_name()
+            .context("upload path has no file name")?
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    let progress = progress_bar(Some(len), opts.client.json);
+    let bar = progress.clone();
+    let on_progress = move |sent| bar.set_position(sent);
+
+    if opts.asset {
+        let result = client
+            .upload_asset(
+                &opts.name,
+                &opts.version,
+                &filename,
+                &opts.file,
+                on_progress,
+            )
+            .await;
+        progress.finish_and_clear();
+        let asset = result?;
+
+        if opts.client.json {
+            print_json(&asset);
+        } else {
+            println!(
+                "Pushed {} to {}/{} ({} bytes, sha256 {})",
+                asset.filename, opts.name, opts.version, asset.size, asset.sha256
+            );
+        }
+        return Ok(());
+    }
+
+    let result = if metadata.is_empty() {
+        client
+            .upload_file_with_progress(
+                &opts.name,
+                &opts.version,
+                &opts.file,
+                Some(&filename),
+                on_progress,
+            )
+            .await
+    } else {
+        client
+            .upload_file_with_metadata(
+                &opts.name,
+                &opts.version,
+                &opts.file,
+                Some(&filename),
+                &metadata.into_iter().collect(),
+                on_progress,
+            )
+            .await
+    };
+    progress.finish_and_clear();
+    let artifact = result?;
+
+    if opts.client.json {
+        print_json(&artifact);
+    } else {
+        println!(
+            "Pushed {}/{} ({} bytes, sha256 {})",
+            artifact.name, artifact.version, artifact.size, artifact.sha256
+        );
+    }
+    Ok(())
+}
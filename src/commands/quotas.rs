@@ -0,0 +1,89 @@
+use anyhow::Result;
+use cask_types::{QuotaScope, SetQuotaRequest};
+
+use crate::cli::{QuotasCommand, QuotasOpts};
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: QuotasOpts) -> Result<()> {
+    client::block_on(quotas(opts.command))?
+}
+
+async fn quotas(command: QuotasCommand) -> Result<()> {
+    match command {
+        QuotasCommand::Set {
+            client,
+            scope,
+            target,
+            max_bytes,
+            max_artifacts,
+        } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let quota = client
+                .set_quota(&SetQuotaRequest {
+                    scope: scope.into(),
+                    target,
+                    max_bytes,
+                    max_artifacts,
+                })
+                .await?;
+
+            if json {
+                print_json(&quota);
+            } else {
+                eprintln!(
+                    "Set {} quota for '{}' ({})",
+                    scope_label(quota.scope),
+                    quota.target,
+                    quota.id
+                );
+            }
+        }
+
+        QuotasCommand::Ls { client } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let quotas = client.list_quotas().await?;
+
+            if json {
+                print_json(&quotas);
+                return Ok(());
+            }
+
+            let rows: Vec<Vec<String>> = quotas
+                .into_iter()
+                .map(|q| {
+                    vec![
+                        q.id,
+                        scope_label(q.scope).to_string(),
+                        q.target,
+                        limit(q.max_bytes),
+                        limit(q.max_artifacts),
+                    ]
+                })
+                .collect();
+            print_table(
+                &["ID", "SCOPE", "TARGET", "MAX BYTES", "MAX ARTIFACTS"],
+                &rows,
+            );
+        }
+
+        QuotasCommand::Rm { client, id } => {
+            let client = client::connect(&client)?;
+            client.delete_quota(&id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn scope_label(scope: QuotaScope) -> &'static str {
+    match scope {
+        QuotaScope::Prefix => "prefix",
+        QuotaScope::Token => "token",
+    }
+}
+
+pub fn limit(value: Option<i64>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
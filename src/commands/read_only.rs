@@ -0,0 +1,46 @@
+use anyhow::Result;
+use cask_types::ReadOnlyMode;
+
+use crate::cli::{ReadOnlyCommand, ReadOnlyOpts};
+use crate::client::{self, print_json};
+
+pub fn execute(opts: ReadOnlyOpts) -> Result<()> {
+    client::block_on(read_only(opts.command))?
+}
+
+async fn read_only(command: ReadOnlyCommand) -> Result<()> {
+    let (client_opts, mode) = match command {
+        ReadOnlyCommand::On {
+            client,
+            reason,
+            retry_after,
+        } => (
+            client,
+            Some(ReadOnlyMode {
+                enabled: true,
+                reason,
+                retry_after,
+            }),
+        ),
+        ReadOnlyCommand::Off { client } => (client, Some(ReadOnlyMode::default())),
+        ReadOnlyCommand::Status { client } => (client, None),
+    };
+
+    let client = client::connect(&client_opts)?;
+    let mode = match mode {
+        Some(mode) => client.set_read_only(&mode).await?,
+        None => client.read_only().await?,
+    };
+
+    if client_opts.json {
+        print_json(&mode);
+    } else if mode.enabled {
+        println!(
+            "read-only{}",
+            mode.reason.map(|r| format!(": {}", r)).unwrap_or_default()
+        );
+    } else {
+        println!("read-write");
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use std::io::Read;
+
+use anyhow::{Context, Result};
+
+use crate::cli::{ReadmeCommand, ReadmeOpts};
+use crate::client;
+
+pub fn execute(opts: ReadmeOpts) -> Result<()> {
+    client::block_on(readme(opts.command))?
+}
+
+async fn readme(command: ReadmeCommand) -> Result<()> {
+    match command {
+        ReadmeCommand::Get { client, name } => {
+            let client = client::connect(&client)?;
+            print!("{}", client.readme(&name).await?);
+        }
+
+        ReadmeCommand::Set { client, name, file } => {
+            let text = if file.as_os_str() == "-" {
+                let mut text = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut text)
+                    .context("failed to read README from stdin")?;
+                text
+            } else {
+                std::fs::read_to_string(&file)
+                    .with_context(|| format!("failed to read {}", file.display()))?
+            };
+            let client = client::connect(&client)?;
+            client.set_readme(&name, &text).await?;
+        }
+
+        ReadmeCommand::Rm { client, name } => {
+            let client = client::connect(&client)?;
+            client.delete_readme(&name).await?;
+        }
+    }
+
+    Ok(())
+}
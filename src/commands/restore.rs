@@ -0,0 +1,190 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection, Row};
+
+use crate::cli::RestoreOpts;
+use crate::commands::backup::{BACKUP_FORMAT, MANIFEST_NAME};
+use crate::db::DB_FILE_NAME;
+
+pub fn execute(opts: RestoreOpts) -> Result<()> {
+    let data_dir = &opts.data_dir;
+
+    let pid_path = data_dir.join("cask.pid");
+    if let Ok(contents) = fs::read_to_string(&pid_path)
+        && let Ok(pid) = contents.trim().parse::<i32>()
+        && kill(Pid::from_raw(pid), None).is_ok()
+    {
+        bail!(
+            "cask is running (PID {}). Use `cask stop` before restoring.",
+            pid
+        );
+    }
+
+    let has_contents = data_dir.exists()
+        && fs::read_dir(data_dir)
+            .with_context(|| format!("failed to read {}", data_dir.display()))?
+            .next()
+            .is_some();
+    if has_contents && !opts.force {
+        bail!(
+            "{} is not empty. Pass --force to replace it (the old directory is kept as {}).",
+            data_dir.display(),
+            old_path(data_dir).display()
+        );
+    }
+
+    // Unpack next to the destination so the final swap is a rename on the
+    // same filesystem.
+    let parent = match data_dir.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    fs::create_dir_all(&parent)?;
+    let staging = tempfile::Builder::new()
+        .prefix(".cask-restore")
+        .tempdir_in(&parent)
+        .context("failed to create staging directory")?;
+
+    let file = File::open(&opts.archive)
+        .with_context(|| format!("failed to open {}", opts.archive.display()))?;
+    let decoder = zstd::Decoder::new(file).context("failed to start decompression")?;
+    tar::Archive::new(decoder)
+        .unpack(staging.path())
+        .context("failed to unpack backup archive")?;
+
+    let count = validate(staging.path())?;
+    fs::remove_file(staging.path().join(MANIFEST_NAME))?;
+    fs::create_dir_all(staging.path().join("artifacts"))?;
+
+    if data_dir.exists() {
+        let old = old_path(data_dir);
+        if old.exists() {
+            bail!(
+                "{} already exists from a previous restore; remove it first",
+                old.display()
+            );
+        }
+        fs::rename(data_dir, &old)
+            .with_context(|| format!("failed to move {} aside", data_dir.display()))?;
+        eprintln!("Previous data directory moved to {}", old.display());
+    }
+
+    let restored = staging.keep();
+    fs::rename(&restored, data_dir)
+        .with_context(|| format!("failed to move restored data into {}", data_dir.display()))?;
+
+    eprintln!("Restored {} file(s) into {}", count, data_dir.display());
+    Ok(())
+}
+
+fn old_path(data_dir: &Path) -> PathBuf {
+    let mut old = data_dir.as_os_str().to_owned();
+    old.push(".old");
+    PathBuf::from(old)
+}
+
+/// Check an unpacked backup: a known manifest format, a database that passes
+/// `integrity_check`, and a file matching the recorded size and sha256 for
+/// every asset row. Returns the number of files.
+fn validate(dir: &Path) -> Result<usize> {
+    let manifest = fs::read(dir.join(MANIFEST_NAME))
+        .context("archive has no manifest; is it a cask backup?")?;
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&manifest).context("invalid backup manifest")?;
+    let format = manifest["format"].as_u64();
+    if format != Some(BACKUP_FORMAT as u64) {
+        bail!("unsupported backup format {:?}", format);
+    }
+
+    let db_path = dir.join(DB_FILE_NAME);
+    if !db_path.exists() {
+        bail!("archive has no database");
+    }
+
+    let rt = tokio::runtime::Runtime::new().context("failed to create tokio runtime")?;
+    let files = rt.block_on(read_files(&db_path))?;
+
+    let mut problems = Vec::new();
+    for (id, sha256, size) in &files {
+        let path = dir.join("artifacts").join(id);
+        match hash_file(&path) {
+            Ok((actual_sha, actual_size)) => {
+                if actual_size != *size as u64 || actual_sha != *sha256 {
+                    problems.push(format!("{}: contents do not match checksum", id));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                problems.push(format!("{}: file missing", id));
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", path.display()));
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("  {}", problem);
+        }
+        bail!(
+            "backup failed validation ({} problem(s)); data directory left untouched",
+            problems.len()
+        );
+    }
+
+    Ok(files.len())
+}
+
+async fn read_files(db_path: &Path) -> Result<Vec<(String, String, i64)>> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(db_path)
+        .read_only(true)
+        .connect()
+        .await
+        .context("failed to open database from backup")?;
+
+    let check = sqlx::query_scalar::<_, String>("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await
+        .context("failed to check database integrity")?;
+    if check != "ok" {
+        bail!("database in backup is corrupt: {}", check);
+    }
+
+    // Backups from before assets have one file per artifact row.
+    let has_assets = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'assets'",
+    )
+    .fetch_one(&mut conn)
+    .await?
+        > 0;
+    let table = if has_assets { "assets" } else { "artifacts" };
+    let rows = sqlx::query(&format!("SELECT id, sha256, size FROM {}", table))
+        .fetch_all(&mut conn)
+        .await?
+        .iter()
+        .map(|r| (r.get("id"), r.get("sha256"), r.get("size")))
+        .collect();
+    conn.close().await?;
+
+    Ok(rows)
+}
+
+fn hash_file(path: &Path) -> io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok((sha256, size))
+}
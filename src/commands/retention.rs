@@ -0,0 +1,84 @@
+use anyhow::Result;
+use cask_types::CreateRetentionRule;
+
+use crate::cli::{RetentionCommand, RetentionOpts};
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: RetentionOpts) -> Result<()> {
+    client::block_on(retention(opts.command))?
+}
+
+async fn retention(command: RetentionCommand) -> Result<()> {
+    match command {
+        RetentionCommand::Add {
+            client,
+            pattern,
+            keep_last,
+            max_age_days,
+            keep_downloaded_days,
+            keep_tagged,
+        } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let rule = client
+                .create_retention_rule(&CreateRetentionRule {
+                    pattern,
+                    keep_last,
+                    max_age_days,
+                    keep_downloaded_days,
+                    keep_tagged,
+                })
+                .await?;
+
+            if json {
+                print_json(&rule);
+            } else {
+                eprintln!("Added retention rule {} for '{}'", rule.id, rule.pattern);
+            }
+        }
+
+        RetentionCommand::Ls { client } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let rules = client.list_retention_rules().await?;
+
+            if json {
+                print_json(&rules);
+                return Ok(());
+            }
+
+            let opt = |v: Option<i64>| v.map_or_else(|| "-".to_string(), |v| v.to_string());
+            let rows: Vec<Vec<String>> = rules
+                .into_iter()
+                .map(|r| {
+                    vec![
+                        r.id,
+                        r.pattern,
+                        opt(r.keep_last),
+                        opt(r.max_age_days),
+                        opt(r.keep_downloaded_days),
+                        r.keep_tagged.unwrap_or_else(|| "-".to_string()),
+                    ]
+                })
+                .collect();
+            print_table(
+                &[
+                    "ID",
+                    "PATTERN",
+                    "KEEP LAST",
+                    "MAX AGE (D)",
+                    "KEEP DL (D)",
+                    "KEEP TAGGED",
+                ],
+                &rows,
+            );
+        }
+
+        RetentionCommand::Rm { client, id } => {
+            let client = client::connect(&client)?;
+            client.delete_retention_rule(&id).await?;
+        }
+    }
+
+    Ok(())
+}
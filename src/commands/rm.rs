@@ -0,0 +1,28 @@
+use anyhow::Result;
+
+use crate::cli::RmOpts;
+use crate::client;
+
+pub fn execute(opts: RmOpts) -> Result<()> {
+    client::block_on(rm(opts))?
+}
+
+async fn rm(opts: RmOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    match &opts.asset {
+        Some(filename) => {
+            client
+                .delete_asset(&opts.name, &opts.version, filename)
+                .await?
+        }
+        None => client.delete_artifact(&opts.name, &opts.version).await?,
+    }
+
+    if !opts.client.json {
+        match &opts.asset {
+            Some(filename) => eprintln!("Deleted {} of {}/{}", filename, opts.name, opts.version),
+            None => eprintln!("Deleted {}/{}", opts.name, opts.version),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::io::Read;
+
+use anyhow::{Context, Result};
+use cask_types::{CreateMetadataSchema, KeySpec};
+
+use crate::cli::{SchemasCommand, SchemasOpts};
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: SchemasOpts) -> Result<()> {
+    client::block_on(schemas(opts.command))?
+}
+
+async fn schemas(command: SchemasCommand) -> Result<()> {
+    match command {
+        SchemasCommand::Add {
+            client,
+            pattern,
+            schema,
+            keys,
+            required,
+            closed,
+        } => {
+            let schema = match schema {
+                Some(path) => {
+                    let text = if path.as_os_str() == "-" {
+                        let mut text = String::new();
+                        std::io::stdin()
+                            .read_to_string(&mut text)
+                            .context("failed to read schema from stdin")?;
+                        text
+                    } else {
+                        std::fs::read_to_string(&path)
+                            .with_context(|| format!("failed to read {}", path.display()))?
+                    };
+                    Some(serde_json::from_str(&text).context("schema is not valid JSON")?)
+                }
+                None => None,
+            };
+            let keys = keys
+                .into_iter()
+                .map(|key| (key, false))
+                .chain(required.into_iter().map(|key| (key, true)))
+                .map(|((key, kind), required)| (key, KeySpec { kind, required }))
+                .collect();
+
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let created = client
+                .create_schema(&CreateMetadataSchema {
+                    pattern,
+                    schema,
+                    keys,
+                    closed,
+                })
+                .await?;
+
+            if json {
+                print_json(&created);
+            } else {
+                eprintln!(
+                    "Added metadata schema {} for '{}'",
+                    created.id, created.pattern
+                );
+            }
+        }
+
+        SchemasCommand::Ls { client } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let schemas = client.list_schemas().await?;
+
+            if json {
+                print_json(&schemas);
+                return Ok(());
+            }
+
+            let rows: Vec<Vec<String>> = schemas
+                .into_iter()
+                .map(|s| vec![s.id, s.pattern, s.schema.to_string(), s.created_at])
+                .collect();
+            print_table(&["ID", "PATTERN", "SCHEMA", "CREATED"], &rows);
+        }
+
+        SchemasCommand::Rm { client, id } => {
+            let client = client::connect(&client)?;
+            client.delete_schema(&id).await?;
+        }
+    }
+
+    Ok(())
+}
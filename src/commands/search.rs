@@ -0,0 +1,58 @@
+use std::io::IsTerminal;
+
+use anyhow::Result;
+use cask_types::SearchQuery;
+
+use crate::cli::SearchOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: SearchOpts) -> Result<()> {
+    client::block_on(search(opts))?
+}
+
+async fn search(opts: SearchOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let hits = client
+        .search(&SearchQuery {
+            q: opts.words.join(" "),
+            limit: Some(opts.n),
+            offset: Some(opts.offset),
+        })
+        .await?;
+
+    if opts.client.json {
+        print_json(&hits);
+        return Ok(());
+    }
+
+    let bold = std::io::stdout().is_terminal();
+    let rows: Vec<Vec<String>> = hits
+        .into_iter()
+        .map(|h| {
+            vec![
+                h.artifact.name,
+                h.artifact.version,
+                plain_snippet(&h.snippet, bold),
+            ]
+        })
+        .collect();
+    print_table(&["NAME", "VERSION", "MATCH"], &rows);
+    Ok(())
+}
+
+/// Undo the server's HTML escaping, showing matches in bold on a terminal.
+fn plain_snippet(snippet: &str, bold: bool) -> String {
+    let (start, end) = if bold {
+        ("\x1b[1m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+    snippet
+        .replace("<mark>", start)
+        .replace("</mark>", end)
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
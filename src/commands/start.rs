@@ -4,6 +4,7 @@ use anyhow::{Context, Result, bail};
 use daemonize::Daemonize;
 
 use crate::cli::ServerOpts;
+use crate::logfile::LOG_FILE_NAME;
 use crate::server;
 
 pub fn execute(opts: ServerOpts) -> Result<()> {
@@ -28,7 +29,7 @@ pub fn execute(opts: ServerOpts) -> Result<()> {
         let _ = fs::remove_file(&pid_path);
     }
 
-    let log_path = data_dir.join("cask.log");
+    let log_path = data_dir.join(LOG_FILE_NAME);
     let log_file = fs::OpenOptions::new()
         .create(true)
         .append(true)
@@ -48,10 +49,7 @@ pub fn execute(opts: ServerOpts) -> Result<()> {
         .stderr(stderr)
         .working_directory(".");
 
-    eprintln!(
-        "Starting cask daemon on {}:{}...",
-        opts.host, opts.port
-    );
+    eprintln!("Starting cask daemon on {}:{}...", opts.host, opts.port);
 
     daemonize.start().context("failed to daemonize")?;
 
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use cask_types::StatsQuery;
+
+use crate::cli::StatsOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: StatsOpts) -> Result<()> {
+    client::block_on(stats(opts))?
+}
+
+async fn stats(opts: StatsOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+
+    let query = StatsQuery {
+        from: opts.from,
+        to: opts.to,
+        interval: opts.interval.map(Into::into),
+        by_version: opts.by_version,
+        by_asset: opts.by_asset,
+        exclude_bots: opts.exclude_bots,
+    };
+    let stats = client
+        .stats(&opts.name, opts.version.as_deref(), &query)
+        .await?;
+
+    if opts.client.json {
+        print_json(&stats);
+        return Ok(());
+    }
+
+    println!(
+        "{} downloads from {} unique IPs",
+        stats.downloads, stats.unique_ips
+    );
+    if let Some(series) = stats.series {
+        let rows: Vec<Vec<String>> = series
+            .into_iter()
+            .map(|b| vec![b.start, b.downloads.to_string(), b.unique_ips.to_string()])
+            .collect();
+        println!();
+        print_table(&["START", "DOWNLOADS", "UNIQUE IPS"], &rows);
+    }
+    if let Some(versions) = stats.versions {
+        let rows: Vec<Vec<String>> = versions
+            .into_iter()
+            .map(|v| vec![v.version, v.downloads.to_string(), v.unique_ips.to_string()])
+            .collect();
+        println!();
+        print_table(&["VERSION", "DOWNLOADS", "UNIQUE IPS"], &rows);
+    }
+    if let Some(assets) = stats.assets {
+        let rows: Vec<Vec<String>> = assets
+            .into_iter()
+            .map(|a| {
+                vec![
+                    a.filename,
+                    a.downloads.to_string(),
+                    a.unique_ips.to_string(),
+                ]
+            })
+            .collect();
+        println!();
+        print_table(&["ASSET", "DOWNLOADS", "UNIQUE IPS"], &rows);
+    }
+    Ok(())
+}
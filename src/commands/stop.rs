@@ -15,15 +15,15 @@ pub fn execute(opts: DataDirOpt) -> Result<()> {
     }
 
     let contents = fs::read_to_string(&pid_path).context("failed to read PID file")?;
-    let pid: i32 = contents
-        .trim()
-        .parse()
-        .context("invalid PID in PID file")?;
+    let pid: i32 = contents.trim().parse().context("invalid PID in PID file")?;
     let nix_pid = Pid::from_raw(pid);
 
     // Check if process is alive
     if signal::kill(nix_pid, None).is_err() {
-        eprintln!("Process {} is not running. Cleaning up stale PID file.", pid);
+        eprintln!(
+            "Process {} is not running. Cleaning up stale PID file.",
+            pid
+        );
         let _ = fs::remove_file(&pid_path);
         return Ok(());
     }
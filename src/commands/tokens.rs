@@ -0,0 +1,74 @@
+use anyhow::Result;
+use cask_types::CreateTokenRequest;
+
+use crate::cli::{TokensCommand, TokensOpts};
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: TokensOpts) -> Result<()> {
+    client::block_on(tokens(opts.command))?
+}
+
+async fn tokens(command: TokensCommand) -> Result<()> {
+    match command {
+        TokensCommand::Create {
+            client,
+            label,
+            admin,
+            expires_at,
+        } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let created = client
+                .create_token(&CreateTokenRequest {
+                    label,
+                    is_admin: admin,
+                    expires_at,
+                })
+                .await?;
+
+            if json {
+                print_json(&created);
+            } else {
+                eprintln!(
+                    "Created {}token '{}' ({}). It will not be shown again:",
+                    if created.is_admin { "admin " } else { "" },
+                    created.label,
+                    created.id
+                );
+                println!("{}", created.token);
+            }
+        }
+
+        TokensCommand::Ls { client } => {
+            let json = client.json;
+            let client = client::connect(&client)?;
+            let tokens = client.list_tokens().await?;
+
+            if json {
+                print_json(&tokens);
+                return Ok(());
+            }
+
+            let rows: Vec<Vec<String>> = tokens
+                .into_iter()
+                .map(|t| {
+                    vec![
+                        t.id,
+                        t.label,
+                        if t.is_admin { "yes" } else { "no" }.to_string(),
+                        t.expires_at.unwrap_or_else(|| "-".to_string()),
+                        t.created_at,
+                    ]
+                })
+                .collect();
+            print_table(&["ID", "LABEL", "ADMIN", "EXPIRES", "CREATED"], &rows);
+        }
+
+        TokensCommand::Revoke { client, id } => {
+            let client = client::connect(&client)?;
+            client.revoke_token(&id).await?;
+        }
+    }
+
+    Ok(())
+}
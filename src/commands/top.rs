@@ -0,0 +1,33 @@
+use anyhow::Result;
+use cask_types::TopQuery;
+
+use crate::cli::TopOpts;
+use crate::client::{self, print_json, print_table};
+
+pub fn execute(opts: TopOpts) -> Result<()> {
+    client::block_on(top(opts))?
+}
+
+async fn top(opts: TopOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let top = client
+        .top_artifacts(&TopQuery {
+            from: opts.from,
+            to: opts.to,
+            limit: Some(opts.n),
+            exclude_bots: opts.exclude_bots,
+        })
+        .await?;
+
+    if opts.client.json {
+        print_json(&top);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = top
+        .into_iter()
+        .map(|t| vec![t.name, t.downloads.to_string(), t.unique_ips.to_string()])
+        .collect();
+    print_table(&["NAME", "DOWNLOADS", "UNIQUE IPS"], &rows);
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+
+use crate::cli::UsageOpts;
+use crate::client::{self, print_json, print_table};
+use crate::commands::quotas::{limit, scope_label};
+
+pub fn execute(opts: UsageOpts) -> Result<()> {
+    client::block_on(usage(opts))?
+}
+
+async fn usage(opts: UsageOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let usage = client.usage().await?;
+
+    if opts.client.json {
+        print_json(&usage);
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = usage
+        .into_iter()
+        .map(|u| {
+            vec![
+                scope_label(u.quota.scope).to_string(),
+                u.quota.target,
+                format!("{} / {}", u.used_bytes, limit(u.quota.max_bytes)),
+                format!("{} / {}", u.used_artifacts, limit(u.quota.max_artifacts)),
+            ]
+        })
+        .collect();
+    print_table(&["SCOPE", "TARGET", "BYTES", "ARTIFACTS"], &rows);
+    Ok(())
+}
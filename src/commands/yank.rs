@@ -0,0 +1,26 @@
+use anyhow::Result;
+
+use crate::cli::YankOpts;
+use crate::client::{self, print_json};
+
+pub fn execute(opts: YankOpts) -> Result<()> {
+    client::block_on(yank(opts))?
+}
+
+async fn yank(opts: YankOpts) -> Result<()> {
+    let client = client::connect(&opts.client)?;
+    let artifact = if opts.undo {
+        client.unyank(&opts.name, &opts.version).await?
+    } else {
+        client.yank(&opts.name, &opts.version).await?
+    };
+
+    if opts.client.json {
+        print_json(&artifact);
+    } else if opts.undo {
+        eprintln!("Un-yanked {}/{}", opts.name, opts.version);
+    } else {
+        eprintln!("Yanked {}/{}", opts.name, opts.version);
+    }
+    Ok(())
+}
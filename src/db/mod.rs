@@ -1,11 +1,15 @@
 use std::path::Path;
 
-use anyhow::{Context, Result};
-use sqlx::SqlitePool;
+use anyhow::{Context, Result, bail};
 use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Sqlite, SqlitePool, Transaction};
+
+use crate::artifacts;
+
+pub const DB_FILE_NAME: &str = "cask.db";
 
 pub async fn create_pool(data_dir: &Path) -> Result<SqlitePool> {
-    let db_path = data_dir.join("cask.db");
+    let db_path = data_dir.join(DB_FILE_NAME);
     let url = format!("sqlite:{}?mode=rwc", db_path.display());
 
     let pool = SqlitePoolOptions::new()
@@ -29,5 +33,57 @@ pub async fn create_pool(data_dir: &Path) -> Result<SqlitePool> {
         .await
         .context("failed to run database migrations")?;
 
+    artifacts::fill_version_keys(&pool)
+        .await
+        .context("failed to compute version sort keys")?;
+
     Ok(pool)
 }
+
+/// Open an existing database without creating it or running migrations,
+/// read-only unless `writable`, for offline tools that must not leave an
+/// empty database in a mistyped `--data-dir`.
+pub async fn open_existing(data_dir: &Path, writable: bool) -> Result<SqlitePool> {
+    let db_path = data_dir.join(DB_FILE_NAME);
+    if !db_path.exists() {
+        bail!("no database found at {}", db_path.display());
+    }
+    let mode = if writable { "rw" } else { "ro" };
+    let url = format!("sqlite:{}?mode={}", db_path.display(), mode);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect(&url)
+        .await
+        .with_context(|| format!("failed to open database at {}", db_path.display()))?;
+
+    sqlx::query("PRAGMA foreign_keys=ON")
+        .execute(&pool)
+        .await
+        .context("failed to enable foreign keys")?;
+
+    let applied: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&pool)
+            .await
+            .with_context(|| format!("{} is not a cask database", db_path.display()))?;
+    let latest = sqlx::migrate!("./migrations")
+        .iter()
+        .map(|m| m.version)
+        .max();
+    if applied < latest {
+        bail!(
+            "database at {} is from an older version of cask; start the server once to migrate it",
+            db_path.display()
+        );
+    }
+
+    Ok(pool)
+}
+
+/// Start a transaction that takes the write lock up front. A deferred
+/// transaction that reads before it writes fails with "database is locked"
+/// instead of waiting if another writer commits in between.
+pub async fn begin_write(db: &SqlitePool) -> sqlx::Result<Transaction<'static, Sqlite>> {
+    db.begin_with("BEGIN IMMEDIATE").await
+}
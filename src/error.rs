@@ -1,9 +1,14 @@
-use axum::http::StatusCode;
+use axum::http::{HeaderValue, StatusCode, header};
 use axum::response::{IntoResponse, Response};
+use cask_types::{ErrorResponse, QUOTA_EXCEEDED, SchemaViolation};
 
 pub struct AppError {
     status: StatusCode,
     message: String,
+    /// Seconds for the `Retry-After` header.
+    retry_after: Option<u64>,
+    violations: Vec<SchemaViolation>,
+    code: Option<&'static str>,
 }
 
 impl AppError {
@@ -11,6 +16,9 @@ impl AppError {
         Self {
             status,
             message: message.into(),
+            retry_after: None,
+            violations: Vec::new(),
+            code: None,
         }
     }
 
@@ -30,10 +38,56 @@ impl AppError {
         Self::new(StatusCode::FORBIDDEN, msg)
     }
 
+    /// `410` for a file that is known to be damaged on disk. Retrying won't
+    /// help until it is repaired or uploaded again.
+    pub fn gone(msg: impl Into<String>) -> Self {
+        Self::new(StatusCode::GONE, msg)
+    }
+
     pub fn payload_too_large(msg: impl Into<String>) -> Self {
         Self::new(StatusCode::PAYLOAD_TOO_LARGE, msg)
     }
 
+    /// `507` for a server that is low on or out of disk space.
+    pub fn insufficient_storage(msg: impl Into<String>) -> Self {
+        Self::new(StatusCode::INSUFFICIENT_STORAGE, msg)
+    }
+
+    /// `413` with code `quota_exceeded`, which tells it apart from an
+    /// upload over the maximum size. Unlike the server running out of disk,
+    /// it isn't a server error.
+    pub fn quota_exceeded(msg: impl Into<String>) -> Self {
+        Self {
+            code: Some(QUOTA_EXCEEDED),
+            ..Self::payload_too_large(msg)
+        }
+    }
+
+    pub fn unavailable(msg: impl Into<String>, retry_after: u64) -> Self {
+        Self {
+            retry_after: Some(retry_after),
+            ..Self::new(StatusCode::SERVICE_UNAVAILABLE, msg)
+        }
+    }
+
+    /// `422` listing every way some metadata fails its schemas.
+    pub fn schema_violations(violations: Vec<SchemaViolation>) -> Self {
+        let summary: Vec<String> = violations
+            .iter()
+            .map(|v| match &v.key {
+                Some(key) => format!("{}: {}", key, v.message),
+                None => v.message.clone(),
+            })
+            .collect();
+        Self {
+            violations,
+            ..Self::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("metadata does not match its schema: {}", summary.join("; ")),
+            )
+        }
+    }
+
     pub fn internal(err: impl std::fmt::Display) -> Self {
         Self::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
     }
@@ -41,18 +95,36 @@ impl AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let body = serde_json::json!({ "error": self.message });
-        (self.status, axum::Json(body)).into_response()
+        let body = ErrorResponse {
+            error: self.message,
+            code: self.code.map(str::to_string),
+            violations: self.violations,
+        };
+        let mut response = (self.status, axum::Json(body)).into_response();
+        if let Some(secs) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
 
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
-        tracing::error!("{:#}", err);
-        Self::internal(err)
+        match err.downcast::<sqlx::Error>() {
+            Ok(err) => err.into(),
+            Err(err) => {
+                tracing::error!("{:#}", err);
+                Self::internal(err)
+            }
+        }
     }
 }
 
+/// Constraint violations are 500s like any other database error. Inserts
+/// that can expect one, e.g. a duplicate version, map it to `409`
+/// themselves.
 impl From<sqlx::Error> for AppError {
     fn from(err: sqlx::Error) -> Self {
         tracing::error!("database error: {:#}", err);
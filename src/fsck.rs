@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use cask_types::{FsckProblem, FsckProblemKind, FsckReport};
+use sqlx::SqlitePool;
+use tokio::fs;
+
+use crate::scrub;
+use crate::storage;
+
+/// Files younger than this are never treated as orphans: `upload` writes
+/// the file before inserting its row.
+const ORPHAN_GRACE: Duration = Duration::from_secs(15 * 60);
+
+#[derive(sqlx::FromRow)]
+struct StoredAsset {
+    id: String,
+    name: String,
+    version: String,
+    filename: String,
+    sha256: String,
+    size: i64,
+    broken_at: Option<String>,
+}
+
+/// Rehash every stored asset against its row and look for files in
+/// `artifacts/` that no row refers to.
+///
+/// With `repair`, broken assets are marked so downloads refuse them (and
+/// ones that verify again are unmarked), and orphans are moved to
+/// `quarantine/`.
+pub async fn run(db: &SqlitePool, data_dir: &Path, repair: bool) -> Result<FsckReport> {
+    let rows = sqlx::query_as::<_, StoredAsset>(
+        "SELECT s.id, a.name, a.version, s.filename, s.sha256, s.size, s.broken_at \
+         FROM assets s JOIN artifacts a ON a.id = s.artifact_id \
+         ORDER BY a.name, a.version, s.filename",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut problems = Vec::new();
+    for row in &rows {
+        match verify(data_dir, &row.id, &row.sha256, row.size, None).await? {
+            Some((kind, detail)) => {
+                // The asset may have been deleted since we listed it.
+                if kind == FsckProblemKind::Missing && !still_exists(db, &row.id).await? {
+                    continue;
+                }
+                if repair {
+                    mark_broken(db, &row.id, &detail).await?;
+                    scrub::record(db, &row.id, false, Some(kind), Some(&detail)).await?;
+                }
+                problems.push(FsckProblem {
+                    kind,
+                    id: row.id.clone(),
+                    name: Some(row.name.clone()),
+                    version: Some(row.version.clone()),
+                    filename: Some(row.filename.clone()),
+                    detail,
+                });
+            }
+            None if repair => {
+                if row.broken_at.is_some() {
+                    clear_broken(db, &row.id).await?;
+                }
+                scrub::record(db, &row.id, true, None, None).await?;
+            }
+            None => {}
+        }
+    }
+
+    // Pending blobs belong to uploads in progress, or are cleaned up at
+    // startup.
+    let pending = sqlx::query_scalar::<_, String>("SELECT asset_id FROM pending_blobs")
+        .fetch_all(db)
+        .await?;
+    let known: HashSet<&str> = rows
+        .iter()
+        .map(|r| r.id.as_str())
+        .chain(pending.iter().map(String::as_str))
+        .collect();
+    let artifacts_dir = data_dir.join("artifacts");
+    let mut entries = fs::read_dir(&artifacts_dir)
+        .await
+        .with_context(|| format!("failed to read {}", artifacts_dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if known.contains(file_name.as_str()) {
+            continue;
+        }
+        let meta = entry.metadata().await?;
+        if !meta.is_file() || is_recent(&meta) {
+            continue;
+        }
+
+        if repair {
+            quarantine(data_dir, &file_name).await?;
+        }
+        problems.push(FsckProblem {
+            kind: FsckProblemKind::Orphan,
+            id: file_name,
+            name: None,
+            version: None,
+            filename: None,
+            detail: format!("{} bytes with no matching asset", meta.len()),
+        });
+    }
+
+    Ok(FsckReport {
+        checked: rows.len(),
+        problems,
+        repaired: repair,
+    })
+}
+
+/// Check one asset's file against its recorded checksum and size.
+/// Returns the problem found, if any.
+/// `bytes_per_sec` throttles the read, as used by the background scrubber.
+pub async fn verify(
+    data_dir: &Path,
+    asset_id: &str,
+    sha256: &str,
+    size: i64,
+    bytes_per_sec: Option<u64>,
+) -> Result<Option<(FsckProblemKind, String)>> {
+    let Some((actual_sha, actual_size)) =
+        storage::checksum(data_dir, asset_id, bytes_per_sec).await?
+    else {
+        return Ok(Some((
+            FsckProblemKind::Missing,
+            "file is missing".to_string(),
+        )));
+    };
+
+    if actual_size != size as u64 {
+        return Ok(Some((
+            FsckProblemKind::SizeMismatch,
+            format!("expected {} bytes, found {}", size, actual_size),
+        )));
+    }
+    if actual_sha != sha256 {
+        return Ok(Some((
+            FsckProblemKind::ChecksumMismatch,
+            format!("expected sha256 {}, found {}", sha256, actual_sha),
+        )));
+    }
+    Ok(None)
+}
+
+pub async fn mark_broken(db: &SqlitePool, asset_id: &str, reason: &str) -> Result<()> {
+    sqlx::query(
+        "UPDATE assets SET broken_at = datetime('now'), broken_reason = ? \
+         WHERE id = ? AND broken_at IS NULL",
+    )
+    .bind(reason)
+    .bind(asset_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn clear_broken(db: &SqlitePool, asset_id: &str) -> Result<()> {
+    sqlx::query("UPDATE assets SET broken_at = NULL, broken_reason = NULL WHERE id = ?")
+        .bind(asset_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn still_exists(db: &SqlitePool, asset_id: &str) -> Result<bool> {
+    let row = sqlx::query("SELECT 1 FROM assets WHERE id = ?")
+        .bind(asset_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(row.is_some())
+}
+
+fn is_recent(meta: &std::fs::Metadata) -> bool {
+    meta.modified()
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age < ORPHAN_GRACE)
+}
+
+/// Move an orphaned file from `artifacts/` to `quarantine/`.
+async fn quarantine(data_dir: &Path, file_name: &str) -> Result<()> {
+    let dir = data_dir.join("quarantine");
+    fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("failed to create {}", dir.display()))?;
+    let from = data_dir.join("artifacts").join(file_name);
+    fs::rename(&from, dir.join(file_name))
+        .await
+        .with_context(|| format!("failed to quarantine {}", from.display()))?;
+    Ok(())
+}
@@ -0,0 +1,23 @@
+pub mod artifacts;
+pub mod audit;
+pub mod auth;
+pub mod cli;
+mod client;
+pub mod commands;
+pub mod db;
+pub mod error;
+pub mod fsck;
+pub mod logfile;
+pub mod maintenance;
+pub mod metrics;
+pub mod privacy;
+pub mod quota;
+pub mod recorder;
+pub mod retention;
+pub mod rollup;
+pub mod schema;
+pub mod scrub;
+pub mod server;
+pub mod state;
+pub mod storage;
+pub mod useragent;
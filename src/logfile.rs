@@ -0,0 +1,362 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::cli::LogRotation;
+
+pub const LOG_FILE_NAME: &str = "cask.log";
+
+/// When and how the daemon log is rotated.
+#[derive(Clone, Copy)]
+pub struct RotationPolicy {
+    /// Rotate once the file reaches this many bytes (0 disables).
+    pub max_size: u64,
+    pub interval: LogRotation,
+    /// Number of rotated files to keep.
+    pub keep: usize,
+    pub compress: bool,
+}
+
+/// Path of the `index`-th rotated file, e.g. `cask.log.1` or `cask.log.1.gz`.
+pub fn rotated_path(log_path: &Path, index: usize, compressed: bool) -> PathBuf {
+    let mut name = log_path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    if compressed {
+        name.push(".gz");
+    }
+    PathBuf::from(name)
+}
+
+/// Append-only log file that rotates itself according to a `RotationPolicy`.
+///
+/// After each rotation stdout and stderr are pointed at the fresh file, so
+/// panics and anything else written outside of tracing follow the log too.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    period: Option<u64>,
+    policy: RotationPolicy,
+    /// Compression of the last rotated file, which runs on its own thread
+    /// so logging doesn't wait for it.
+    compressing: Option<JoinHandle<()>>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, policy: RotationPolicy) -> Result<Self> {
+        remove_partial(path).context("failed to remove partly compressed logs")?;
+        let file = open_append(path)
+            .with_context(|| format!("failed to open log file: {}", path.display()))?;
+        let meta = file.metadata().context("failed to stat log file")?;
+
+        // Seed the period from the last write so a daemon restarted the next
+        // day still rotates yesterday's file before logging to it.
+        let period = meta
+            .modified()
+            .ok()
+            .and_then(|t| period_of(policy.interval, t));
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written: meta.len(),
+            period,
+            policy,
+            compressing: None,
+        })
+    }
+
+    fn should_rotate(&self, incoming: usize) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+        if self.policy.max_size > 0 && self.written + incoming as u64 > self.policy.max_size {
+            return true;
+        }
+        match (
+            self.period,
+            period_of(self.policy.interval, SystemTime::now()),
+        ) {
+            (Some(current), Some(now)) => now != current,
+            _ => false,
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let keep = self.policy.keep;
+
+        // The previous file must be compressed before it's shifted.
+        if let Some(compressing) = self.compressing.take() {
+            let _ = compressing.join();
+        }
+
+        if let Some(first) = shift(&self.path, keep)?
+            && self.policy.compress
+        {
+            self.compressing = Some(thread::spawn(move || {
+                if let Err(e) = compress(&first) {
+                    eprintln!("log compression failed: {}", e);
+                }
+            }));
+        }
+
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        self.period = period_of(self.policy.interval, SystemTime::now());
+
+        let fd = self.file.as_raw_fd();
+        nix::unistd::dup2(fd, io::stdout().as_raw_fd())?;
+        nix::unistd::dup2(fd, io::stderr().as_raw_fd())?;
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // On failure keep logging to whatever file we still hold rather
+        // than losing the event.
+        if self.should_rotate(buf.len())
+            && let Err(e) = self.rotate()
+        {
+            let _ = writeln!(self.file, "log rotation failed: {}", e);
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Move `path` to `path.1`, shifting older rotated files up by one and
+/// dropping those past `keep`. Returns the new `path.1`, or `None` if
+/// `keep` is 0 and `path` was removed instead.
+fn shift(path: &Path, keep: usize) -> io::Result<Option<PathBuf>> {
+    // Drop the oldest file(s), then shift the rest up by one.
+    for compressed in [false, true] {
+        let oldest = rotated_path(path, keep.max(1), compressed);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+    }
+    for i in (1..keep).rev() {
+        for compressed in [false, true] {
+            let from = rotated_path(path, i, compressed);
+            if from.exists() {
+                fs::rename(&from, rotated_path(path, i + 1, compressed))?;
+            }
+        }
+    }
+
+    if keep == 0 {
+        fs::remove_file(path)?;
+        return Ok(None);
+    }
+    let first = rotated_path(path, 1, false);
+    fs::rename(path, &first)?;
+    Ok(Some(first))
+}
+
+/// Remove `.gz.part` files next to `path` left by a compression that was
+/// interrupted. The uncompressed file they came from is still there.
+fn remove_partial(path: &Path) -> io::Result<()> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(());
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let entry_name = entry.file_name();
+        let entry_name = entry_name.to_string_lossy();
+        if entry_name.starts_with(&prefix) && entry_name.ends_with(".gz.part") {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Index of the hour/day containing `time`, or `None` if time-based
+/// rotation is disabled.
+fn period_of(interval: LogRotation, time: SystemTime) -> Option<u64> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    match interval {
+        LogRotation::Never => None,
+        LogRotation::Hourly => Some(secs / 3600),
+        LogRotation::Daily => Some(secs / 86400),
+    }
+}
+
+/// Gzip `path` into `path.gz` and remove the original. The output only
+/// appears under its final name once complete, so readers never see a
+/// truncated archive.
+fn compress(path: &Path) -> io::Result<()> {
+    let mut gz_name = path.as_os_str().to_owned();
+    gz_name.push(".gz");
+    let gz_path = PathBuf::from(gz_name);
+    let mut partial = gz_path.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let mut input = File::open(path)?;
+    let output = File::create(&partial)?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::rename(&partial, &gz_path)?;
+
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotated_paths_number_from_one() {
+        let log = Path::new("/data/cask.log");
+        assert_eq!(rotated_path(log, 1, false), Path::new("/data/cask.log.1"));
+        assert_eq!(
+            rotated_path(log, 12, true),
+            Path::new("/data/cask.log.12.gz")
+        );
+    }
+
+    #[test]
+    fn shift_keeps_the_newest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join(LOG_FILE_NAME);
+        for generation in 1..=4 {
+            fs::write(&log, format!("generation {}", generation)).unwrap();
+            assert_eq!(shift(&log, 2).unwrap(), Some(rotated_path(&log, 1, false)));
+        }
+        assert_eq!(names(dir.path()), ["cask.log.1", "cask.log.2"]);
+        assert_eq!(
+            fs::read_to_string(rotated_path(&log, 1, false)).unwrap(),
+            "generation 4"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated_path(&log, 2, false)).unwrap(),
+            "generation 3"
+        );
+    }
+
+    #[test]
+    fn shift_moves_compressed_files_too() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join(LOG_FILE_NAME);
+        fs::write(rotated_path(&log, 1, true), "one").unwrap();
+        fs::write(rotated_path(&log, 2, true), "two").unwrap();
+        fs::write(&log, "current").unwrap();
+
+        shift(&log, 2).unwrap();
+        assert_eq!(names(dir.path()), ["cask.log.1", "cask.log.2.gz"]);
+        assert_eq!(
+            fs::read_to_string(rotated_path(&log, 2, true)).unwrap(),
+            "one"
+        );
+    }
+
+    #[test]
+    fn shift_with_keep_zero_removes_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join(LOG_FILE_NAME);
+        fs::write(&log, "current").unwrap();
+        assert_eq!(shift(&log, 0).unwrap(), None);
+        assert!(names(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn compress_replaces_the_file_with_a_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = rotated_path(&dir.path().join(LOG_FILE_NAME), 1, false);
+        fs::write(&first, "line one\nline two\n").unwrap();
+
+        compress(&first).unwrap();
+        assert_eq!(names(dir.path()), ["cask.log.1.gz"]);
+        let mut text = String::new();
+        GzDecoder::new(File::open(dir.path().join("cask.log.1.gz")).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "line one\nline two\n");
+    }
+
+    #[test]
+    fn open_removes_partial_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join(LOG_FILE_NAME);
+        fs::write(rotated_path(&log, 1, false), "kept").unwrap();
+        fs::write(dir.path().join("cask.log.1.gz.part"), "partial").unwrap();
+        fs::write(dir.path().join("other.log.1.gz.part"), "not ours").unwrap();
+
+        let policy = RotationPolicy {
+            max_size: 0,
+            interval: LogRotation::Never,
+            keep: 5,
+            compress: true,
+        };
+        RotatingFile::open(&log, policy).unwrap();
+        assert_eq!(
+            names(dir.path()),
+            ["cask.log", "cask.log.1", "other.log.1.gz.part"]
+        );
+    }
+
+    #[test]
+    fn rotates_past_the_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join(LOG_FILE_NAME);
+        let policy = RotationPolicy {
+            max_size: 10,
+            interval: LogRotation::Never,
+            keep: 1,
+            compress: false,
+        };
+        let mut file = RotatingFile::open(&log, policy).unwrap();
+        assert!(!file.should_rotate(100), "an empty file is never rotated");
+        file.written = 6;
+        assert!(!file.should_rotate(4));
+        assert!(file.should_rotate(5));
+    }
+
+    #[test]
+    fn periods_follow_the_interval() {
+        let at = |secs| UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        assert_eq!(period_of(LogRotation::Never, at(0)), None);
+        assert_eq!(period_of(LogRotation::Hourly, at(3599)), Some(0));
+        assert_eq!(period_of(LogRotation::Hourly, at(3600)), Some(1));
+        assert_eq!(period_of(LogRotation::Daily, at(86399)), Some(0));
+        assert_eq!(period_of(LogRotation::Daily, at(86400)), Some(1));
+    }
+}
@@ -1,15 +1,7 @@
-mod auth;
-mod cli;
-mod commands;
-mod db;
-mod error;
-mod server;
-mod state;
-mod storage;
-
 use anyhow::Result;
+use cask::cli::{Cli, Command};
+use cask::commands;
 use clap::Parser;
-use cli::{Cli, Command};
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -20,5 +12,29 @@ fn main() -> Result<()> {
         Command::Stop(opts) => commands::stop::execute(opts),
         Command::Pid(opts) => commands::pid::execute(opts),
         Command::Log(opts) => commands::log::execute(opts),
+        Command::Backup(opts) => commands::backup::execute(opts),
+        Command::Restore(opts) => commands::restore::execute(opts),
+        Command::Fsck(opts) => commands::fsck::execute(opts),
+        Command::Push(opts) => commands::push::execute(opts),
+        Command::Pull(opts) => commands::pull::execute(opts),
+        Command::Ls(opts) => commands::ls::execute(opts),
+        Command::Rm(opts) => commands::rm::execute(opts),
+        Command::Assets(opts) => commands::assets::execute(opts),
+        Command::Yank(opts) => commands::yank::execute(opts),
+        Command::Meta(opts) => commands::meta::execute(opts),
+        Command::Package(opts) => commands::package::execute(opts),
+        Command::Readme(opts) => commands::readme::execute(opts),
+        Command::Stats(opts) => commands::stats::execute(opts),
+        Command::Top(opts) => commands::top::execute(opts),
+        Command::Clients(opts) => commands::clients::execute(opts),
+        Command::Search(opts) => commands::search::execute(opts),
+        Command::Tokens(opts) => commands::tokens::execute(opts),
+        Command::Retention(opts) => commands::retention::execute(opts),
+        Command::Schemas(opts) => commands::schemas::execute(opts),
+        Command::Gc(opts) => commands::gc::execute(opts),
+        Command::Audit(opts) => commands::audit::execute(opts),
+        Command::Quotas(opts) => commands::quotas::execute(opts),
+        Command::Usage(opts) => commands::usage::execute(opts),
+        Command::ReadOnly(opts) => commands::read_only::execute(opts),
     }
 }
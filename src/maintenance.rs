@@ -0,0 +1,75 @@
+//! Read-only (maintenance) mode, kept in a file in the data directory so a
+//! restart during maintenance doesn't quietly re-enable writes.
+//!
+//! The mode lives outside the database on purpose: maintenance often means
+//! the database itself is being backed up or replaced.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use cask_types::ReadOnlyMode;
+
+pub const READ_ONLY_FILE_NAME: &str = "read-only.json";
+
+/// The mode saved by [`save`], or the default (writable) if there is none.
+pub fn load(data_dir: &Path) -> Result<ReadOnlyMode> {
+    let path = data_dir.join(READ_ONLY_FILE_NAME);
+    match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("invalid read-only mode in {}", path.display())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ReadOnlyMode::default()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Save `mode` for the next startup. Turning the mode off removes the file.
+pub fn save(data_dir: &Path, mode: &ReadOnlyMode) -> Result<()> {
+    let path = data_dir.join(READ_ONLY_FILE_NAME);
+    if !mode.enabled {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        };
+    }
+
+    // Write next to it and rename, so a crash can't leave half a file that
+    // stops the server from starting.
+    let partial = path.with_extension("json.part");
+    fs::write(&partial, serde_json::to_vec(mode)?)
+        .with_context(|| format!("failed to write {}", partial.display()))?;
+    fs::rename(&partial, &path)
+        .with_context(|| format!("failed to rename {} into place", partial.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!load(dir.path()).unwrap().enabled);
+
+        let mode = ReadOnlyMode {
+            enabled: true,
+            reason: Some("restoring a backup".to_string()),
+            retry_after: 120,
+        };
+        save(dir.path(), &mode).unwrap();
+        let loaded = load(dir.path()).unwrap();
+        assert!(loaded.enabled);
+        assert_eq!(loaded.reason.as_deref(), Some("restoring a backup"));
+        assert_eq!(loaded.retry_after, 120);
+
+        save(dir.path(), &ReadOnlyMode::default()).unwrap();
+        assert!(!dir.path().join(READ_ONLY_FILE_NAME).exists());
+        assert!(!load(dir.path()).unwrap().enabled);
+        // Turning it off again is fine.
+        save(dir.path(), &ReadOnlyMode::default()).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+//! Process-wide counters, exposed in Prometheus text format at `/metrics`.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Default)]
+pub struct Metrics {
+    /// Artifacts verified by the background scrubber.
+    pub scrub_checked: AtomicU64,
+    /// Bytes of artifacts that passed a scrub check.
+    pub scrub_bytes: AtomicU64,
+    /// Scrub checks that found a missing or corrupt file.
+    pub scrub_failures: AtomicU64,
+    /// Download stat events written to the database.
+    pub downloads_recorded: AtomicU64,
+    /// Download stat events lost because the queue was full or the write
+    /// failed.
+    pub downloads_dropped: AtomicU64,
+}
+
+impl Metrics {
+    pub fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Render the counters, followed by `gauges` (name, help, value) computed
+    /// by the caller at scrape time.
+    pub fn render(&self, gauges: &[(&str, &str, i64)]) -> String {
+        let mut out = String::new();
+        let counters = [
+            (
+                "cask_scrub_checked_total",
+                "Artifacts verified by the background scrubber.",
+                &self.scrub_checked,
+            ),
+            (
+                "cask_scrub_bytes_total",
+                "Bytes of artifacts that passed a scrub check.",
+                &self.scrub_bytes,
+            ),
+            (
+                "cask_scrub_failures_total",
+                "Scrub checks that found a missing or corrupt file.",
+                &self.scrub_failures,
+            ),
+            (
+                "cask_download_events_recorded_total",
+                "Download stat events written to the database.",
+                &self.downloads_recorded,
+            ),
+            (
+                "cask_download_events_dropped_total",
+                "Download stat events lost because the queue was full or the write failed.",
+                &self.downloads_dropped,
+            ),
+        ];
+        for (name, help, value) in counters {
+            write_metric(
+                &mut out,
+                name,
+                "counter",
+                help,
+                value.load(Ordering::Relaxed),
+            );
+        }
+        for (name, help, value) in gauges {
+            write_metric(&mut out, name, "gauge", help, value);
+        }
+        out
+    }
+}
+
+fn write_metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: impl std::fmt::Display,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
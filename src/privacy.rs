@@ -0,0 +1,288 @@
+//! How client IPs are determined and what of them is stored.
+
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use axum::http::HeaderMap;
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::cli::IpMode;
+use crate::state::AppState;
+
+/// How often IPs past the retention window are cleared.
+const SCRUB_IPS_EVERY: Duration = Duration::from_secs(60 * 60);
+
+pub struct IpPolicy {
+    mode: IpMode,
+    trusted_proxies: Vec<IpNet>,
+    /// The UTC day (`YYYY-MM-DD`) and its salt, cached from `ip_salts`.
+    salt: Mutex<Option<(String, String)>>,
+}
+
+impl Default for IpPolicy {
+    fn default() -> Self {
+        Self::new(IpMode::Full, Vec::new())
+    }
+}
+
+impl IpPolicy {
+    pub fn new(mode: IpMode, trusted_proxies: Vec<IpNet>) -> Self {
+        Self {
+            mode,
+            trusted_proxies,
+            salt: Mutex::new(None),
+        }
+    }
+
+    /// The client's address: `peer`, unless `peer` is a trusted proxy, in
+    /// which case the rightmost untrusted `X-Forwarded-For` entry (or the
+    /// leftmost entry if all are trusted).
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|entry| entry.trim().parse().ok())
+            .collect();
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.is_trusted(**ip))
+            .or(forwarded.first())
+            .copied()
+            .unwrap_or(peer)
+    }
+
+    /// What to store for `ip` under the configured mode. Nothing is stored
+    /// if the hashing salt can't be loaded.
+    pub async fn stored(&self, db: &SqlitePool, ip: IpAddr) -> Option<String> {
+        match self.mode {
+            IpMode::Full => Some(ip.to_string()),
+            IpMode::Truncate => Some(truncate(ip).to_string()),
+            IpMode::Hash => match self.salt(db).await {
+                Ok(salt) => Some(hash(&salt, ip)),
+                Err(e) => {
+                    tracing::error!("failed to load the IP hashing salt: {:#}", e);
+                    None
+                }
+            },
+            IpMode::None => None,
+        }
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        let ip = canonical(ip);
+        self.trusted_proxies.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Today's salt. Each UTC day gets a new one, stored so restarts keep
+    /// it; earlier days' salts are deleted, so hashes of the same IP can only
+    /// be correlated within one day.
+    async fn salt(&self, db: &SqlitePool) -> Result<String> {
+        let today =
+            humantime::format_rfc3339_seconds(SystemTime::now()).to_string()[..10].to_string();
+        if let Some((day, salt)) = self.salt.lock().unwrap().as_ref()
+            && *day == today
+        {
+            return Ok(salt.clone());
+        }
+
+        sqlx::query("INSERT INTO ip_salts (day, salt) VALUES (?, ?) ON CONFLICT (day) DO NOTHING")
+            .bind(&today)
+            .bind(Uuid::new_v4().to_string())
+            .execute(db)
+            .await?;
+        let salt: String = sqlx::query_scalar("SELECT salt FROM ip_salts WHERE day = ?")
+            .bind(&today)
+            .fetch_one(db)
+            .await?;
+        sqlx::query("DELETE FROM ip_salts WHERE day < ?")
+            .bind(&today)
+            .execute(db)
+            .await?;
+
+        *self.salt.lock().unwrap() = Some((today, salt.clone()));
+        Ok(salt)
+    }
+}
+
+fn hash(salt: &str, ip: IpAddr) -> String {
+    let digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update(canonical(ip).to_string())
+        .finalize();
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) as plain IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+/// Zero all but the first 24 (IPv4) or 48 (IPv6) bits.
+fn truncate(ip: IpAddr) -> IpAddr {
+    match canonical(ip) {
+        IpAddr::V4(v4) => IpAddr::V4(Ipv4Net::new(v4, 24).unwrap().network()),
+        IpAddr::V6(v6) => IpAddr::V6(Ipv6Net::new(v6, 48).unwrap().network()),
+    }
+}
+
+/// Clear recorded IPs older than `retention`. Returns the number of rows
+/// changed.
+pub async fn forget_ips(db: &SqlitePool, retention: Duration) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE download_stats SET ip = NULL \
+         WHERE ip IS NOT NULL AND downloaded_at < datetime('now', ?)",
+    )
+    .bind(format!("-{} seconds", retention.as_secs()))
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Clear old IPs every hour, skipping runs while the server is read-only.
+pub async fn run(state: AppState, retention: Duration) {
+    let mut ticker = tokio::time::interval(SCRUB_IPS_EVERY);
+    loop {
+        ticker.tick().await;
+        if state.read_only.read().unwrap().enabled {
+            continue;
+        }
+        match forget_ips(&state.db, retention).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("cleared IPs from {} download stat row(s)", n),
+            Err(e) => tracing::error!("failed to clear old IPs: {:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+    use crate::db;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn truncate_keeps_the_network() {
+        for (input, expected) in [
+            ("203.0.113.77", "203.0.113.0"),
+            ("203.0.113.0", "203.0.113.0"),
+            ("::ffff:203.0.113.77", "203.0.113.0"),
+            ("2001:db8:abcd:12:1:2:3:4", "2001:db8:abcd::"),
+            ("::1", "::"),
+        ] {
+            assert_eq!(truncate(ip(input)), ip(expected), "{}", input);
+        }
+    }
+
+    fn forwarded(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn policy(trusted: &[&str]) -> IpPolicy {
+        let trusted = trusted.iter().map(|net| net.parse().unwrap()).collect();
+        IpPolicy::new(IpMode::Full, trusted)
+    }
+
+    #[test]
+    fn untrusted_peers_cannot_forward() {
+        let headers = forwarded(&["198.51.100.1"]);
+        assert_eq!(
+            policy(&["10.0.0.0/8"]).client_ip(ip("192.0.2.9"), &headers),
+            ip("192.0.2.9")
+        );
+        // Nor can anyone without trusted proxies configured.
+        assert_eq!(
+            policy(&[]).client_ip(ip("10.0.0.1"), &headers),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn trusted_proxies_forward_the_rightmost_untrusted_entry() {
+        let policy = policy(&["10.0.0.0/8", "127.0.0.1/32"]);
+        let peer = ip("127.0.0.1");
+        for (values, expected) in [
+            // A client-supplied entry to the left is ignored.
+            (&["203.0.113.5, 198.51.100.1"][..], "198.51.100.1"),
+            (&["198.51.100.1, 10.1.2.3"], "198.51.100.1"),
+            // Entries across several headers count as one list.
+            (&["198.51.100.1", "10.1.2.3"], "198.51.100.1"),
+            // All trusted: the leftmost one.
+            (&["10.1.2.3, 10.4.5.6"], "10.1.2.3"),
+            // Unparseable entries are skipped.
+            (&["garbage, 198.51.100.1"], "198.51.100.1"),
+            (&["garbage"], "127.0.0.1"),
+            (&[], "127.0.0.1"),
+        ] {
+            assert_eq!(
+                policy.client_ip(peer, &forwarded(values)),
+                ip(expected),
+                "{:?}",
+                values
+            );
+        }
+        // An IPv4-mapped peer is matched against IPv4 networks.
+        assert_eq!(
+            policy.client_ip(ip("::ffff:10.0.0.1"), &forwarded(&["198.51.100.1"])),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[tokio::test]
+    async fn salt_rotates_daily_and_survives_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::create_pool(dir.path()).await.unwrap();
+        sqlx::query("INSERT INTO ip_salts (day, salt) VALUES ('2000-01-01', 'old')")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let policy = IpPolicy::new(IpMode::Hash, Vec::new());
+        let salt = policy.salt(&db).await.unwrap();
+        assert_ne!(salt, "old");
+        let days: Vec<String> = sqlx::query_scalar("SELECT day FROM ip_salts")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(days.len(), 1);
+        assert_ne!(days[0], "2000-01-01");
+
+        // A restarted server picks up the same salt.
+        let restarted = IpPolicy::new(IpMode::Hash, Vec::new());
+        assert_eq!(restarted.salt(&db).await.unwrap(), salt);
+        assert_eq!(
+            restarted.stored(&db, ip("198.51.100.1")).await,
+            policy.stored(&db, ip("198.51.100.1")).await
+        );
+
+        // A cached salt from an earlier day is replaced.
+        *policy.salt.lock().unwrap() = Some(("2000-01-01".to_string(), "old".to_string()));
+        assert_eq!(policy.salt(&db).await.unwrap(), salt);
+        assert_ne!(
+            hash("old", ip("198.51.100.1")),
+            hash(&salt, ip("198.51.100.1"))
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Byte and artifact-count quotas per name prefix and per token.
+
+use anyhow::Result;
+use cask_types::{Quota, QuotaScope, QuotaUsage};
+use sqlx::{SqliteConnection, SqliteExecutor, SqlitePool};
+
+const QUOTA_COLUMNS: &str = "id, scope, target, max_bytes, max_artifacts, created_at";
+
+/// Quotas covering an artifact called `name` uploaded with `token_id`.
+pub async fn applicable(
+    db: impl SqliteExecutor<'_>,
+    name: &str,
+    token_id: &str,
+) -> Result<Vec<Quota>> {
+    let quotas = sqlx::query_as::<_, Quota>(&format!(
+        "SELECT {} FROM quotas \
+         WHERE (scope = 'prefix' AND substr(?1, 1, length(target)) = target) \
+            OR (scope = 'token' AND target = ?2) \
+         ORDER BY scope, target",
+        QUOTA_COLUMNS
+    ))
+    .bind(name)
+    .bind(token_id)
+    .fetch_all(db)
+    .await?;
+    Ok(quotas)
+}
+
+/// Every quota, or only the prefix quotas and `token_id`'s own quota.
+pub async fn list(db: &SqlitePool, token_id: Option<&str>) -> Result<Vec<Quota>> {
+    let quotas = sqlx::query_as::<_, Quota>(&format!(
+        "SELECT {} FROM quotas \
+         WHERE ?1 IS NULL OR scope = 'prefix' OR target = ?1 \
+         ORDER BY scope, target",
+        QUOTA_COLUMNS
+    ))
+    .bind(token_id)
+    .fetch_all(db)
+    .await?;
+    Ok(quotas)
+}
+
+/// Bytes count every asset; a token's are the assets it uploaded, which
+/// can belong to versions other tokens created.
+pub async fn usage(db: impl SqliteExecutor<'_>, quota: Quota) -> Result<QuotaUsage> {
+    let (bytes_filter, artifacts_filter) = match quota.scope {
+        QuotaScope::Prefix => (
+            "substr(a.name, 1, length(?1)) = ?1",
+            "substr(name, 1, length(?1)) = ?1",
+        ),
+        QuotaScope::Token => ("s.uploaded_by = ?1", "uploaded_by = ?1"),
+    };
+    let (used_bytes, used_artifacts) = sqlx::query_as::<_, (i64, i64)>(&format!(
+        "SELECT (SELECT COALESCE(SUM(s.size), 0) FROM assets s \
+         JOIN artifacts a ON a.id = s.artifact_id WHERE {}), \
+         (SELECT COUNT(*) FROM artifacts WHERE {})",
+        bytes_filter, artifacts_filter
+    ))
+    .bind(&quota.target)
+    .fetch_one(db)
+    .await?;
+
+    Ok(QuotaUsage {
+        quota,
+        used_bytes,
+        used_artifacts,
+    })
+}
+
+/// Whether uploading `size` bytes, as a new version if `new_version` or
+/// else as an asset of an existing one, would exceed any quota for `name`
+/// and `token_id`. Returns a message describing the first quota exceeded.
+///
+/// Run it in the transaction that inserts the upload, so that concurrent
+/// uploads can't each see room for themselves and together go over.
+pub async fn check(
+    conn: &mut SqliteConnection,
+    name: &str,
+    token_id: &str,
+    size: u64,
+    new_version: bool,
+) -> Result<Option<String>> {
+    for quota in applicable(&mut *conn, name, token_id).await? {
+        let usage = usage(&mut *conn, quota).await?;
+        let quota = &usage.quota;
+        let scope = match quota.scope {
+            QuotaScope::Prefix => "prefix",
+            QuotaScope::Token => "token",
+        };
+
+        if let Some(max) = quota.max_bytes
+            && usage.used_bytes as u64 + size > max as u64
+        {
+            return Ok(Some(format!(
+                "quota exceeded for {} '{}': {} of {} bytes used, upload is {} bytes",
+                scope, quota.target, usage.used_bytes, max, size
+            )));
+        }
+        if let Some(max) = quota.max_artifacts
+            && new_version
+            && usage.used_artifacts >= max
+        {
+            return Ok(Some(format!(
+                "quota exceeded for {} '{}': {} of {} artifacts used",
+                scope, quota.target, usage.used_artifacts, max
+            )));
+        }
+    }
+    Ok(None)
+}
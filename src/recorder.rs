@@ -0,0 +1,143 @@
+//! Download stat recording off the request path.
+//!
+//! `download` hands events to a bounded channel and a background task writes
+//! them in batches, one transaction per batch, so SQLite write contention
+//! never delays serving bytes. When the channel is full, events are dropped
+//! and counted in `/metrics` rather than making downloads wait.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::db;
+use crate::metrics::Metrics;
+use crate::useragent::ClientInfo;
+
+/// Most events written in one transaction.
+const MAX_BATCH: usize = 512;
+
+struct DownloadEvent {
+    artifact_id: String,
+    asset_id: String,
+    ip: Option<String>,
+    client: ClientInfo,
+    /// `YYYY-MM-DD HH:MM:SS` UTC, as `datetime('now')` would give.
+    at: String,
+}
+
+enum Message {
+    Download(DownloadEvent),
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Clone)]
+pub struct DownloadRecorder {
+    tx: mpsc::Sender<Message>,
+    metrics: Arc<Metrics>,
+}
+
+impl DownloadRecorder {
+    /// Start the writer task with room for `capacity` pending events.
+    pub fn spawn(db: SqlitePool, metrics: Arc<Metrics>, capacity: usize) -> Self {
+        let (tx, rx) = mpsc::channel(capacity);
+        tokio::spawn(write_loop(db, metrics.clone(), rx));
+        Self { tx, metrics }
+    }
+
+    /// Queue a download of `asset_id` of `artifact_id`, dropping it if the
+    /// queue is full.
+    pub fn record(
+        &self,
+        artifact_id: &str,
+        asset_id: &str,
+        ip: Option<String>,
+        client: ClientInfo,
+    ) {
+        let at = humantime::format_rfc3339_seconds(SystemTime::now())
+            .to_string()
+            .replace('T', " ")
+            .trim_end_matches('Z')
+            .to_string();
+        let event = DownloadEvent {
+            artifact_id: artifact_id.to_string(),
+            asset_id: asset_id.to_string(),
+            ip,
+            client,
+            at,
+        };
+        if self.tx.try_send(Message::Download(event)).is_err() {
+            Metrics::add(&self.metrics.downloads_dropped, 1);
+        }
+    }
+
+    /// Wait until every event queued before this call has been written.
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.tx.send(Message::Flush(done)).await.is_ok() {
+            let _ = wait.await;
+        }
+    }
+}
+
+async fn write_loop(db: SqlitePool, metrics: Arc<Metrics>, mut rx: mpsc::Receiver<Message>) {
+    let mut batch = Vec::new();
+    let mut flushed = Vec::new();
+    while let Some(message) = rx.recv().await {
+        let mut next = Some(message);
+        while let Some(message) = next {
+            match message {
+                Message::Download(event) => batch.push(event),
+                Message::Flush(done) => flushed.push(done),
+            }
+            next = if batch.len() < MAX_BATCH {
+                rx.try_recv().ok()
+            } else {
+                None
+            };
+        }
+
+        if !batch.is_empty() {
+            match write(&db, &batch).await {
+                Ok(()) => Metrics::add(&metrics.downloads_recorded, batch.len() as u64),
+                Err(e) => {
+                    tracing::error!("failed to record {} download(s): {:#}", batch.len(), e);
+                    Metrics::add(&metrics.downloads_dropped, batch.len() as u64);
+                }
+            }
+            batch.clear();
+        }
+        for done in flushed.drain(..) {
+            let _ = done.send(());
+        }
+    }
+}
+
+async fn write(db: &SqlitePool, batch: &[DownloadEvent]) -> Result<()> {
+    let mut tx = db::begin_write(db).await?;
+    for event in batch {
+        // The artifact may have been deleted since it was downloaded.
+        sqlx::query(
+            "INSERT INTO download_stats \
+             (id, artifact_id, asset_id, downloaded_at, ip, user_agent, client, bot, referrer) \
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9 \
+             WHERE EXISTS (SELECT 1 FROM artifacts WHERE id = ?2)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&event.artifact_id)
+        .bind(&event.asset_id)
+        .bind(&event.at)
+        .bind(&event.ip)
+        .bind(&event.client.user_agent)
+        .bind(&event.client.family)
+        .bind(event.client.bot)
+        .bind(&event.client.referrer)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,313 @@
+//! Retention rules and the garbage collector that enforces them.
+//!
+//! See [`cask_types::RetentionRule`] for how a rule selects versions. Each
+//! rule is evaluated on its own; a version is deleted if any matching rule
+//! selects it.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use cask_types::{ArtifactRow, CreateRetentionRule, GcCandidate, GcReport, RetentionRule};
+use glob::Pattern;
+
+use crate::artifacts;
+use crate::audit;
+use crate::state::AppState;
+
+#[derive(sqlx::FromRow)]
+struct Version {
+    #[sqlx(flatten)]
+    row: ArtifactRow,
+    /// Bytes of all its assets.
+    total_size: i64,
+    age_days: f64,
+    /// Days since the most recent download, if any.
+    idle_days: Option<f64>,
+}
+
+/// Reject malformed rules, and rules with no deletion criterion, which would
+/// select every version.
+pub fn validate(rule: &CreateRetentionRule) -> Result<()> {
+    Pattern::new(&rule.pattern).with_context(|| format!("invalid pattern {:?}", rule.pattern))?;
+    if rule.keep_last.is_none() && rule.max_age_days.is_none() {
+        bail!("a rule needs keep_last or max_age_days");
+    }
+    for (field, value) in [
+        ("keep_last", rule.keep_last),
+        ("max_age_days", rule.max_age_days),
+        ("keep_downloaded_days", rule.keep_downloaded_days),
+    ] {
+        if value.is_some_and(|v| v < 0) {
+            bail!("{} must not be negative", field);
+        }
+    }
+    Ok(())
+}
+
+/// Work out which versions the current rules select for deletion and, unless
+/// `dry_run`, delete them on behalf of `actor`.
+pub async fn collect(state: &AppState, actor: &str, dry_run: bool) -> Result<GcReport> {
+    let rules = sqlx::query_as::<_, RetentionRule>(
+        "SELECT id, pattern, keep_last, max_age_days, keep_downloaded_days, keep_tagged, \
+         created_at FROM retention_rules ORDER BY created_at",
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut report = GcReport {
+        dry_run,
+        deleted: Vec::new(),
+        freed_bytes: 0,
+    };
+    if rules.is_empty() {
+        return Ok(report);
+    }
+
+    // Newest first within each name, so a version's index is its rank.
+    let versions = sqlx::query_as::<_, Version>(
+        "SELECT a.id, a.name, a.version, a.filename, a.sha256, a.size, a.created_at, a.yanked_at, \
+         (SELECT COALESCE(SUM(s.size), 0) FROM assets s WHERE s.artifact_id = a.id) AS total_size, \
+         julianday('now') - julianday(a.created_at) AS age_days, \
+         julianday('now') - julianday(d.last_download) AS idle_days \
+         FROM artifacts a LEFT JOIN ( \
+             SELECT artifact_id, MAX(downloaded_at) AS last_download \
+             FROM download_events GROUP BY artifact_id \
+         ) d ON d.artifact_id = a.id \
+         ORDER BY a.name, a.created_at DESC, a.rowid DESC",
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut selected: Vec<(&ArtifactRow, GcCandidate)> = Vec::new();
+    let mut seen = HashSet::new();
+    for rule in &rules {
+        let Ok(pattern) = Pattern::new(&rule.pattern) else {
+            tracing::warn!("skipping retention rule {}: invalid pattern", rule.id);
+            continue;
+        };
+        // Versions the rule's tag protects, in one query for all of them.
+        let tagged: HashSet<String> = match &rule.keep_tagged {
+            Some(key) => {
+                sqlx::query_scalar("SELECT artifact_id FROM artifact_metadata WHERE key = ?")
+                    .bind(key)
+                    .fetch_all(&state.db)
+                    .await?
+                    .into_iter()
+                    .collect()
+            }
+            None => HashSet::new(),
+        };
+
+        let mut rank = 0;
+        let mut current_name = None;
+        for version in &versions {
+            let row = &version.row;
+            if !pattern.matches(&row.name) {
+                continue;
+            }
+            if current_name != Some(&row.name) {
+                current_name = Some(&row.name);
+                rank = 0;
+            }
+            rank += 1;
+
+            let Some(reason) = select(rule, version, rank, &tagged) else {
+                continue;
+            };
+            if seen.insert(row.id.clone()) {
+                selected.push((
+                    row,
+                    GcCandidate {
+                        name: row.name.clone(),
+                        version: row.version.clone(),
+                        size: version.total_size,
+                        rule_id: rule.id.clone(),
+                        reason,
+                    },
+                ));
+            }
+        }
+    }
+
+    for (row, candidate) in selected {
+        if !dry_run {
+            let detail = format!("retention rule {}: {}", candidate.rule_id, candidate.reason);
+            // Someone else may have deleted it since we listed it.
+            if !artifacts::delete(state, row, actor, Some(&detail)).await? {
+                continue;
+            }
+        }
+        report.freed_bytes += candidate.size;
+        report.deleted.push(candidate);
+    }
+    Ok(report)
+}
+
+/// Why `rule` selects `version` (the `rank`-th newest of its name), or `None`
+/// if it keeps it.
+fn select(
+    rule: &RetentionRule,
+    version: &Version,
+    rank: i64,
+    tagged: &HashSet<String>,
+) -> Option<String> {
+    let mut reasons = Vec::new();
+    if let Some(keep) = rule.keep_last {
+        if rank <= keep {
+            return None;
+        }
+        reasons.push(format!("not among the newest {}", keep));
+    }
+    if let Some(days) = rule.max_age_days {
+        if version.age_days <= days as f64 {
+            return None;
+        }
+        reasons.push(format!("older than {} days", days));
+    }
+
+    if let Some(days) = rule.keep_downloaded_days
+        && version.idle_days.is_some_and(|idle| idle <= days as f64)
+    {
+        return None;
+    }
+    if tagged.contains(&version.row.id) {
+        return None;
+    }
+
+    Some(reasons.join(", "))
+}
+
+/// Run garbage collection every `interval`, skipping ticks while the server
+/// is read-only; errors are logged and retried on the next tick.
+pub async fn run(state: AppState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick completes immediately; wait a full interval instead of
+    // collecting while the server is still starting.
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if state.read_only.read().unwrap().enabled {
+            continue;
+        }
+        match collect(&state, audit::GC_ACTOR, false).await {
+            Ok(report) if !report.deleted.is_empty() => tracing::info!(
+                "garbage collection deleted {} version(s), freeing {} bytes",
+                report.deleted.len(),
+                report.freed_bytes
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::error!("garbage collection failed: {:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule() -> RetentionRule {
+        RetentionRule {
+            id: "rule".to_string(),
+            pattern: "*".to_string(),
+            keep_last: None,
+            max_age_days: None,
+            keep_downloaded_days: None,
+            keep_tagged: None,
+            created_at: "2026-01-01 00:00:00".to_string(),
+        }
+    }
+
+    fn version(age_days: f64, idle_days: Option<f64>) -> Version {
+        Version {
+            row: ArtifactRow {
+                id: "v1".to_string(),
+                name: "app".to_string(),
+                version: "1.0.0".to_string(),
+                filename: "app.tar.gz".to_string(),
+                sha256: String::new(),
+                size: 0,
+                created_at: "2026-01-01 00:00:00".to_string(),
+                yanked_at: None,
+                assets: Vec::new(),
+            },
+            total_size: 0,
+            age_days,
+            idle_days,
+        }
+    }
+
+    fn select(rule: &RetentionRule, version: &Version, rank: i64) -> Option<String> {
+        super::select(rule, version, rank, &HashSet::new())
+    }
+
+    #[test]
+    fn keep_last_keeps_the_newest() {
+        let rule = RetentionRule {
+            keep_last: Some(2),
+            ..rule()
+        };
+        let v = version(1.0, None);
+        assert_eq!(select(&rule, &v, 1), None);
+        assert_eq!(select(&rule, &v, 2), None);
+        assert_eq!(
+            select(&rule, &v, 3).as_deref(),
+            Some("not among the newest 2")
+        );
+    }
+
+    #[test]
+    fn max_age_keeps_younger_versions() {
+        let rule = RetentionRule {
+            max_age_days: Some(30),
+            ..rule()
+        };
+        assert_eq!(select(&rule, &version(30.0, None), 1), None);
+        assert_eq!(
+            select(&rule, &version(30.5, None), 1).as_deref(),
+            Some("older than 30 days")
+        );
+    }
+
+    #[test]
+    fn criteria_must_all_select() {
+        let rule = RetentionRule {
+            keep_last: Some(1),
+            max_age_days: Some(30),
+            ..rule()
+        };
+        // Old enough but the newest.
+        assert_eq!(select(&rule, &version(90.0, None), 1), None);
+        // Not the newest but too young.
+        assert_eq!(select(&rule, &version(10.0, None), 2), None);
+        assert_eq!(
+            select(&rule, &version(90.0, None), 2).as_deref(),
+            Some("not among the newest 1, older than 30 days")
+        );
+    }
+
+    #[test]
+    fn recent_downloads_protect() {
+        let rule = RetentionRule {
+            max_age_days: Some(30),
+            keep_downloaded_days: Some(7),
+            ..rule()
+        };
+        assert_eq!(select(&rule, &version(90.0, Some(3.0)), 1), None);
+        assert!(select(&rule, &version(90.0, Some(8.0)), 1).is_some());
+        assert!(select(&rule, &version(90.0, None), 1).is_some());
+    }
+
+    #[test]
+    fn tagged_versions_are_kept() {
+        let rule = RetentionRule {
+            max_age_days: Some(30),
+            keep_tagged: Some("release".to_string()),
+            ..rule()
+        };
+        let v = version(90.0, None);
+        let tagged = HashSet::from([v.row.id.clone()]);
+        assert_eq!(super::select(&rule, &v, 1, &tagged), None);
+        assert!(super::select(&rule, &v, 1, &HashSet::new()).is_some());
+    }
+}
@@ -0,0 +1,73 @@
+//! Compaction of old `download_stats` rows into `download_stats_daily`.
+//!
+//! Stats queries read the `download_events` view, which combines both
+//! tables, so rolled-up days keep counting with only unique-IP figures
+//! becoming estimates. Rolled-up days keep the client family and referrer of
+//! their downloads but not the raw user-agent.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+
+use crate::db;
+use crate::state::AppState;
+
+/// How often the rollup runs.
+const ROLLUP_EVERY: Duration = Duration::from_secs(60 * 60);
+
+/// Fold raw rows from whole UTC days that ended more than `age` ago into
+/// daily aggregates and delete them. Returns the number of raw rows
+/// compacted.
+pub async fn rollup(db: &SqlitePool, age: Duration) -> Result<u64> {
+    let modifier = format!("-{} seconds", age.as_secs());
+    let mut tx = db::begin_write(db).await?;
+
+    let cutoff: String = sqlx::query_scalar("SELECT date('now', ?)")
+        .bind(&modifier)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    // A day can be rolled up twice if rows for it arrive late (e.g. after a
+    // restore); unique IPs then add up like any other rolled-up days.
+    sqlx::query(
+        "INSERT INTO download_stats_daily \
+         (artifact_id, asset_id, day, client, bot, referrer, downloads, unique_ips) \
+         SELECT artifact_id, asset_id, date(downloaded_at), client, MAX(bot), \
+         COALESCE(referrer, ''), COUNT(*), COUNT(DISTINCT ip) \
+         FROM download_stats WHERE downloaded_at < ? \
+         GROUP BY artifact_id, asset_id, date(downloaded_at), client, COALESCE(referrer, '') \
+         ON CONFLICT (artifact_id, asset_id, day, client, referrer) DO UPDATE SET \
+         downloads = downloads + excluded.downloads, \
+         unique_ips = unique_ips + excluded.unique_ips",
+    )
+    .bind(&cutoff)
+    .execute(&mut *tx)
+    .await?;
+
+    let deleted = sqlx::query("DELETE FROM download_stats WHERE downloaded_at < ?")
+        .bind(&cutoff)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    tx.commit().await?;
+    Ok(deleted)
+}
+
+/// Roll up every hour, skipping runs while the server is read-only; errors
+/// are logged and retried on the next run.
+pub async fn run(state: AppState, age: Duration) {
+    let mut ticker = tokio::time::interval(ROLLUP_EVERY);
+    loop {
+        ticker.tick().await;
+        if state.read_only.read().unwrap().enabled {
+            continue;
+        }
+        match rollup(&state.db, age).await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("rolled up {} download stat row(s)", n),
+            Err(e) => tracing::error!("download stats rollup failed: {:#}", e),
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! Metadata schemas and their enforcement.
+//!
+//! See [`cask_types::CreateMetadataSchema`] for how a schema is given. The
+//! custom metadata of a version must satisfy every schema whose pattern
+//! matches its name.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{Context, Result, anyhow, bail};
+use cask_types::{CreateMetadataSchema, MetadataSchema, SchemaViolation};
+use glob::Pattern;
+use jsonschema::Validator;
+use jsonschema::error::ValidationErrorKind;
+use serde_json::{Map, Value, json};
+use sqlx::SqliteExecutor;
+
+/// Compiled validators by schema ID. Schemas are never changed once
+/// created, so an entry stays valid until its schema is deleted.
+#[derive(Default)]
+pub struct Validators(RwLock<HashMap<String, Arc<Validator>>>);
+
+impl Validators {
+    pub fn insert(&self, id: &str, validator: Validator) {
+        self.0
+            .write()
+            .unwrap()
+            .insert(id.to_string(), Arc::new(validator));
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.0.write().unwrap().remove(id);
+    }
+
+    /// The validator for `schema`, compiled now if it was registered before
+    /// the server started.
+    fn get(&self, schema: &MetadataSchema) -> Result<Arc<Validator>> {
+        if let Some(validator) = self.0.read().unwrap().get(&schema.id) {
+            return Ok(validator.clone());
+        }
+        let validator =
+            Arc::new(jsonschema::validator_for(&schema.schema).map_err(|e| anyhow!("{}", e))?);
+        self.0
+            .write()
+            .unwrap()
+            .insert(schema.id.clone(), validator.clone());
+        Ok(validator)
+    }
+}
+
+/// Reject malformed schemas, and return the JSON Schema to store for `body`
+/// along with its compiled validator.
+pub fn compile(body: &CreateMetadataSchema) -> Result<(Value, Validator)> {
+    Pattern::new(&body.pattern).with_context(|| format!("invalid pattern {:?}", body.pattern))?;
+    let schema = match (&body.schema, body.keys.is_empty()) {
+        (Some(_), false) => bail!("give either schema or keys, not both"),
+        (None, true) => bail!("a schema needs schema or keys"),
+        (Some(_), true) if body.closed => bail!("closed only applies to keys"),
+        (Some(schema), true) => schema.clone(),
+        (None, false) => {
+            let properties: Map<String, Value> = body
+                .keys
+                .iter()
+                .map(|(key, spec)| {
+                    let property = match spec.kind {
+                        Some(kind) => json!({ "type": kind }),
+                        None => json!({}),
+                    };
+                    (key.clone(), property)
+                })
+                .collect();
+            let required: Vec<&String> = body
+                .keys
+                .iter()
+                .filter(|(_, spec)| spec.required)
+                .map(|(key, _)| key)
+                .collect();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+                "additionalProperties": !body.closed,
+            })
+        }
+    };
+    let validator =
+        jsonschema::validator_for(&schema).map_err(|e| anyhow!("invalid schema: {}", e))?;
+    Ok((schema, validator))
+}
+
+type Row = (String, String, String, String);
+
+pub async fn list(db: impl SqliteExecutor<'_>) -> Result<Vec<MetadataSchema>> {
+    let rows = sqlx::query_as::<_, Row>(
+        "SELECT id, pattern, schema, created_at FROM metadata_schemas ORDER BY created_at",
+    )
+    .fetch_all(db)
+    .await?;
+
+    rows.into_iter().map(from_row).collect()
+}
+
+fn from_row((id, pattern, schema, created_at): Row) -> Result<MetadataSchema> {
+    let schema = serde_json::from_str(&schema)
+        .with_context(|| format!("metadata schema {} is not valid JSON", id))?;
+    Ok(MetadataSchema {
+        id,
+        pattern,
+        schema,
+        created_at,
+    })
+}
+
+/// Every way `after`, the custom keys of a version of `name` once changed
+/// from `before` (`None` for a new version), fails the schemas that apply to
+/// it. Failures `before` already had that involve only keys the change
+/// leaves alone aren't reported, so metadata stored before a schema was
+/// added doesn't block unrelated changes.
+pub async fn check(
+    db: impl SqliteExecutor<'_>,
+    validators: &Validators,
+    name: &str,
+    before: Option<&Map<String, Value>>,
+    after: &Map<String, Value>,
+) -> Result<Vec<SchemaViolation>> {
+    let schemas = list(db).await?;
+    let found = violations(&schemas, validators, name, after);
+    let Some(before) = before else {
+        return Ok(found);
+    };
+    let existing = violations(&schemas, validators, name, before);
+    Ok(found
+        .into_iter()
+        .filter(|violation| {
+            let unchanged = violation
+                .key
+                .as_ref()
+                .is_none_or(|key| before.get(key) == after.get(key));
+            !(unchanged && existing.contains(violation))
+        })
+        .collect())
+}
+
+fn violations(
+    schemas: &[MetadataSchema],
+    validators: &Validators,
+    name: &str,
+    metadata: &Map<String, Value>,
+) -> Vec<SchemaViolation> {
+    let instance = Value::Object(metadata.clone());
+    let mut violations = Vec::new();
+
+    for schema in schemas {
+        let Ok(pattern) = Pattern::new(&schema.pattern) else {
+            tracing::warn!("skipping metadata schema {}: invalid pattern", schema.id);
+            continue;
+        };
+        if !pattern.matches(name) {
+            continue;
+        }
+        let validator = match validators.get(schema) {
+            Ok(validator) => validator,
+            Err(e) => {
+                tracing::warn!("skipping metadata schema {}: {}", schema.id, e);
+                continue;
+            }
+        };
+
+        for error in validator.iter_errors(&instance) {
+            let path = error.instance_path.to_string();
+            let violation = |key: Option<String>, path: String, message: String| SchemaViolation {
+                schema_id: schema.id.clone(),
+                pattern: schema.pattern.clone(),
+                key,
+                path,
+                message,
+            };
+
+            // Missing and unexpected top-level keys are reported against
+            // the key itself rather than the whole object.
+            match &error.kind {
+                ValidationErrorKind::Required { property } if path.is_empty() => {
+                    let key = property.as_str().unwrap_or_default().to_string();
+                    violations.push(violation(
+                        Some(key.clone()),
+                        pointer(&key),
+                        "required key is missing".to_string(),
+                    ));
+                }
+                ValidationErrorKind::AdditionalProperties { unexpected } if path.is_empty() => {
+                    for key in unexpected {
+                        violations.push(violation(
+                            Some(key.clone()),
+                            pointer(key),
+                            "key is not allowed".to_string(),
+                        ));
+                    }
+                }
+                _ => violations.push(violation(top_level_key(&path), path, error.to_string())),
+            }
+        }
+    }
+
+    violations
+}
+
+/// JSON Pointer to a top-level key.
+fn pointer(key: &str) -> String {
+    format!("/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// The top-level key a JSON Pointer goes through, if any.
+fn top_level_key(path: &str) -> Option<String> {
+    let segment = path.strip_prefix('/')?.split('/').next()?;
+    Some(segment.replace("~1", "/").replace("~0", "~"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use cask_types::{KeySpec, KeyType};
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::db;
+
+    fn keys(pattern: &str, keys: &[(&str, Option<KeyType>, bool)]) -> CreateMetadataSchema {
+        CreateMetadataSchema {
+            pattern: pattern.to_string(),
+            schema: None,
+            keys: keys
+                .iter()
+                .map(|(key, kind, required)| {
+                    let spec = KeySpec {
+                        kind: *kind,
+                        required: *required,
+                    };
+                    (key.to_string(), spec)
+                })
+                .collect(),
+            closed: false,
+        }
+    }
+
+    fn object(value: Value) -> Map<String, Value> {
+        let Value::Object(map) = value else {
+            panic!("not an object")
+        };
+        map
+    }
+
+    #[test]
+    fn keys_compile_to_a_json_schema() {
+        let mut body = keys(
+            "app*",
+            &[
+                ("git_sha", Some(KeyType::String), true),
+                ("notes", None, false),
+            ],
+        );
+        body.closed = true;
+        let (schema, validator) = compile(&body).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": { "git_sha": { "type": "string" }, "notes": {} },
+                "required": ["git_sha"],
+                "additionalProperties": false,
+            })
+        );
+        assert!(validator.is_valid(&json!({ "git_sha": "abc", "notes": [1] })));
+        assert!(!validator.is_valid(&json!({ "git_sha": 1 })));
+        assert!(!validator.is_valid(&json!({ "git_sha": "abc", "other": 1 })));
+    }
+
+    #[test]
+    fn compile_rejects_malformed_schemas() {
+        let valid = keys("app", &[("a", None, false)]);
+        let with_schema = |schema: Value| CreateMetadataSchema {
+            schema: Some(schema),
+            keys: BTreeMap::new(),
+            ..valid.clone()
+        };
+
+        assert!(compile(&with_schema(json!({ "type": "object" }))).is_ok());
+        for (body, error) in [
+            (
+                CreateMetadataSchema {
+                    pattern: "[".to_string(),
+                    ..valid.clone()
+                },
+                "invalid pattern",
+            ),
+            (
+                CreateMetadataSchema {
+                    schema: Some(json!({})),
+                    ..valid.clone()
+                },
+                "not both",
+            ),
+            (keys("app", &[]), "needs schema or keys"),
+            (
+                CreateMetadataSchema {
+                    closed: true,
+                    ..with_schema(json!({}))
+                },
+                "closed only applies to keys",
+            ),
+            (with_schema(json!({ "type": "nonsense" })), "invalid schema"),
+        ] {
+            let message = format!("{:#}", compile(&body).unwrap_err());
+            assert!(message.contains(error), "{:?}: {}", body, message);
+        }
+    }
+
+    /// A database holding a schema compiled from `body`, and its ID.
+    async fn with_schema(body: CreateMetadataSchema) -> (SqlitePool, String, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::create_pool(dir.path()).await.unwrap();
+        let (schema, _) = compile(&body).unwrap();
+        sqlx::query("INSERT INTO metadata_schemas (id, pattern, schema) VALUES ('s1', ?, ?)")
+            .bind(&body.pattern)
+            .bind(schema.to_string())
+            .execute(&db)
+            .await
+            .unwrap();
+        (db, "s1".to_string(), dir)
+    }
+
+    #[tokio::test]
+    async fn check_reports_violations_per_key() {
+        let mut body = keys(
+            "app*",
+            &[
+                ("git_sha", Some(KeyType::String), true),
+                ("tests", Some(KeyType::Integer), false),
+            ],
+        );
+        body.closed = true;
+        let (db, _, _dir) = with_schema(body).await;
+        let validators = Validators::default();
+        let empty = Map::new();
+
+        let ok = object(json!({ "git_sha": "abc", "tests": 3 }));
+        assert!(
+            check(&db, &validators, "app", None, &ok)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        // Other names aren't covered.
+        assert!(
+            check(&db, &validators, "other", None, &empty)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let bad = object(json!({ "tests": "many", "extra": 1 }));
+        let mut found: Vec<(Option<String>, String)> = check(&db, &validators, "app", None, &bad)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.key, v.path))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                (Some("extra".to_string()), "/extra".to_string()),
+                (Some("git_sha".to_string()), "/git_sha".to_string()),
+                (Some("tests".to_string()), "/tests".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn check_ignores_existing_failures_of_unchanged_keys() {
+        let body = keys(
+            "app",
+            &[
+                ("git_sha", Some(KeyType::String), true),
+                ("tests", Some(KeyType::Integer), false),
+            ],
+        );
+        let (db, _, _dir) = with_schema(body).await;
+        let validators = Validators::default();
+
+        // Stored before the schema: no git_sha, and tests of the wrong type.
+        let before = object(json!({ "tests": "many" }));
+        let after = object(json!({ "tests": "many", "notes": "x" }));
+        assert!(
+            check(&db, &validators, "app", Some(&before), &after)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Changing a failing key must fix it.
+        let after = object(json!({ "tests": "more" }));
+        let violations = check(&db, &validators, "app", Some(&before), &after)
+            .await
+            .unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].key.as_deref(), Some("tests"));
+
+        // A change can't introduce a new failure either.
+        let before = object(json!({ "git_sha": "abc" }));
+        let after = object(json!({}));
+        let violations = check(&db, &validators, "app", Some(&before), &after)
+            .await
+            .unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].message, "required key is missing");
+    }
+
+    #[tokio::test]
+    async fn validators_are_cached_until_removed() {
+        let (db, id, _dir) = with_schema(keys("app", &[("a", None, true)])).await;
+        let validators = Validators::default();
+        let empty = Map::new();
+        assert_eq!(
+            check(&db, &validators, "app", None, &empty)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(validators.0.read().unwrap().contains_key(&id));
+
+        // A cached validator is used as is.
+        let (_, accept_all) = compile(&CreateMetadataSchema {
+            schema: Some(json!({})),
+            ..keys("app", &[])
+        })
+        .unwrap();
+        validators.insert(&id, accept_all);
+        assert!(
+            check(&db, &validators, "app", None, &empty)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        validators.remove(&id);
+        assert_eq!(
+            check(&db, &validators, "app", None, &empty)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Background re-verification of stored artifacts.
+//!
+//! Walks the assets least-recently-checked first, rehashing each file at
+//! no more than the configured read rate, and records the outcome in
+//! `scrub_results`. Failures are marked broken so downloads refuse them,
+//! and counted in `/metrics` and `/ready` until resolved.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use cask_types::FsckProblemKind;
+use sqlx::SqlitePool;
+
+use crate::fsck;
+use crate::metrics::Metrics;
+use crate::state::AppState;
+
+pub struct ScrubConfig {
+    /// Read budget in bytes per second.
+    pub bytes_per_sec: u64,
+    /// Pause between full passes.
+    pub interval: Duration,
+}
+
+#[derive(sqlx::FromRow)]
+struct Candidate {
+    id: String,
+    sha256: String,
+    size: i64,
+    broken_at: Option<String>,
+}
+
+/// Run forever; errors are logged and the pass is retried after `interval`.
+pub async fn run(state: AppState, config: ScrubConfig) {
+    tracing::info!(
+        "scrubber started ({} bytes/s, every {})",
+        config.bytes_per_sec,
+        humantime::format_duration(config.interval)
+    );
+    loop {
+        // Scrubbing writes results and broken marks.
+        if state.read_only.read().unwrap().enabled {
+            tokio::time::sleep(config.interval).await;
+            continue;
+        }
+        match pass(&state, config.bytes_per_sec).await {
+            Ok((checked, failed)) => {
+                tracing::info!(
+                    "scrub pass finished: {} checked, {} failed",
+                    checked,
+                    failed
+                )
+            }
+            Err(e) => tracing::error!("scrub pass failed: {:#}", e),
+        }
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn pass(state: &AppState, bytes_per_sec: u64) -> Result<(u64, u64)> {
+    let candidates = sqlx::query_as::<_, Candidate>(
+        "SELECT a.id, a.sha256, a.size, a.broken_at FROM assets a \
+         LEFT JOIN scrub_results s ON s.asset_id = a.id \
+         ORDER BY s.checked_at IS NOT NULL, s.checked_at",
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let (mut checked, mut failed) = (0, 0);
+    for asset in candidates {
+        if state.read_only.read().unwrap().enabled {
+            break;
+        }
+        let verified = fsck::verify(
+            &state.data_dir,
+            &asset.id,
+            &asset.sha256,
+            asset.size,
+            Some(bytes_per_sec),
+        )
+        .await;
+        // A file we can't read can't be served either, so it is marked
+        // broken too; the next pass that reads it cleanly clears the mark.
+        let problem = match verified {
+            Ok(problem) => problem,
+            Err(e) => {
+                let detail = format!("{:#}", e);
+                tracing::error!("scrub: failed to verify asset {}: {}", asset.id, detail);
+                record(&state.db, &asset.id, false, None, Some(&detail)).await?;
+                checked += 1;
+                failed += 1;
+                Metrics::add(&state.metrics.scrub_checked, 1);
+                Metrics::add(&state.metrics.scrub_failures, 1);
+                fsck::mark_broken(&state.db, &asset.id, &detail).await?;
+                continue;
+            }
+        };
+
+        // The asset may have been deleted since we listed it.
+        if matches!(problem, Some((FsckProblemKind::Missing, _)))
+            && !fsck::still_exists(&state.db, &asset.id).await?
+        {
+            continue;
+        }
+
+        record(
+            &state.db,
+            &asset.id,
+            problem.is_none(),
+            problem.as_ref().map(|(kind, _)| *kind),
+            problem.as_ref().map(|(_, detail)| detail.as_str()),
+        )
+        .await?;
+        checked += 1;
+        Metrics::add(&state.metrics.scrub_checked, 1);
+        match problem {
+            Some((_, detail)) => {
+                failed += 1;
+                Metrics::add(&state.metrics.scrub_failures, 1);
+                tracing::warn!("scrub: asset {} failed verification: {}", asset.id, detail);
+                fsck::mark_broken(&state.db, &asset.id, &detail).await?;
+            }
+            None => {
+                Metrics::add(&state.metrics.scrub_bytes, asset.size as u64);
+                if asset.broken_at.is_some() {
+                    fsck::clear_broken(&state.db, &asset.id).await?;
+                }
+            }
+        }
+    }
+    Ok((checked, failed))
+}
+
+/// Record the outcome of checking an asset, by the scrubber or by
+/// `fsck --repair`. `problem` is unset for a file that couldn't be read.
+pub async fn record(
+    db: &SqlitePool,
+    asset_id: &str,
+    ok: bool,
+    problem: Option<FsckProblemKind>,
+    detail: Option<&str>,
+) -> Result<()> {
+    let kind = problem.map(serde_json::to_value).transpose()?;
+    sqlx::query(
+        "INSERT INTO scrub_results (asset_id, checked_at, ok, problem, detail) \
+         SELECT ?1, datetime('now'), ?2, ?3, ?4 WHERE EXISTS (SELECT 1 FROM assets WHERE id = ?1) \
+         ON CONFLICT (asset_id) DO UPDATE SET checked_at = excluded.checked_at, \
+         ok = excluded.ok, problem = excluded.problem, detail = excluded.detail",
+    )
+    .bind(asset_id)
+    .bind(ok)
+    .bind(kind.as_ref().and_then(|k| k.as_str()))
+    .bind(detail)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Number of assets whose latest scrub failed.
+pub async fn failing(db: &SqlitePool) -> Result<i64> {
+    let count = sqlx::query_scalar("SELECT COUNT(*) FROM scrub_results WHERE ok = 0")
+        .fetch_one(db)
+        .await?;
+    Ok(count)
+}
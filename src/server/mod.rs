@@ -1,36 +1,61 @@
 pub mod routes;
 
+use std::sync::{Arc, Mutex, RwLock};
+
 use anyhow::{Context, Result};
 use tokio::net::TcpListener;
 use tokio::signal;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
-use crate::cli::ServerOpts;
+use crate::artifacts;
+use crate::cli::{LogFormat, ServerOpts};
 use crate::db;
+use crate::logfile::{LOG_FILE_NAME, RotatingFile, RotationPolicy};
+use crate::maintenance;
+use crate::metrics::Metrics;
+use crate::privacy::{self, IpPolicy};
+use crate::recorder::DownloadRecorder;
+use crate::scrub::{self, ScrubConfig};
 use crate::state::AppState;
+use crate::{retention, rollup};
 
 /// Initialize tracing and create + run the tokio runtime.
-/// `foreground`: true = log to stdout, false = log to file (daemon mode).
+/// `foreground`: true = log to stdout, false = log to a rotating file (daemon mode).
 pub fn run(opts: ServerOpts, foreground: bool) -> Result<()> {
-    init_tracing(&opts.log_level, foreground);
+    init_tracing(&opts, foreground)?;
 
     let rt = tokio::runtime::Runtime::new().context("failed to create tokio runtime")?;
     rt.block_on(run_server(opts))
 }
 
-fn init_tracing(log_level: &str, foreground: bool) {
-    let filter = EnvFilter::try_new(log_level).unwrap_or_else(|_| EnvFilter::new("info"));
+fn init_tracing(opts: &ServerOpts, foreground: bool) -> Result<()> {
+    let filter = EnvFilter::try_new(&opts.log_level).unwrap_or_else(|_| EnvFilter::new("info"));
 
-    if foreground {
-        tracing_subscriber::fmt()
-            .with_env_filter(filter)
-            .init();
+    let writer = if foreground {
+        BoxMakeWriter::new(std::io::stdout)
     } else {
-        tracing_subscriber::fmt()
-            .with_env_filter(filter)
-            .with_ansi(false)
-            .init();
+        let policy = RotationPolicy {
+            max_size: opts.log_max_size,
+            interval: opts.log_rotate,
+            keep: opts.log_keep,
+            compress: opts.log_compress,
+        };
+        let log_file = RotatingFile::open(&opts.data_dir.join(LOG_FILE_NAME), policy)?;
+        BoxMakeWriter::new(Mutex::new(log_file))
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(foreground)
+        .with_writer(writer);
+
+    match opts.log_format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
     }
+
+    Ok(())
 }
 
 async fn run_server(opts: ServerOpts) -> Result<()> {
@@ -40,12 +65,59 @@ async fn run_server(opts: ServerOpts) -> Result<()> {
 
     let pool = db::create_pool(data_dir).await?;
 
+    let cleaned = artifacts::clean_pending(&pool, data_dir)
+        .await
+        .context("failed to clean up interrupted uploads")?;
+    if cleaned > 0 {
+        tracing::info!(
+            "cleaned up {} blobs left by interrupted uploads or deletions",
+            cleaned
+        );
+    }
+
+    let read_only = maintenance::load(data_dir)?;
+    if read_only.enabled {
+        tracing::warn!(
+            "starting in read-only mode{}",
+            read_only
+                .reason
+                .as_deref()
+                .map(|r| format!(": {}", r))
+                .unwrap_or_default()
+        );
+    }
+
+    let metrics = Arc::new(Metrics::default());
+    let recorder = DownloadRecorder::spawn(pool.clone(), metrics.clone(), opts.stats_queue_size);
     let state = AppState {
         db: pool,
         data_dir: data_dir.clone(),
         max_upload_size: opts.max_upload_size,
+        metrics,
+        min_free_space: opts.min_free_space,
+        read_only: Arc::new(RwLock::new(read_only)),
+        recorder: recorder.clone(),
+        ip_policy: Arc::new(IpPolicy::new(opts.ip_mode, opts.trusted_proxies.clone())),
+        schemas: Default::default(),
     };
 
+    if opts.scrub_rate > 0 {
+        let config = ScrubConfig {
+            bytes_per_sec: opts.scrub_rate,
+            interval: opts.scrub_interval,
+        };
+        tokio::spawn(scrub::run(state.clone(), config));
+    }
+    if !opts.gc_interval.is_zero() {
+        tokio::spawn(retention::run(state.clone(), opts.gc_interval));
+    }
+    if !opts.ip_retention.is_zero() {
+        tokio::spawn(privacy::run(state.clone(), opts.ip_retention));
+    }
+    if !opts.stats_rollup_after.is_zero() {
+        tokio::spawn(rollup::run(state.clone(), opts.stats_rollup_after));
+    }
+
     let app = routes::router(state);
 
     let addr = format!("{}:{}", opts.host, opts.port);
@@ -63,6 +135,8 @@ async fn run_server(opts: ServerOpts) -> Result<()> {
     .await
     .context("server error")?;
 
+    recorder.flush().await;
+
     tracing::info!("cask shut down gracefully");
     Ok(())
 }
@@ -0,0 +1,268 @@
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{
+    Json, Router,
+    routing::{delete, get, post},
+};
+use cask_types::{
+    AuditEntry, CreateMetadataSchema, CreateRetentionRule, FsckReport, GcReport, MetadataSchema,
+    Quota, RetentionRule, SetQuotaRequest,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::RequireAdmin;
+use crate::error::AppError;
+use crate::state::AppState;
+use crate::{fsck, quota, retention, schema};
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/v1/admin/fsck", post(run_fsck))
+        .route("/v1/admin/retention", post(create_rule).get(list_rules))
+        .route("/v1/admin/retention/{id}", delete(delete_rule))
+        .route("/v1/admin/gc", post(run_gc))
+        .route("/v1/admin/audit", get(list_audit))
+        .route("/v1/admin/quotas", post(set_quota).get(list_quotas))
+        .route("/v1/admin/quotas/{id}", delete(delete_quota))
+        .route("/v1/admin/schemas", post(create_schema).get(list_schemas))
+        .route("/v1/admin/schemas/{id}", delete(delete_schema))
+}
+
+#[derive(Deserialize)]
+struct FsckParams {
+    #[serde(default)]
+    repair: bool,
+}
+
+async fn run_fsck(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Query(params): Query<FsckParams>,
+) -> Result<Json<FsckReport>, AppError> {
+    // On its own task, so a repair isn't abandoned halfway if the client
+    // gives up waiting.
+    let report =
+        tokio::spawn(async move { fsck::run(&state.db, &state.data_dir, params.repair).await })
+            .await
+            .map_err(anyhow::Error::from)??;
+    Ok(Json(report))
+}
+
+async fn create_rule(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Json(body): Json<CreateRetentionRule>,
+) -> Result<impl IntoResponse, AppError> {
+    retention::validate(&body)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+
+    let rule = sqlx::query_as::<_, RetentionRule>(
+        "INSERT INTO retention_rules \
+         (id, pattern, keep_last, max_age_days, keep_downloaded_days, keep_tagged) \
+         VALUES (?, ?, ?, ?, ?, ?) \
+         RETURNING id, pattern, keep_last, max_age_days, keep_downloaded_days, keep_tagged, \
+         created_at",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&body.pattern)
+    .bind(body.keep_last)
+    .bind(body.max_age_days)
+    .bind(body.keep_downloaded_days)
+    .bind(&body.keep_tagged)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+async fn list_rules(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+) -> Result<Json<Vec<RetentionRule>>, AppError> {
+    let rules = sqlx::query_as::<_, RetentionRule>(
+        "SELECT id, pattern, keep_last, max_age_days, keep_downloaded_days, keep_tagged, \
+         created_at FROM retention_rules ORDER BY created_at",
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(rules))
+}
+
+async fn delete_rule(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = sqlx::query("DELETE FROM retention_rules WHERE id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found("retention rule not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct GcParams {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+async fn run_gc(
+    State(state): State<AppState>,
+    auth: RequireAdmin,
+    Query(params): Query<GcParams>,
+) -> Result<Json<GcReport>, AppError> {
+    let report = retention::collect(&state, &auth.0.token_id, params.dry_run).await?;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+struct AuditParams {
+    #[serde(default = "default_audit_limit")]
+    limit: i64,
+}
+
+fn default_audit_limit() -> i64 {
+    100
+}
+
+/// Most audit entries returned at once.
+const MAX_AUDIT_LIMIT: i64 = 1000;
+
+async fn list_audit(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Query(params): Query<AuditParams>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    let entries = sqlx::query_as::<_, AuditEntry>(
+        "SELECT id, at, actor, action, name, version, detail \
+         FROM audit_log ORDER BY at DESC, rowid DESC LIMIT ?",
+    )
+    .bind(params.limit.clamp(1, MAX_AUDIT_LIMIT))
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(entries))
+}
+
+async fn set_quota(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Json(body): Json<SetQuotaRequest>,
+) -> Result<Json<Quota>, AppError> {
+    if body.max_bytes.is_none() && body.max_artifacts.is_none() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "a quota needs max_bytes or max_artifacts",
+        ));
+    }
+    if body.max_bytes.is_some_and(|v| v < 0) || body.max_artifacts.is_some_and(|v| v < 0) {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "quota limits must not be negative",
+        ));
+    }
+
+    let quota = sqlx::query_as::<_, Quota>(
+        "INSERT INTO quotas (id, scope, target, max_bytes, max_artifacts) \
+         VALUES (?, ?, ?, ?, ?) \
+         ON CONFLICT (scope, target) DO UPDATE SET \
+         max_bytes = excluded.max_bytes, max_artifacts = excluded.max_artifacts \
+         RETURNING id, scope, target, max_bytes, max_artifacts, created_at",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(body.scope)
+    .bind(&body.target)
+    .bind(body.max_bytes)
+    .bind(body.max_artifacts)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(quota))
+}
+
+async fn list_quotas(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+) -> Result<Json<Vec<Quota>>, AppError> {
+    Ok(Json(quota::list(&state.db, None).await?))
+}
+
+async fn delete_quota(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = sqlx::query("DELETE FROM quotas WHERE id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found("quota not found"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn create_schema(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Json(body): Json<CreateMetadataSchema>,
+) -> Result<impl IntoResponse, AppError> {
+    let (compiled, validator) = schema::compile(&body)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+    let id = Uuid::new_v4().to_string();
+
+    let created_at = sqlx::query_scalar::<_, String>(
+        "INSERT INTO metadata_schemas (id, pattern, schema) VALUES (?, ?, ?) \
+         RETURNING created_at",
+    )
+    .bind(&id)
+    .bind(&body.pattern)
+    .bind(compiled.to_string())
+    .fetch_one(&state.db)
+    .await?;
+    state.schemas.insert(&id, validator);
+
+    let created = MetadataSchema {
+        id,
+        pattern: body.pattern,
+        schema: compiled,
+        created_at,
+    };
+
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+async fn list_schemas(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+) -> Result<Json<Vec<MetadataSchema>>, AppError> {
+    Ok(Json(schema::list(&state.db).await?))
+}
+
+async fn delete_schema(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = sqlx::query("DELETE FROM metadata_schemas WHERE id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found("metadata schema not found"));
+    }
+    state.schemas.remove(&id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
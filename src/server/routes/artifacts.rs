@@ -1,16 +1,32 @@
-use axum::body::Bytes;
+use std::collections::BTreeMap;
+
+use axum::body::{Body, Bytes};
 use axum::extract::{ConnectInfo, Path, Query, State};
-use axum::http::{HeaderMap, header};
-use axum::response::IntoResponse;
-use axum::{Json, Router, routing::{get, put}};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use axum::{
+    Json, Router,
+    routing::{get, put},
+};
+use cask_types::{
+    ArtifactRow, ListQuery, ListSort, PackageSummary, SHA256_HEADER, SortOrder, UploadParams,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use sha2::{Digest, Sha256};
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection, Transaction};
 use uuid::Uuid;
 
+use super::{metadata, packages, stats};
+use crate::artifacts;
+use crate::audit;
 use crate::auth::RequireToken;
+use crate::db;
 use crate::error::AppError;
+use crate::quota;
 use crate::state::AppState;
 use crate::storage;
+use crate::useragent::ClientInfo;
 
 pub fn routes() -> Router<AppState> {
     Router::new()
@@ -20,66 +36,372 @@ pub fn routes() -> Router<AppState> {
             "/v1/artifacts/{name}/{version}",
             put(upload).get(download).delete(delete_artifact),
         )
+        .route(
+            "/v1/artifacts/{name}/{version}/yank",
+            put(yank).delete(unyank),
+        )
 }
 
-#[derive(Serialize, sqlx::FromRow)]
-struct ArtifactRow {
-    id: String,
-    name: String,
-    version: String,
-    filename: String,
-    sha256: String,
-    size: i64,
-    created_at: String,
-}
-
-#[derive(Deserialize)]
-struct UploadParams {
-    filename: Option<String>,
-}
+/// Page size when `limit` isn't given, and the largest allowed.
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
 
 async fn list_artifacts(
     State(state): State<AppState>,
-) -> Result<Json<Vec<ArtifactRow>>, AppError> {
-    let rows = sqlx::query_as::<_, ArtifactRow>(
-        "SELECT id, name, version, filename, sha256, size, created_at \
-         FROM artifacts ORDER BY name, created_at DESC",
-    )
-    .fetch_all(&state.db)
-    .await?;
-
-    Ok(Json(rows))
+    uri: Uri,
+    Query(query): Query<ListQuery>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<impl IntoResponse, AppError> {
+    list(&state, None, &uri, &with_meta(query, params)).await
 }
 
 async fn list_versions(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Vec<ArtifactRow>>, AppError> {
-    let rows = sqlx::query_as::<_, ArtifactRow>(
-        "SELECT id, name, version, filename, sha256, size, created_at \
-         FROM artifacts WHERE name = ? ORDER BY created_at DESC",
-    )
-    .bind(&name)
-    .fetch_all(&state.db)
-    .await?;
+    uri: Uri,
+    Query(query): Query<ListQuery>,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<impl IntoResponse, AppError> {
+    list(&state, Some(&name), &uri, &with_meta(query, params)).await
+}
 
-    Ok(Json(rows))
+/// Fill in `query.meta` from the `meta.<key>` parameters among `params`,
+/// which serde can't map onto a field.
+fn with_meta(mut query: ListQuery, params: Vec<(String, String)>) -> ListQuery {
+    query.meta = params
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("meta.")?.to_string(), value)))
+        .collect();
+    query
+}
+
+/// Where a page ends, for resuming after it. `after` holds the last row's
+/// values of the sort columns.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: ListSort,
+    order: SortOrder,
+    after: Vec<Value>,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor serializes");
+        json.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+/// Columns a listing is ordered by, each with whether it is descending.
+/// The id comes last so the order is total and cursors are unambiguous.
+fn sort_columns(sort: ListSort, desc: bool) -> Vec<(&'static str, bool)> {
+    match sort {
+        ListSort::Name => vec![("name", desc), ("created_at", !desc), ("id", desc)],
+        ListSort::Created => vec![("created_at", desc), ("id", desc)],
+        ListSort::Size => vec![("size", desc), ("id", desc)],
+        ListSort::Semver => vec![("name", false), ("version_key", desc), ("id", desc)],
+    }
+}
+
+/// `row`'s values of the sort columns, matching [`sort_columns`].
+fn sort_values(sort: ListSort, row: &ArtifactRow) -> Vec<Value> {
+    let id = Value::from(row.id.clone());
+    match sort {
+        ListSort::Name => vec![row.name.clone().into(), row.created_at.clone().into(), id],
+        ListSort::Created => vec![row.created_at.clone().into(), id],
+        ListSort::Size => vec![row.size.into(), id],
+        ListSort::Semver => vec![
+            row.name.clone().into(),
+            artifacts::version_key(&row.version).into(),
+            id,
+        ],
+    }
+}
+
+/// One page of artifact versions (of `name`, if given), or of names if
+/// `query.grouped`, with a `Link` header pointing at the next page if there
+/// is one.
+async fn list(
+    state: &AppState,
+    name: Option<&str>,
+    uri: &Uri,
+    query: &ListQuery,
+) -> Result<Response, AppError> {
+    if query.grouped {
+        if name.is_some() {
+            return Err(AppError::new(
+                StatusCode::BAD_REQUEST,
+                "grouped listings are only available for all artifacts",
+            ));
+        }
+        return list_grouped(state, uri, query).await;
+    }
+    let bad_request = |msg: &str| AppError::new(StatusCode::BAD_REQUEST, msg);
+
+    let sort = query.sort.unwrap_or(ListSort::Name);
+    let order = query.order.unwrap_or(match sort {
+        ListSort::Name => SortOrder::Asc,
+        _ => SortOrder::Desc,
+    });
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let columns = sort_columns(sort, order == SortOrder::Desc);
+    let cursor = match &query.cursor {
+        Some(c) => {
+            let cursor = Cursor::decode(c).ok_or_else(|| bad_request("invalid cursor"))?;
+            if cursor.sort != sort || cursor.order != order || cursor.after.len() != columns.len() {
+                return Err(bad_request("cursor is for a different sort order"));
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+    for (param, value) in [
+        ("created_after", &query.created_after),
+        ("created_before", &query.created_before),
+    ] {
+        if let Some(value) = value {
+            stats::validate_time(&state.db, param, value).await?;
+        }
+    }
+
+    let mut sql = QueryBuilder::<Sqlite>::new(format!(
+        "SELECT {} FROM artifacts WHERE 1 = 1",
+        artifacts::COLUMNS
+    ));
+    if let Some(name) = name {
+        sql.push(" AND name = ").push_bind(name.to_string());
+    }
+    if let Some(prefix) = &query.prefix {
+        sql.push(" AND substr(name, 1, ")
+            .push_bind(prefix.chars().count() as i64)
+            .push(") = ")
+            .push_bind(prefix.clone());
+    }
+    if let Some(after) = &query.created_after {
+        sql.push(" AND created_at >= datetime(")
+            .push_bind(after.clone())
+            .push(")");
+    }
+    if let Some(before) = &query.created_before {
+        sql.push(" AND created_at < datetime(")
+            .push_bind(before.clone())
+            .push(")");
+    }
+    if let Some(min) = query.min_size {
+        sql.push(" AND size >= ").push_bind(min);
+    }
+    if let Some(max) = query.max_size {
+        sql.push(" AND size <= ").push_bind(max);
+    }
+    match query.yanked {
+        Some(true) => {
+            sql.push(" AND yanked_at IS NOT NULL");
+        }
+        Some(false) => {
+            sql.push(" AND yanked_at IS NULL");
+        }
+        None => {}
+    }
+    if let Some(key) = &query.tagged {
+        sql.push(
+            " AND EXISTS (SELECT 1 FROM artifact_metadata m \
+             WHERE m.artifact_id = artifacts.id AND m.key = ",
+        )
+        .push_bind(key.clone())
+        .push(")");
+    }
+
+    let mut predicates: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (key, value) in &query.meta {
+        if let Some((op, number)) = metadata::comparison(value) {
+            metadata::push_comparison(&mut sql, "artifacts.id", key, op, number);
+        } else {
+            predicates.entry(key).or_default().push(value);
+        }
+    }
+    for (key, values) in predicates {
+        sql.push(
+            " AND EXISTS (SELECT 1 FROM artifact_metadata m \
+             WHERE m.artifact_id = artifacts.id AND m.key = ",
+        )
+        .push_bind(key.to_string())
+        .push(format!(" AND {} IN (", metadata::TEXT_VALUE));
+        let mut list = sql.separated(", ");
+        for value in values {
+            list.push_bind(value.to_string());
+        }
+        sql.push("))");
+    }
+
+    if let Some(cursor) = &cursor {
+        // Rows after the cursor: greater in the first column, or equal in it
+        // and greater in the next, and so on.
+        sql.push(" AND (");
+        for (i, (column, desc)) in columns.iter().enumerate() {
+            if i > 0 {
+                sql.push(" OR ");
+            }
+            sql.push("(");
+            for (equal, value) in columns[..i].iter().zip(&cursor.after) {
+                sql.push(format!("{} = ", equal.0));
+                push_value(&mut sql, value);
+                sql.push(" AND ");
+            }
+            sql.push(format!("{} {} ", column, if *desc { "<" } else { ">" }));
+            push_value(&mut sql, &cursor.after[i]);
+            sql.push(")");
+        }
+        sql.push(")");
+    }
+
+    sql.push(" ORDER BY ");
+    for (i, (column, desc)) in columns.iter().enumerate() {
+        if i > 0 {
+            sql.push(", ");
+        }
+        sql.push(format!("{} {}", column, if *desc { "DESC" } else { "ASC" }));
+    }
+    // One extra row tells whether there is a next page.
+    sql.push(" LIMIT ").push_bind(limit as i64 + 1);
+
+    let mut rows = sql
+        .build_query_as::<ArtifactRow>()
+        .fetch_all(&state.db)
+        .await?;
+
+    let mut headers = HeaderMap::new();
+    if rows.len() > limit as usize {
+        rows.truncate(limit as usize);
+        let last = rows.last().expect("page is not empty");
+        let next = Cursor {
+            sort,
+            order,
+            after: sort_values(sort, last),
+        };
+        headers.insert(header::LINK, next_link(uri, &next));
+    }
+    artifacts::attach_assets(&state.db, &mut rows).await?;
+
+    Ok((headers, Json(rows)).into_response())
+}
+
+/// One page of [`PackageSummary`]s, one per name, in name order.
+async fn list_grouped(
+    state: &AppState,
+    uri: &Uri,
+    query: &ListQuery,
+) -> Result<Response, AppError> {
+    let bad_request = |msg: &str| AppError::new(StatusCode::BAD_REQUEST, msg);
+
+    let version_filters = query.created_after.is_some()
+        || query.created_before.is_some()
+        || query.min_size.is_some()
+        || query.max_size.is_some()
+        || query.yanked.is_some()
+        || query.tagged.is_some()
+        || !query.meta.is_empty();
+    if query.sort.is_some_and(|s| s != ListSort::Name) || version_filters {
+        return Err(bad_request(
+            "grouped listings are sorted by name and can only filter by prefix",
+        ));
+    }
+    let order = query.order.unwrap_or(SortOrder::Asc);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let cursor = match &query.cursor {
+        Some(c) => {
+            let cursor = Cursor::decode(c).ok_or_else(|| bad_request("invalid cursor"))?;
+            if cursor.sort != ListSort::Name || cursor.order != order || cursor.after.len() != 1 {
+                return Err(bad_request("cursor is for a different sort order"));
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+
+    let mut sql = QueryBuilder::<Sqlite>::new(format!("{} WHERE 1 = 1", packages::SUMMARY));
+    if let Some(prefix) = &query.prefix {
+        sql.push(" AND substr(a.name, 1, ")
+            .push_bind(prefix.chars().count() as i64)
+            .push(") = ")
+            .push_bind(prefix.clone());
+    }
+    let (op, dir) = match order {
+        SortOrder::Asc => (">", "ASC"),
+        SortOrder::Desc => ("<", "DESC"),
+    };
+    if let Some(cursor) = &cursor {
+        sql.push(format!(" AND a.name {} ", op));
+        push_value(&mut sql, &cursor.after[0]);
+    }
+    sql.push(format!(" GROUP BY a.name ORDER BY a.name {} LIMIT ", dir))
+        .push_bind(limit as i64 + 1);
+
+    let mut rows = sql
+        .build_query_as::<PackageSummary>()
+        .fetch_all(&state.db)
+        .await?;
+
+    let mut headers = HeaderMap::new();
+    if rows.len() > limit as usize {
+        rows.truncate(limit as usize);
+        let last = rows.last().expect("page is not empty");
+        let next = Cursor {
+            sort: ListSort::Name,
+            order,
+            after: vec![last.name.clone().into()],
+        };
+        headers.insert(header::LINK, next_link(uri, &next));
+    }
+
+    Ok((headers, Json(rows)).into_response())
+}
+
+/// `Link` header value pointing at the page after `cursor`, keeping the
+/// other query parameters of `uri`.
+fn next_link(uri: &Uri, cursor: &Cursor) -> HeaderValue {
+    let mut params: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|p| !p.is_empty() && !p.starts_with("cursor="))
+        .collect();
+    let cursor = format!("cursor={}", cursor.encode());
+    params.push(&cursor);
+    let link = format!("<{}?{}>; rel=\"next\"", uri.path(), params.join("&"));
+    link.parse().expect("link is a valid header value")
+}
+
+fn push_value(sql: &mut QueryBuilder<'_, Sqlite>, value: &Value) {
+    match value {
+        Value::Number(n) => sql.push_bind(n.as_i64()),
+        Value::String(s) => sql.push_bind(s.clone()),
+        _ => sql.push_bind(None::<String>),
+    };
 }
 
 async fn upload(
     State(state): State<AppState>,
-    _auth: RequireToken,
+    auth: RequireToken,
     Path((name, version)): Path<(String, String)>,
     Query(params): Query<UploadParams>,
-    body: Bytes,
+    headers: HeaderMap,
+    body: Body,
 ) -> Result<impl IntoResponse, AppError> {
-    if body.len() > state.max_upload_size {
-        return Err(AppError::payload_too_large(format!(
-            "upload size {} exceeds maximum {}",
-            body.len(),
-            state.max_upload_size
-        )));
-    }
+    // Check what we can from the declared length before reading the body
+    let declared = declared_length(&state, &headers)?;
 
     // Check for duplicate
     let existing = sqlx::query("SELECT id FROM artifacts WHERE name = ? AND version = ?")
@@ -95,111 +417,506 @@ async fn upload(
         )));
     }
 
-    let filename = params
-        .filename
-        .unwrap_or_else(|| format!("{}-{}", name, version));
+    check_quota(
+        &mut *state.db.acquire().await?,
+        &name,
+        &auth.token_id,
+        declared.unwrap_or(0),
+        true,
+    )
+    .await?;
+    check_free_space(&state, declared.unwrap_or(0))?;
+
+    let mut metadata = header_metadata(&headers)?;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let (body, part_filename) = if content_type.starts_with("multipart/form-data") {
+        let form = read_form(content_type, body, state.max_upload_size).await?;
+        // As in a merge patch, null means no value.
+        metadata.extend(form.metadata.into_iter().filter(|(_, v)| !v.is_null()));
+        (form.file, form.filename)
+    } else {
+        let body = axum::body::to_bytes(body, state.max_upload_size)
+            .await
+            .map_err(|_| {
+                AppError::payload_too_large(format!(
+                    "upload exceeds maximum size {}",
+                    state.max_upload_size
+                ))
+            })?;
+        (body, None)
+    };
+    // Even without metadata, so schemas with required keys apply.
+    metadata::enforce_schemas(&state.db, &state, &name, None, &metadata).await?;
+
+    // The body may be larger than declared, or have had no declared length
+    if declared != Some(body.len() as u64) {
+        check_quota(
+            &mut *state.db.acquire().await?,
+            &name,
+            &auth.token_id,
+            body.len() as u64,
+            true,
+        )
+        .await?;
+    }
+
+    let filename = params.filename.or(part_filename);
+    if let Some(filename) = &filename {
+        check_filename(filename)?;
+    }
+    let filename = filename.unwrap_or_else(|| format!("{}-{}", name, version));
+    let file = store(&state, &body).await?;
+
+    // The version, its primary asset and its metadata appear together or
+    // not at all, and the file stops being pending with them.
+    let inserted: Result<ArtifactRow, AppError> = async {
+        let mut tx = db::begin_write(&state.db).await?;
+        check_quota(&mut tx, &name, &auth.token_id, file.size as u64, true).await?;
+        insert_artifact(&mut tx, &name, &version, &filename, &file, &auth.token_id)
+            .await
+            .map_err(|e| match &e {
+                // Another upload of the same version got past the check above.
+                sqlx::Error::Database(db) if db.is_unique_violation() => {
+                    AppError::conflict(format!("artifact {}/{} already exists", name, version))
+                }
+                _ => e.into(),
+            })?;
+        insert_asset(&mut tx, &file, &file.id, &filename, &auth.token_id).await?;
+        metadata::apply(&mut tx, &file.id, &auth.token_id, &Map::new(), &metadata).await?;
+        artifacts::remove_pending(&mut *tx, &file.id).await?;
+
+        let artifact = sqlx::query_as::<_, ArtifactRow>(&format!(
+            "SELECT {} FROM artifacts WHERE id = ?",
+            artifacts::COLUMNS
+        ))
+        .bind(&file.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(artifact)
+    }
+    .await;
+
+    match inserted {
+        Ok(mut artifact) => {
+            artifacts::attach_assets(&state.db, std::slice::from_mut(&mut artifact)).await?;
+            Ok((StatusCode::CREATED, Json(artifact)))
+        }
+        Err(e) => {
+            discard(&state, &file).await?;
+            Err(e)
+        }
+    }
+}
+
+/// An uploaded file saved under a new asset ID and marked pending, until
+/// its row is committed or it is discarded.
+pub(super) struct StoredFile {
+    pub id: String,
+    pub sha256: String,
+    pub size: i64,
+}
+
+/// Save `body` as a new pending file, checking there's room for it first.
+pub(super) async fn store(state: &AppState, body: &Bytes) -> Result<StoredFile, AppError> {
     let id = Uuid::new_v4().to_string();
-    let hash_bytes = Sha256::digest(&body);
+    let hash_bytes = Sha256::digest(body);
     let sha256: String = hash_bytes.iter().map(|b| format!("{:02x}", b)).collect();
-    let size = body.len() as i64;
 
-    storage::save(&state.data_dir, &id, &body)
-        .await
-        .map_err(anyhow::Error::from)?;
+    check_free_space(state, body.len() as u64)?;
+    artifacts::add_pending(&state.db, &id).await?;
+    if let Err(e) = storage::save(&state.data_dir, &id, body).await {
+        artifacts::remove_pending(&state.db, &id).await?;
+        if storage::is_out_of_space(&e) {
+            tracing::error!("{:#}", e);
+            return Err(AppError::insufficient_storage(
+                "server is out of disk space",
+            ));
+        }
+        return Err(e.into());
+    }
+    Ok(StoredFile {
+        id,
+        sha256,
+        size: body.len() as i64,
+    })
+}
+
+/// Delete a stored file whose row wasn't committed. It's left pending for
+/// the next startup if this fails too.
+pub(super) async fn discard(state: &AppState, file: &StoredFile) -> Result<(), AppError> {
+    if let Err(e) = storage::delete(&state.data_dir, &file.id).await {
+        tracing::warn!("{:#}", e);
+    } else {
+        artifacts::remove_pending(&state.db, &file.id).await?;
+    }
+    Ok(())
+}
 
+/// Insert a version whose primary asset is `file`, taking the file's ID.
+pub(super) async fn insert_artifact(
+    tx: &mut Transaction<'_, Sqlite>,
+    name: &str,
+    version: &str,
+    filename: &str,
+    file: &StoredFile,
+    uploaded_by: &str,
+) -> sqlx::Result<()> {
     sqlx::query(
-        "INSERT INTO artifacts (id, name, version, filename, sha256, size) \
-         VALUES (?, ?, ?, ?, ?, ?)",
+        "INSERT INTO artifacts \
+         (id, name, version, version_key, filename, sha256, size, uploaded_by) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
     )
-    .bind(&id)
-    .bind(&name)
-    .bind(&version)
-    .bind(&filename)
-    .bind(&sha256)
-    .bind(size)
-    .execute(&state.db)
+    .bind(&file.id)
+    .bind(name)
+    .bind(version)
+    .bind(artifacts::version_key(version))
+    .bind(filename)
+    .bind(&file.sha256)
+    .bind(file.size)
+    .bind(uploaded_by)
+    .execute(&mut **tx)
     .await?;
+    Ok(())
+}
 
-    let artifact = sqlx::query_as::<_, ArtifactRow>(
-        "SELECT id, name, version, filename, sha256, size, created_at \
-         FROM artifacts WHERE id = ?",
+/// Insert `file`'s row as asset `filename` of `artifact_id`.
+pub(super) async fn insert_asset(
+    tx: &mut Transaction<'_, Sqlite>,
+    file: &StoredFile,
+    artifact_id: &str,
+    filename: &str,
+    uploaded_by: &str,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO assets (id, artifact_id, filename, sha256, size, uploaded_by) \
+         VALUES (?, ?, ?, ?, ?, ?)",
     )
-    .bind(&id)
-    .fetch_one(&state.db)
+    .bind(&file.id)
+    .bind(artifact_id)
+    .bind(filename)
+    .bind(&file.sha256)
+    .bind(file.size)
+    .bind(uploaded_by)
+    .execute(&mut **tx)
     .await?;
+    Ok(())
+}
+
+const META_HEADER_PREFIX: &str = "x-cask-meta-";
+
+/// Metadata given as `X-Cask-Meta-<key>: <value>` headers. Header names
+/// are case-insensitive, so keys are lowercase; values are strings, and
+/// aren't converted even where a schema types the key, since `1.10` may as
+/// well be a version as a number. Typed values go in a multipart upload.
+fn header_metadata(headers: &HeaderMap) -> Result<Map<String, Value>, AppError> {
+    let mut metadata = Map::new();
+    for (name, value) in headers {
+        let Some(key) = name.as_str().strip_prefix(META_HEADER_PREFIX) else {
+            continue;
+        };
+        if key.is_empty() {
+            return Err(AppError::new(
+                StatusCode::BAD_REQUEST,
+                format!("header {} has no key", name),
+            ));
+        }
+        let value = value.to_str().map_err(|_| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                format!("header {} is not valid ASCII", name),
+            )
+        })?;
+        metadata.insert(key.to_string(), Value::String(value.to_string()));
+    }
+    Ok(metadata)
+}
+
+/// `400` for a filename that can't be served in a `Content-Disposition`
+/// header or that looks like a path.
+pub(super) fn check_filename(filename: &str) -> Result<(), AppError> {
+    if filename.is_empty()
+        || filename
+            .chars()
+            .any(|c| c.is_control() || c == '/' || c == '"')
+    {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!("invalid filename {:?}", filename),
+        ));
+    }
+    Ok(())
+}
+
+/// Largest accepted `metadata` part of a multipart upload.
+const MAX_FORM_METADATA: u64 = 1024 * 1024;
+
+struct UploadForm {
+    file: Bytes,
+    filename: Option<String>,
+    metadata: Map<String, Value>,
+}
+
+/// Read a `multipart/form-data` upload: a `file` part with the contents,
+/// whose filename is used unless `?filename=` is given, and an optional
+/// `metadata` part holding a JSON object.
+async fn read_form(content_type: &str, body: Body, limit: usize) -> Result<UploadForm, AppError> {
+    let bad_request = |message: String| AppError::new(StatusCode::BAD_REQUEST, message);
+    let form_error = |e: multer::Error| match e {
+        multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+            AppError::payload_too_large(format!("upload exceeds maximum size {}", limit))
+        }
+        e => AppError::new(StatusCode::BAD_REQUEST, format!("invalid form: {}", e)),
+    };
+
+    let boundary = multer::parse_boundary(content_type).map_err(form_error)?;
+    let constraints = multer::Constraints::new()
+        .allowed_fields(vec!["file", "metadata"])
+        .size_limit(
+            multer::SizeLimit::new()
+                .for_field("file", limit as u64)
+                .for_field("metadata", MAX_FORM_METADATA),
+        );
+    let mut form =
+        multer::Multipart::with_constraints(body.into_data_stream(), boundary, constraints);
+
+    let mut file = None;
+    let mut filename = None;
+    let mut metadata = None;
+    while let Some(field) = form.next_field().await.map_err(form_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "file" && file.is_none() {
+            filename = field.file_name().map(str::to_string);
+            file = Some(field.bytes().await.map_err(form_error)?);
+        } else if name == "metadata" && metadata.is_none() {
+            let bytes = field.bytes().await.map_err(form_error)?;
+            match serde_json::from_slice(&bytes) {
+                Ok(Value::Object(object)) => metadata = Some(object),
+                _ => return Err(bad_request("metadata part must be a JSON object".into())),
+            }
+        } else {
+            return Err(bad_request(format!("duplicate form part {:?}", name)));
+        }
+    }
+
+    Ok(UploadForm {
+        file: file.ok_or_else(|| bad_request("form has no file part".into()))?,
+        filename,
+        metadata: metadata.unwrap_or_default(),
+    })
+}
 
-    Ok((axum::http::StatusCode::CREATED, Json(artifact)))
+/// The upload's `Content-Length`, if it declares one, refusing it if that is
+/// over the maximum upload size.
+pub(super) fn declared_length(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Option<u64>, AppError> {
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if let Some(len) = declared
+        && len > state.max_upload_size as u64
+    {
+        return Err(AppError::payload_too_large(format!(
+            "upload size {} exceeds maximum {}",
+            len, state.max_upload_size
+        )));
+    }
+    Ok(declared)
+}
+
+/// Refuse an upload of `size` bytes that would go over a quota. Uploads
+/// check before storing the file, to fail early, and again in the
+/// transaction inserting it, which is what keeps concurrent uploads within
+/// the quota.
+pub(super) async fn check_quota(
+    conn: &mut SqliteConnection,
+    name: &str,
+    token_id: &str,
+    size: u64,
+    new_version: bool,
+) -> Result<(), AppError> {
+    match quota::check(conn, name, token_id, size, new_version).await? {
+        Some(message) => Err(AppError::quota_exceeded(message)),
+        None => Ok(()),
+    }
+}
+
+pub(super) fn check_free_space(state: &AppState, size: u64) -> Result<(), AppError> {
+    if state.min_free_space == 0 {
+        return Ok(());
+    }
+    let free = storage::free_space(&state.data_dir)?;
+    if free.saturating_sub(size) < state.min_free_space {
+        tracing::warn!(
+            "refusing upload of {} bytes: {} bytes free, minimum is {}",
+            size,
+            free,
+            state.min_free_space
+        );
+        return Err(AppError::insufficient_storage(
+            "server is low on disk space; uploads are disabled",
+        ));
+    }
+    Ok(())
 }
 
 async fn download(
     State(state): State<AppState>,
     Path((name, version)): Path<(String, String)>,
     ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
+    request_headers: HeaderMap,
 ) -> Result<impl IntoResponse, AppError> {
-    let artifact = sqlx::query_as::<_, ArtifactRow>(
-        "SELECT id, name, version, filename, sha256, size, created_at \
-         FROM artifacts WHERE name = ? AND version = ?",
-    )
+    let artifact = sqlx::query_as::<_, ArtifactRow>(&format!(
+        "SELECT {} FROM artifacts WHERE name = ? AND version = ?",
+        artifacts::COLUMNS
+    ))
     .bind(&name)
     .bind(&version)
     .fetch_optional(&state.db)
     .await?
     .ok_or_else(|| AppError::not_found(format!("artifact {}/{} not found", name, version)))?;
 
-    // Record download stat
-    let stat_id = Uuid::new_v4().to_string();
-    let ip = addr.ip().to_string();
-    let _ = sqlx::query("INSERT INTO download_stats (id, artifact_id, ip) VALUES (?, ?, ?)")
-        .bind(&stat_id)
-        .bind(&artifact.id)
-        .bind(&ip)
-        .execute(&state.db)
-        .await;
+    serve(
+        &state,
+        &artifact,
+        &artifact.id,
+        &artifact.filename,
+        addr,
+        &request_headers,
+    )
+    .await
+}
+
+/// Serve one asset of `artifact`, counting the download, unless fsck or
+/// the scrubber found its file missing or corrupt.
+pub(super) async fn serve(
+    state: &AppState,
+    artifact: &ArtifactRow,
+    asset_id: &str,
+    filename: &str,
+    addr: std::net::SocketAddr,
+    request_headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    let (sha256, broken, broken_reason) = sqlx::query_as::<_, (String, bool, Option<String>)>(
+        "SELECT sha256, broken_at IS NOT NULL, broken_reason FROM assets WHERE id = ?",
+    )
+    .bind(asset_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| {
+        AppError::not_found(format!(
+            "{}/{} has no file {}",
+            artifact.name, artifact.version, filename
+        ))
+    })?;
+
+    if broken {
+        return Err(AppError::gone(format!(
+            "{} of {}/{} is damaged on disk and cannot be served: {}",
+            filename,
+            artifact.name,
+            artifact.version,
+            broken_reason.unwrap_or_default()
+        )));
+    }
+
+    let ip = state.ip_policy.client_ip(addr.ip(), request_headers);
+    state.recorder.record(
+        &artifact.id,
+        asset_id,
+        state.ip_policy.stored(&state.db, ip).await,
+        ClientInfo::from_headers(request_headers),
+    );
 
-    let bytes = storage::load(&state.data_dir, &artifact.id)
-        .await
-        .map_err(anyhow::Error::from)?;
+    let bytes = storage::load(&state.data_dir, asset_id).await?;
 
     let mut headers = HeaderMap::new();
     headers.insert(
         header::CONTENT_TYPE,
         "application/octet-stream".parse().unwrap(),
     );
-    headers.insert(
-        header::CONTENT_DISPOSITION,
-        format!("attachment; filename=\"{}\"", artifact.filename)
-            .parse()
-            .unwrap(),
-    );
+    // Lets clients verify the body against this very file, rather than
+    // against a version that may have been replaced since.
+    headers.insert(SHA256_HEADER, HeaderValue::from_str(&sha256).unwrap());
+    // Filenames stored before they were checked may not fit in a header.
+    if let Ok(value) = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename)) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
 
-    Ok((headers, bytes))
+    Ok((headers, bytes).into_response())
 }
 
 async fn delete_artifact(
     State(state): State<AppState>,
-    _auth: RequireToken,
+    auth: RequireToken,
     Path((name, version)): Path<(String, String)>,
 ) -> Result<impl IntoResponse, AppError> {
-    let artifact = sqlx::query_as::<_, ArtifactRow>(
-        "SELECT id, name, version, filename, sha256, size, created_at \
-         FROM artifacts WHERE name = ? AND version = ?",
-    )
+    let artifact = sqlx::query_as::<_, ArtifactRow>(&format!(
+        "SELECT {} FROM artifacts WHERE name = ? AND version = ?",
+        artifacts::COLUMNS
+    ))
     .bind(&name)
     .bind(&version)
     .fetch_optional(&state.db)
     .await?
     .ok_or_else(|| AppError::not_found(format!("artifact {}/{} not found", name, version)))?;
 
-    // Delete from DB (cascades to metadata + stats)
-    sqlx::query("DELETE FROM artifacts WHERE id = ?")
-        .bind(&artifact.id)
-        .execute(&state.db)
-        .await?;
+    if !artifacts::delete(&state, &artifact, &auth.token_id, None).await? {
+        return Err(AppError::not_found(format!(
+            "artifact {}/{} not found",
+            name, version
+        )));
+    }
 
-    // Delete file from disk
-    storage::delete(&state.data_dir, &artifact.id)
-        .await
-        .map_err(anyhow::Error::from)?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    Ok(axum::http::StatusCode::NO_CONTENT)
+async fn yank(
+    State(state): State<AppState>,
+    auth: RequireToken,
+    Path((name, version)): Path<(String, String)>,
+) -> Result<Json<ArtifactRow>, AppError> {
+    set_yanked(&state, &auth.token_id, &name, &version, true).await
+}
+
+async fn unyank(
+    State(state): State<AppState>,
+    auth: RequireToken,
+    Path((name, version)): Path<(String, String)>,
+) -> Result<Json<ArtifactRow>, AppError> {
+    set_yanked(&state, &auth.token_id, &name, &version, false).await
+}
+
+/// Mark a version yanked (keeping the original time if it already was) or
+/// not yanked, and record who did it in the audit log.
+async fn set_yanked(
+    state: &AppState,
+    actor: &str,
+    name: &str,
+    version: &str,
+    yanked: bool,
+) -> Result<Json<ArtifactRow>, AppError> {
+    let mut tx = db::begin_write(&state.db).await?;
+    let artifact = sqlx::query_as::<_, ArtifactRow>(&format!(
+        "UPDATE artifacts SET yanked_at = \
+         CASE WHEN ? THEN COALESCE(yanked_at, datetime('now')) END \
+         WHERE name = ? AND version = ? RETURNING {}",
+        artifacts::COLUMNS
+    ))
+    .bind(yanked)
+    .bind(name)
+    .bind(version)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::not_found(format!("artifact {}/{} not found", name, version)))?;
+    let action = if yanked { "yank" } else { "unyank" };
+    audit::record(&mut *tx, actor, action, Some((name, version)), None).await?;
+    tx.commit().await?;
+    Ok(Json(artifact))
 }
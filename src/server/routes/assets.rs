@@ -0,0 +1,215 @@
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::{Json, Router, routing::get};
+use cask_types::{ArtifactRow, Asset};
+use serde_json::Map;
+use sqlx::SqliteExecutor;
+
+use super::artifacts::{
+    check_filename, check_free_space, check_quota, declared_length, discard, insert_artifact,
+    insert_asset, serve, store,
+};
+use super::metadata;
+use crate::artifacts;
+use crate::auth::RequireToken;
+use crate::db;
+use crate::error::AppError;
+use crate::state::AppState;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/v1/artifacts/{name}/{version}/files", get(list_assets))
+        .route(
+            "/v1/artifacts/{name}/{version}/files/{filename}",
+            get(download_asset).put(upload_asset).delete(delete_asset),
+        )
+}
+
+async fn list_assets(
+    State(state): State<AppState>,
+    Path((name, version)): Path<(String, String)>,
+) -> Result<Json<Vec<Asset>>, AppError> {
+    let mut artifact = lookup_artifact(&state.db, &name, &version)
+        .await?
+        .ok_or_else(|| not_found(&name, &version))?;
+    artifacts::attach_assets(&state.db, std::slice::from_mut(&mut artifact)).await?;
+    Ok(Json(artifact.assets))
+}
+
+/// Add a file to a version, creating the version with it as the primary
+/// file if it doesn't exist yet. Files can't be replaced; delete one first.
+async fn upload_asset(
+    State(state): State<AppState>,
+    auth: RequireToken,
+    Path((name, version, filename)): Path<(String, String, String)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, AppError> {
+    check_filename(&filename)?;
+
+    // Check what we can from the declared length before reading the body
+    let declared = declared_length(&state, &headers)?;
+    let exists = lookup_artifact(&state.db, &name, &version).await?.is_some();
+    check_quota(
+        &mut *state.db.acquire().await?,
+        &name,
+        &auth.token_id,
+        declared.unwrap_or(0),
+        !exists,
+    )
+    .await?;
+    check_free_space(&state, declared.unwrap_or(0))?;
+    // A new version has no metadata, which schemas with required keys reject.
+    if !exists {
+        metadata::enforce_schemas(&state.db, &state, &name, None, &Map::new()).await?;
+    }
+
+    let body = axum::body::to_bytes(body, state.max_upload_size)
+        .await
+        .map_err(|_| {
+            AppError::payload_too_large(format!(
+                "upload exceeds maximum size {}",
+                state.max_upload_size
+            ))
+        })?;
+    // The body may be larger than declared, or have had no declared length
+    if declared != Some(body.len() as u64) {
+        check_quota(
+            &mut *state.db.acquire().await?,
+            &name,
+            &auth.token_id,
+            body.len() as u64,
+            !exists,
+        )
+        .await?;
+    }
+
+    let file = store(&state, &body).await?;
+
+    let inserted: Result<Asset, AppError> = async {
+        let mut tx = db::begin_write(&state.db).await?;
+        let existing = lookup_artifact(&mut *tx, &name, &version).await?;
+        check_quota(
+            &mut tx,
+            &name,
+            &auth.token_id,
+            file.size as u64,
+            existing.is_none(),
+        )
+        .await?;
+        let artifact_id = match existing {
+            Some(artifact) => artifact.id,
+            None => {
+                insert_artifact(&mut tx, &name, &version, &filename, &file, &auth.token_id).await?;
+                file.id.clone()
+            }
+        };
+        insert_asset(&mut tx, &file, &artifact_id, &filename, &auth.token_id)
+            .await
+            .map_err(|e| match &e {
+                sqlx::Error::Database(db) if db.is_unique_violation() => AppError::conflict(
+                    format!("{}/{} already has a file {}", name, version, filename),
+                ),
+                _ => e.into(),
+            })?;
+        artifacts::remove_pending(&mut *tx, &file.id).await?;
+
+        let asset = sqlx::query_as::<_, Asset>(&format!(
+            "SELECT {} FROM assets WHERE id = ?",
+            artifacts::ASSET_COLUMNS
+        ))
+        .bind(&file.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(asset)
+    }
+    .await;
+
+    match inserted {
+        Ok(asset) => Ok((StatusCode::CREATED, Json(asset))),
+        Err(e) => {
+            discard(&state, &file).await?;
+            Err(e)
+        }
+    }
+}
+
+async fn download_asset(
+    State(state): State<AppState>,
+    Path((name, version, filename)): Path<(String, String, String)>,
+    ConnectInfo(addr): ConnectInfo<std::net::SocketAddr>,
+    request_headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let (artifact, asset_id) = lookup_asset(&state, &name, &version, &filename).await?;
+    serve(
+        &state,
+        &artifact,
+        &asset_id,
+        &filename,
+        addr,
+        &request_headers,
+    )
+    .await
+}
+
+/// Delete a file other than the primary one, which goes with its version.
+async fn delete_asset(
+    State(state): State<AppState>,
+    auth: RequireToken,
+    Path((name, version, filename)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    let (artifact, asset_id) = lookup_asset(&state, &name, &version, &filename).await?;
+    if asset_id == artifact.id {
+        return Err(AppError::conflict(format!(
+            "{} is the primary file of {}/{}; delete the version instead",
+            filename, name, version
+        )));
+    }
+
+    artifacts::delete_asset(&state, &artifact, &asset_id, &filename, &auth.token_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn lookup_artifact(
+    db: impl SqliteExecutor<'_>,
+    name: &str,
+    version: &str,
+) -> Result<Option<ArtifactRow>, AppError> {
+    Ok(sqlx::query_as::<_, ArtifactRow>(&format!(
+        "SELECT {} FROM artifacts WHERE name = ? AND version = ?",
+        artifacts::COLUMNS
+    ))
+    .bind(name)
+    .bind(version)
+    .fetch_optional(db)
+    .await?)
+}
+
+/// The version and the ID of its asset named `filename`.
+async fn lookup_asset(
+    state: &AppState,
+    name: &str,
+    version: &str,
+    filename: &str,
+) -> Result<(ArtifactRow, String), AppError> {
+    let artifact = lookup_artifact(&state.db, name, version)
+        .await?
+        .ok_or_else(|| not_found(name, version))?;
+    let asset_id = sqlx::query_scalar::<_, String>(
+        "SELECT id FROM assets WHERE artifact_id = ? AND filename = ?",
+    )
+    .bind(&artifact.id)
+    .bind(filename)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::not_found(format!("{}/{} has no file {}", name, version, filename)))?;
+    Ok((artifact, asset_id))
+}
+
+fn not_found(name: &str, version: &str) -> AppError {
+    AppError::not_found(format!("artifact {}/{} not found", name, version))
+}
@@ -1,41 +1,81 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::{Json, Router, routing::{delete, get}};
-use serde::Serialize;
-use sqlx::Row;
+use axum::{
+    Json, Router,
+    routing::{delete, get},
+};
+use cask_types::{Metadata, MetadataChange};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteExecutor, Transaction};
 
 use crate::auth::RequireToken;
+use crate::db;
 use crate::error::AppError;
+use crate::schema;
 use crate::state::AppState;
 
-#[derive(Serialize)]
-struct BaseMetadata {
-    sha256: String,
-    created_at: String,
-    #[serde(flatten)]
-    custom: HashMap<String, String>,
+/// A metadata value as text: strings unquoted, anything else as JSON. Used to
+/// match `meta.<key>=<value>` filters against values of any type.
+pub(super) const TEXT_VALUE: &str =
+    "CASE json_type(m.value) WHEN 'text' THEN m.value ->> '$' ELSE m.value END";
+
+/// A value of the form `>N`, `>=N`, `<N` or `<=N` with a number `N`, as an
+/// SQL operator and the number.
+pub(super) fn comparison(value: &str) -> Option<(&'static str, f64)> {
+    let (op, rest) = [">=", "<=", ">", "<"]
+        .into_iter()
+        .find_map(|op| Some((op, value.strip_prefix(op)?)))?;
+    let number: f64 = rest.trim().parse().ok()?;
+    number.is_finite().then_some((op, number))
+}
+
+/// Require the artifact whose id is in `id_column` to have a numeric `key`
+/// for which `value <op> number` holds.
+pub(super) fn push_comparison(
+    sql: &mut QueryBuilder<'_, Sqlite>,
+    id_column: &str,
+    key: &str,
+    op: &str,
+    number: f64,
+) {
+    sql.push(format!(
+        " AND EXISTS (SELECT 1 FROM artifact_metadata m WHERE m.artifact_id = {} AND m.key = ",
+        id_column
+    ))
+    .push_bind(key.to_string())
+    .push(format!(
+        " AND json_type(m.value) IN ('integer', 'real') AND m.value ->> '$' {} ",
+        op
+    ))
+    .push_bind(number)
+    .push(")");
 }
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route(
             "/v1/artifacts/{name}/{version}/meta",
-            get(get_metadata).put(set_metadata),
+            get(get_metadata).put(set_metadata).patch(patch_metadata),
         )
         .route(
             "/v1/artifacts/{name}/{version}/meta/{key}",
             delete(delete_metadata),
         )
+        .route(
+            "/v1/artifacts/{name}/{version}/history",
+            get(metadata_history),
+        )
 }
 
 async fn get_metadata(
     State(state): State<AppState>,
     Path((name, version)): Path<(String, String)>,
-) -> Result<Json<BaseMetadata>, AppError> {
-    let artifact_id = lookup_artifact_id(&state, &name, &version).await?;
+) -> Result<Json<Metadata>, AppError> {
+    let artifact_id = lookup_artifact_id(&state.db, &name, &version).await?;
 
     let base_row = sqlx::query("SELECT sha256, created_at FROM artifacts WHERE id = ?")
         .bind(&artifact_id)
@@ -45,75 +85,278 @@ async fn get_metadata(
     let sha256: String = base_row.get("sha256");
     let created_at: String = base_row.get("created_at");
 
-    let rows = sqlx::query("SELECT key, value FROM artifact_metadata WHERE artifact_id = ?")
-        .bind(&artifact_id)
-        .fetch_all(&state.db)
-        .await?;
-
-    let custom: HashMap<String, String> = rows
-        .iter()
-        .map(|r| (r.get("key"), r.get("value")))
-        .collect();
+    let custom: BTreeMap<String, Value> =
+        custom(&state.db, &artifact_id).await?.into_iter().collect();
 
-    Ok(Json(BaseMetadata {
+    Ok(Json(Metadata {
         sha256,
         created_at,
         custom,
     }))
 }
 
+/// Set the given keys, leaving others alone. A `null` value removes its key,
+/// as in a merge patch, rather than being stored.
 async fn set_metadata(
     State(state): State<AppState>,
-    _auth: RequireToken,
+    auth: RequireToken,
     Path((name, version)): Path<(String, String)>,
-    Json(body): Json<HashMap<String, String>>,
+    Json(body): Json<HashMap<String, Value>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let artifact_id = lookup_artifact_id(&state, &name, &version).await?;
+    let mut tx = db::begin_write(&state.db).await?;
+    let artifact_id = lookup_artifact_id(&mut *tx, &name, &version).await?;
+    let before = custom(&mut *tx, &artifact_id).await?;
+    let mut after = before.clone();
+    for (key, value) in body {
+        if value.is_null() {
+            after.remove(&key);
+        } else {
+            after.insert(key, value);
+        }
+    }
+    enforce_schemas(&mut *tx, &state, &name, Some(&before), &after).await?;
+    apply(&mut tx, &artifact_id, &auth.token_id, &before, &after).await?;
+    tx.commit().await?;
 
-    for (key, value) in &body {
-        sqlx::query(
-            "INSERT OR REPLACE INTO artifact_metadata (artifact_id, key, value) \
-             VALUES (?, ?, ?)",
-        )
-        .bind(&artifact_id)
-        .bind(key)
-        .bind(value)
-        .execute(&state.db)
-        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Apply a JSON merge patch (RFC 7396) to the custom keys: `null` removes a
+/// key, objects merge into existing objects, and anything else replaces.
+async fn patch_metadata(
+    State(state): State<AppState>,
+    auth: RequireToken,
+    Path((name, version)): Path<(String, String)>,
+    Json(patch): Json<Value>,
+) -> Result<impl IntoResponse, AppError> {
+    if !patch.is_object() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "merge patch must be a JSON object",
+        ));
     }
+    let mut tx = db::begin_write(&state.db).await?;
+    let artifact_id = lookup_artifact_id(&mut *tx, &name, &version).await?;
+    let before = custom(&mut *tx, &artifact_id).await?;
+    let mut after = Value::Object(before.clone());
+    merge_patch(&mut after, patch);
+    let Value::Object(after) = after else {
+        unreachable!()
+    };
+    enforce_schemas(&mut *tx, &state, &name, Some(&before), &after).await?;
+    apply(&mut tx, &artifact_id, &auth.token_id, &before, &after).await?;
+    tx.commit().await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn delete_metadata(
     State(state): State<AppState>,
-    _auth: RequireToken,
+    auth: RequireToken,
     Path((name, version, key)): Path<(String, String, String)>,
 ) -> Result<impl IntoResponse, AppError> {
-    let artifact_id = lookup_artifact_id(&state, &name, &version).await?;
+    let mut tx = db::begin_write(&state.db).await?;
+    let artifact_id = lookup_artifact_id(&mut *tx, &name, &version).await?;
+    let before = custom(&mut *tx, &artifact_id).await?;
+    let mut after = before.clone();
+    if after.remove(&key).is_some() {
+        enforce_schemas(&mut *tx, &state, &name, Some(&before), &after).await?;
+        apply(&mut tx, &artifact_id, &auth.token_id, &before, &after).await?;
+    }
+    tx.commit().await?;
 
-    sqlx::query("DELETE FROM artifact_metadata WHERE artifact_id = ? AND key = ?")
-        .bind(&artifact_id)
-        .bind(&key)
-        .execute(&state.db)
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct HistoryParams {
+    key: Option<String>,
+    #[serde(default = "default_history_limit")]
+    limit: i64,
+}
+
+fn default_history_limit() -> i64 {
+    100
+}
+
+/// Most history entries returned at once.
+const MAX_HISTORY_LIMIT: i64 = 1000;
+
+/// Changes to the custom keys, newest first. The history of a deleted
+/// version is kept, and is listed along with that of a version uploaded
+/// again under the same name. Public, like the metadata itself.
+async fn metadata_history(
+    State(state): State<AppState>,
+    Path((name, version)): Path<(String, String)>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<MetadataChange>>, AppError> {
+    let mut sql = QueryBuilder::<Sqlite>::new(
+        "SELECT id, at, actor, key, old_value, new_value \
+         FROM metadata_history WHERE name = ",
+    );
+    sql.push_bind(&name)
+        .push(" AND version = ")
+        .push_bind(&version);
+    if let Some(key) = &params.key {
+        sql.push(" AND key = ").push_bind(key);
+    }
+    sql.push(" ORDER BY id DESC LIMIT ")
+        .push_bind(params.limit.clamp(1, MAX_HISTORY_LIMIT));
+
+    let rows = sql
+        .build_query_as::<(i64, String, String, String, Option<String>, Option<String>)>()
+        .fetch_all(&state.db)
         .await?;
+    if rows.is_empty() {
+        // Not found unless the version exists with no history yet.
+        lookup_artifact_id(&state.db, &name, &version).await?;
+    }
 
-    Ok(StatusCode::NO_CONTENT)
+    Ok(Json(
+        rows.into_iter()
+            .map(
+                |(id, at, actor, key, old_value, new_value)| MetadataChange {
+                    id,
+                    at,
+                    actor,
+                    key,
+                    old_value: old_value.map(decode),
+                    new_value: new_value.map(decode),
+                },
+            )
+            .collect(),
+    ))
 }
 
-async fn lookup_artifact_id(
+/// Change the custom keys of an artifact from `before` to `after`, recording
+/// each key that changes in its history as done by `actor`.
+pub(super) async fn apply(
+    tx: &mut Transaction<'_, Sqlite>,
+    artifact_id: &str,
+    actor: &str,
+    before: &Map<String, Value>,
+    after: &Map<String, Value>,
+) -> Result<(), AppError> {
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        let old_value = before.get(key);
+        let new_value = after.get(key);
+        if old_value == new_value {
+            continue;
+        }
+
+        match new_value {
+            Some(value) => {
+                sqlx::query(
+                    "INSERT OR REPLACE INTO artifact_metadata (artifact_id, key, value) \
+                     VALUES (?, ?, ?)",
+                )
+                .bind(artifact_id)
+                .bind(key)
+                .bind(value.to_string())
+                .execute(&mut **tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM artifact_metadata WHERE artifact_id = ? AND key = ?")
+                    .bind(artifact_id)
+                    .bind(key)
+                    .execute(&mut **tx)
+                    .await?;
+            }
+        }
+
+        sqlx::query(
+            "INSERT INTO metadata_history \
+             (artifact_id, name, version, key, old_value, new_value, actor) \
+             SELECT id, name, version, ?, ?, ?, ? FROM artifacts WHERE id = ?",
+        )
+        .bind(key)
+        .bind(old_value.map(Value::to_string))
+        .bind(new_value.map(Value::to_string))
+        .bind(actor)
+        .bind(artifact_id)
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
+
+/// `422` unless `after`, the custom keys of a version of `name` once changed
+/// from `before` (`None` for a new version), satisfies the schemas for that
+/// name, leaving out failures of unchanged keys that were already there (see
+/// [`schema::check`]).
+pub(super) async fn enforce_schemas(
+    db: impl SqliteExecutor<'_>,
     state: &AppState,
     name: &str,
+    before: Option<&Map<String, Value>>,
+    after: &Map<String, Value>,
+) -> Result<(), AppError> {
+    let violations = schema::check(db, &state.schemas, name, before, after).await?;
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::schema_violations(violations))
+    }
+}
+
+/// The custom keys of an artifact.
+async fn custom<'e>(
+    db: impl SqliteExecutor<'e>,
+    artifact_id: &str,
+) -> Result<Map<String, Value>, AppError> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT key, value FROM artifact_metadata WHERE artifact_id = ?",
+    )
+    .bind(artifact_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(key, value)| (key, decode(value)))
+        .collect())
+}
+
+/// Looked up in the write transaction by handlers that change metadata, so
+/// the version can't be deleted before they're done with it.
+async fn lookup_artifact_id(
+    db: impl SqliteExecutor<'_>,
+    name: &str,
     version: &str,
 ) -> Result<String, AppError> {
     let row = sqlx::query("SELECT id FROM artifacts WHERE name = ? AND version = ?")
         .bind(name)
         .bind(version)
-        .fetch_optional(&state.db)
+        .fetch_optional(db)
         .await?
-        .ok_or_else(|| {
-            AppError::not_found(format!("artifact {}/{} not found", name, version))
-        })?;
+        .ok_or_else(|| AppError::not_found(format!("artifact {}/{} not found", name, version)))?;
 
     Ok(row.get("id"))
 }
+
+/// A stored metadata value, which is JSON.
+pub(super) fn decode(value: String) -> Value {
+    serde_json::from_str(&value).unwrap_or(Value::String(value))
+}
+
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(object) = target else {
+        unreachable!()
+    };
+    for (key, change) in patch {
+        if change.is_null() {
+            object.remove(&key);
+        } else {
+            merge_patch(object.entry(key).or_insert(Value::Null), change);
+        }
+    }
+}
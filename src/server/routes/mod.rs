@@ -1,31 +1,50 @@
+mod admin;
 mod artifacts;
+mod assets;
 mod metadata;
+mod packages;
+mod read_only;
+mod search;
 mod stats;
 mod tokens;
+mod usage;
 
 use axum::{
-    extract::DefaultBodyLimit,
-    http::StatusCode,
+    Json, Router,
+    extract::{DefaultBodyLimit, State},
+    http::{StatusCode, header},
+    middleware,
     response::IntoResponse,
-    Router,
     routing::get,
 };
-use tower_http::{
-    cors::CorsLayer,
-    trace::TraceLayer,
-};
+use tower_http::{cors::CorsLayer, trace::TraceLayer};
 
+use crate::error::AppError;
+use crate::scrub;
 use crate::state::AppState;
+use crate::storage;
 
 pub fn router(state: AppState) -> Router {
     let max_upload = state.max_upload_size;
 
     Router::new()
         .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/metrics", get(metrics))
         .merge(artifacts::routes())
+        .merge(assets::routes())
         .merge(metadata::routes())
+        .merge(packages::routes())
         .merge(tokens::routes())
         .merge(stats::routes())
+        .merge(search::routes())
+        .merge(admin::routes())
+        .merge(usage::routes())
+        .merge(read_only::routes())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            read_only::guard,
+        ))
         .layer(DefaultBodyLimit::max(max_upload))
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
@@ -35,3 +54,57 @@ pub fn router(state: AppState) -> Router {
 async fn health() -> impl IntoResponse {
     (StatusCode::OK, "ok")
 }
+
+/// 503 while the database is unreachable. Assets that failed their latest
+/// scrub are reported but don't make the node unready: they're refused for
+/// download, and the rest can still be served.
+async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let (status, body) = match scrub::failing(&state.db).await {
+        Ok(0) => (StatusCode::OK, serde_json::json!({ "status": "ready" })),
+        Ok(n) => (
+            StatusCode::OK,
+            serde_json::json!({ "status": "ready", "scrub_failures": n }),
+        ),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({ "status": "unready", "database": format!("{:#}", e) }),
+        ),
+    };
+    (status, Json(body))
+}
+
+async fn metrics(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let failing = scrub::failing(&state.db).await?;
+    let broken: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM assets WHERE broken_at IS NOT NULL")
+        .fetch_one(&state.db)
+        .await?;
+    let read_only = state.read_only.read().unwrap().enabled;
+    let mut gauges = vec![
+        (
+            "cask_scrub_failing_artifacts",
+            "Assets whose latest scrub failed.",
+            failing,
+        ),
+        (
+            "cask_broken_artifacts",
+            "Assets marked broken and refused for download.",
+            broken,
+        ),
+        (
+            "cask_read_only",
+            "1 while the server is in read-only mode.",
+            read_only as i64,
+        ),
+    ];
+    // Leave the gauge out rather than failing the whole scrape.
+    match storage::free_space(&state.data_dir) {
+        Ok(free) => gauges.push((
+            "cask_disk_free_bytes",
+            "Bytes available on the filesystem holding the data directory.",
+            free as i64,
+        )),
+        Err(e) => tracing::warn!("{:#}", e),
+    }
+    let body = state.metrics.render(&gauges);
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
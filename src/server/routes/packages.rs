@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::{
+    Json, Router,
+    routing::{delete, get},
+};
+use cask_types::{PackageInfo, PackageSummary};
+use serde_json::{Map, Value};
+use sqlx::{SqliteExecutor, SqlitePool};
+
+use crate::auth::RequireToken;
+use crate::db;
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Selects a [`PackageSummary`] per name from `artifacts a`; callers add
+/// `WHERE` conditions before `GROUP BY a.name`. The latest version prefers
+/// semver versions (whose sort keys don't start with `!`) over the rest. A
+/// `description` that isn't a string isn't shown.
+pub(super) const SUMMARY: &str = "SELECT a.name, \
+     (SELECT l.version FROM artifacts l WHERE l.name = a.name AND l.yanked_at IS NULL \
+      ORDER BY substr(l.version_key, 1, 1) <> '!' DESC, l.version_key DESC, l.created_at DESC \
+      LIMIT 1) AS latest_version, \
+     (SELECT p.value ->> '$' FROM package_metadata p \
+      WHERE p.name = a.name AND p.key = 'description' AND json_type(p.value) = 'text') \
+      AS description, \
+     COUNT(*) AS versions, \
+     (SELECT COALESCE(SUM(d.downloads), 0) FROM download_events d \
+      JOIN artifacts x ON x.id = d.artifact_id WHERE x.name = a.name) AS downloads, \
+     MAX(a.created_at) AS updated_at \
+     FROM artifacts a";
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/v1/packages/{name}", get(get_package))
+        .route(
+            "/v1/packages/{name}/meta",
+            get(get_metadata).put(set_metadata),
+        )
+        .route("/v1/packages/{name}/meta/{key}", delete(delete_metadata))
+        .route(
+            "/v1/packages/{name}/readme",
+            get(get_readme).put(set_readme).delete(delete_readme),
+        )
+}
+
+async fn get_package(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<PackageInfo>, AppError> {
+    require_package(&state.db, &name).await?;
+
+    // A package row may exist before any version is uploaded.
+    let summary = sqlx::query_as::<_, PackageSummary>(&format!(
+        "{} WHERE a.name = ? GROUP BY a.name",
+        SUMMARY
+    ))
+    .bind(&name)
+    .fetch_optional(&state.db)
+    .await?;
+    let metadata = metadata(&state.db, &name).await?;
+    let summary = summary.unwrap_or_else(|| PackageSummary {
+        name: name.clone(),
+        latest_version: None,
+        description: metadata
+            .get("description")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        versions: 0,
+        downloads: 0,
+        updated_at: None,
+    });
+
+    let has_readme = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM packages WHERE name = ? AND readme IS NOT NULL)",
+    )
+    .bind(&name)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(PackageInfo {
+        summary,
+        metadata,
+        has_readme,
+    }))
+}
+
+async fn get_metadata(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<BTreeMap<String, Value>>, AppError> {
+    require_package(&state.db, &name).await?;
+    Ok(Json(metadata(&state.db, &name).await?))
+}
+
+/// Upsert the given keys; a `null` value removes its key, as in a merge
+/// patch.
+async fn set_metadata(
+    State(state): State<AppState>,
+    _auth: RequireToken,
+    Path(name): Path<String>,
+    Json(body): Json<Map<String, Value>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = db::begin_write(&state.db).await?;
+    touch_package(&mut *tx, &name).await?;
+
+    for (key, value) in &body {
+        if value.is_null() {
+            sqlx::query("DELETE FROM package_metadata WHERE name = ? AND key = ?")
+                .bind(&name)
+                .bind(key)
+                .execute(&mut *tx)
+                .await?;
+            continue;
+        }
+        sqlx::query(
+            "INSERT OR REPLACE INTO package_metadata (name, key, value) \
+             VALUES (?, ?, ?)",
+        )
+        .bind(&name)
+        .bind(key)
+        .bind(value.to_string())
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_metadata(
+    State(state): State<AppState>,
+    _auth: RequireToken,
+    Path((name, key)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = db::begin_write(&state.db).await?;
+    require_package(&mut *tx, &name).await?;
+
+    let deleted = sqlx::query("DELETE FROM package_metadata WHERE name = ? AND key = ?")
+        .bind(&name)
+        .bind(&key)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if deleted > 0 {
+        touch_package(&mut *tx, &name).await?;
+    }
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_readme(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let readme =
+        sqlx::query_scalar::<_, Option<String>>("SELECT readme FROM packages WHERE name = ?")
+            .bind(&name)
+            .fetch_optional(&state.db)
+            .await?
+            .flatten()
+            .ok_or_else(|| AppError::not_found(format!("package {} has no README", name)))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+        readme,
+    ))
+}
+
+async fn set_readme(
+    State(state): State<AppState>,
+    _auth: RequireToken,
+    Path(name): Path<String>,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = db::begin_write(&state.db).await?;
+    touch_package(&mut *tx, &name).await?;
+
+    sqlx::query("UPDATE packages SET readme = ? WHERE name = ?")
+        .bind(&body)
+        .bind(&name)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_readme(
+    State(state): State<AppState>,
+    _auth: RequireToken,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = db::begin_write(&state.db).await?;
+    require_package(&mut *tx, &name).await?;
+
+    let deleted =
+        sqlx::query("UPDATE packages SET readme = NULL WHERE name = ? AND readme IS NOT NULL")
+            .bind(&name)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+    if deleted > 0 {
+        touch_package(&mut *tx, &name).await?;
+    }
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn metadata(db: &SqlitePool, name: &str) -> Result<BTreeMap<String, Value>, AppError> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT key, value FROM package_metadata WHERE name = ?",
+    )
+    .bind(name)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(key, value)| (key, super::metadata::decode(value)))
+        .collect())
+}
+
+/// 404 unless `name` has versions or package data.
+async fn require_package(db: impl SqliteExecutor<'_>, name: &str) -> Result<(), AppError> {
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM packages WHERE name = ?1) \
+         OR EXISTS (SELECT 1 FROM artifacts WHERE name = ?1)",
+    )
+    .bind(name)
+    .fetch_one(db)
+    .await?;
+
+    if !exists {
+        return Err(AppError::not_found(format!("package {} not found", name)));
+    }
+    Ok(())
+}
+
+/// Create the package row if needed and bump its `updated_at`.
+async fn touch_package(db: impl SqliteExecutor<'_>, name: &str) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO packages (name) VALUES (?) \
+         ON CONFLICT (name) DO UPDATE SET updated_at = datetime('now')",
+    )
+    .bind(name)
+    .execute(db)
+    .await?;
+    Ok(())
+}
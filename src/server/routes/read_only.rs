@@ -0,0 +1,60 @@
+use axum::extract::{Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router, routing::get};
+use cask_types::ReadOnlyMode;
+
+use crate::auth::RequireAdmin;
+use crate::error::AppError;
+use crate::maintenance;
+use crate::state::AppState;
+
+const READ_ONLY_PATH: &str = "/v1/admin/read-only";
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route(READ_ONLY_PATH, get(get_mode).put(set_mode))
+}
+
+async fn get_mode(State(state): State<AppState>, _auth: RequireAdmin) -> Json<ReadOnlyMode> {
+    Json(state.read_only.read().unwrap().clone())
+}
+
+async fn set_mode(
+    State(state): State<AppState>,
+    _auth: RequireAdmin,
+    Json(mode): Json<ReadOnlyMode>,
+) -> Result<Json<ReadOnlyMode>, AppError> {
+    // Saved first, so the mode never claims more than a restart would keep.
+    maintenance::save(&state.data_dir, &mode)?;
+    if mode.enabled {
+        tracing::warn!(
+            "read-only mode enabled{}",
+            mode.reason
+                .as_deref()
+                .map(|r| format!(": {}", r))
+                .unwrap_or_default()
+        );
+    } else {
+        tracing::info!("read-only mode disabled");
+    }
+    *state.read_only.write().unwrap() = mode.clone();
+    Ok(Json(mode))
+}
+
+/// Refuse every request that could modify state while read-only mode is on,
+/// except the one that turns it off.
+pub async fn guard(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let safe = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !safe && req.uri().path() != READ_ONLY_PATH {
+        let mode = state.read_only.read().unwrap().clone();
+        if mode.enabled {
+            let message = match mode.reason {
+                Some(reason) => format!("server is read-only: {}", reason),
+                None => "server is read-only".to_string(),
+            };
+            return AppError::unavailable(message, mode.retry_after).into_response();
+        }
+    }
+    next.run(req).await
+}
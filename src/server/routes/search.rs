@@ -0,0 +1,137 @@
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::response::IntoResponse;
+use axum::{Json, Router, routing::get};
+use cask_types::{SearchHit, SearchQuery};
+use sqlx::{QueryBuilder, Sqlite};
+
+use super::metadata;
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Page size when `limit` isn't given, and the largest allowed.
+const DEFAULT_LIMIT: u32 = 20;
+const MAX_LIMIT: u32 = 100;
+
+/// Marks around matches in snippets before escaping. The search index
+/// strips them from the text it stores (see migration 010).
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/v1/search", get(search))
+}
+
+async fn search(
+    State(state): State<AppState>,
+    uri: Uri,
+    Query(query): Query<SearchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let (expr, comparisons) = parse_query(&query.q);
+    if expr.is_none() && comparisons.is_empty() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "q must contain at least one word",
+        ));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+
+    let mut sql = QueryBuilder::<Sqlite>::new(
+        "SELECT a.id, a.name, a.version, a.filename, a.sha256, a.size, a.created_at, \
+         a.yanked_at, ",
+    );
+    // bm25 is lower for better matches; names weigh most, metadata least.
+    // Without words there's nothing to rank or highlight.
+    if expr.is_some() {
+        sql.push(format!(
+            "-bm25(artifact_search, 10.0, 5.0, 1.0) AS score, \
+             snippet(artifact_search, -1, '{}', '{}', '…', 12) AS snippet ",
+            MATCH_START, MATCH_END
+        ));
+    } else {
+        sql.push("0.0 AS score, '' AS snippet ");
+    }
+    sql.push(
+        "FROM artifact_search JOIN artifacts a ON a.id = artifact_search.artifact_id \
+         WHERE 1 = 1",
+    );
+    if let Some(expr) = &expr {
+        sql.push(" AND artifact_search MATCH ")
+            .push_bind(expr.clone());
+    }
+    for (key, op, number) in comparisons {
+        metadata::push_comparison(&mut sql, "a.id", key, op, number);
+    }
+    if expr.is_some() {
+        sql.push(" ORDER BY bm25(artifact_search, 10.0, 5.0, 1.0), a.name, a.created_at DESC");
+    } else {
+        sql.push(" ORDER BY a.name, a.created_at DESC");
+    }
+    sql.push(" LIMIT ")
+        .push_bind(limit as i64 + 1)
+        .push(" OFFSET ")
+        .push_bind(offset as i64);
+
+    let mut hits = sql
+        .build_query_as::<SearchHit>()
+        .fetch_all(&state.db)
+        .await?;
+
+    let mut headers = HeaderMap::new();
+    if hits.len() > limit as usize {
+        hits.truncate(limit as usize);
+        let mut params: Vec<String> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty() && !p.starts_with("offset="))
+            .map(str::to_string)
+            .collect();
+        params.push(format!("offset={}", offset + limit));
+        let link = format!("<{}?{}>; rel=\"next\"", uri.path(), params.join("&"));
+        headers.insert(header::LINK, link.parse().unwrap());
+    }
+    for hit in &mut hits {
+        hit.snippet = highlight(&hit.snippet);
+    }
+
+    Ok((headers, Json(hits)))
+}
+
+/// Split `q` into numeric metadata comparisons (`key>N`, `key<=N`, ...) and
+/// an FTS5 query matching rows that contain every other word, each as a
+/// prefix. Words are quoted so that FTS5 syntax in them is taken literally.
+fn parse_query(q: &str) -> (Option<String>, Vec<(&str, &'static str, f64)>) {
+    let mut terms = Vec::new();
+    let mut comparisons = Vec::new();
+    for word in q.split_whitespace() {
+        let comparison = word
+            .find(['<', '>'])
+            .filter(|&i| i > 0)
+            .and_then(|i| Some((&word[..i], metadata::comparison(&word[i..])?)));
+        match comparison {
+            Some((key, (op, number))) => comparisons.push((key, op, number)),
+            None => terms.push(format!("\"{}\"*", word.replace('"', "\"\""))),
+        }
+    }
+    ((!terms.is_empty()).then(|| terms.join(" ")), comparisons)
+}
+
+/// Escape a snippet for HTML and turn its match marks into `<mark>` tags.
+fn highlight(snippet: &str) -> String {
+    let mut out = String::with_capacity(snippet.len());
+    for c in snippet.chars() {
+        match c {
+            MATCH_START => out.push_str("<mark>"),
+            MATCH_END => out.push_str("</mark>"),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -1,51 +1,363 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
 use axum::{Json, Router, routing::get};
-use serde::Serialize;
+use cask_types::{
+    AssetStats, BreakdownQuery, ClientStats, ReferrerStats, StatsBucket, StatsInterval, StatsQuery,
+    StatsResponse, TopArtifact, TopQuery, VersionStats,
+};
+use sqlx::SqlitePool;
 
 use crate::error::AppError;
 use crate::state::AppState;
 
+/// Artifacts `/v1/stats/top` returns when `limit` isn't given, and the most
+/// it allows.
+const DEFAULT_TOP_LIMIT: u32 = 10;
+const MAX_TOP_LIMIT: u32 = 100;
+
 pub fn routes() -> Router<AppState> {
     Router::new()
-        .route(
-            "/v1/artifacts/{name}/{version}/stats",
-            get(version_stats),
-        )
+        .route("/v1/artifacts/{name}/{version}/stats", get(version_stats))
         .route("/v1/artifacts/{name}/stats", get(artifact_stats))
+        .route("/v1/stats/top", get(top))
+        .route("/v1/stats/clients", get(clients))
+        .route("/v1/stats/referrers", get(referrers))
 }
 
-#[derive(Serialize)]
-struct StatsResponse {
-    downloads: i64,
-}
+/// Downloads of artifact `?1` and version `?2` (each if not NULL) between
+/// `?3` and `?4`, raw and rolled up, leaving out bots if `?5`.
+const FILTER: &str = "FROM download_events ds JOIN artifacts a ON ds.artifact_id = a.id \
+     WHERE (?1 IS NULL OR a.name = ?1) AND (?2 IS NULL OR a.version = ?2) \
+     AND (?3 IS NULL OR ds.downloaded_at >= datetime(?3)) \
+     AND (?4 IS NULL OR ds.downloaded_at < datetime(?4)) \
+     AND (NOT ?5 OR ds.bot = 0)";
+
+const DOWNLOADS: &str = "COALESCE(SUM(ds.downloads), 0)";
+
+/// Exact for raw rows. Rolled-up days add their per-version daily uniques,
+/// which over-counts IPs seen on several days or versions.
+const UNIQUE_IPS: &str = "COUNT(DISTINCT ds.ip) + COALESCE(SUM(ds.rolled_unique_ips), 0)";
 
 async fn version_stats(
     State(state): State<AppState>,
     Path((name, version)): Path<(String, String)>,
+    Query(query): Query<StatsQuery>,
 ) -> Result<Json<StatsResponse>, AppError> {
-    let downloads = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM download_stats \
-         WHERE artifact_id = (SELECT id FROM artifacts WHERE name = ? AND version = ?)",
-    )
-    .bind(&name)
-    .bind(&version)
-    .fetch_one(&state.db)
-    .await?;
-
-    Ok(Json(StatsResponse { downloads }))
+    stats(&state.db, &name, Some(&version), &query).await
 }
 
 async fn artifact_stats(
     State(state): State<AppState>,
     Path(name): Path<String>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<StatsResponse>, AppError> {
+    stats(&state.db, &name, None, &query).await
+}
+
+async fn stats(
+    db: &SqlitePool,
+    name: &str,
+    version: Option<&str>,
+    query: &StatsQuery,
 ) -> Result<Json<StatsResponse>, AppError> {
-    let downloads = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM download_stats ds \
-         JOIN artifacts a ON ds.artifact_id = a.id WHERE a.name = ?",
+    validate_range(db, query.from.as_deref(), query.to.as_deref()).await?;
+
+    let (downloads, unique_ips) = sqlx::query_as::<_, (i64, i64)>(&format!(
+        "SELECT {}, {} {}",
+        DOWNLOADS, UNIQUE_IPS, FILTER
+    ))
+    .bind(name)
+    .bind(version)
+    .bind(&query.from)
+    .bind(&query.to)
+    .bind(query.exclude_bots)
+    .fetch_one(db)
+    .await?;
+
+    let series = match query.interval {
+        Some(interval) => Some(
+            sqlx::query_as::<_, StatsBucket>(&format!(
+                "SELECT {} AS start, {} AS downloads, {} AS unique_ips \
+                 {} GROUP BY start ORDER BY start",
+                bucket_expr(interval),
+                DOWNLOADS,
+                UNIQUE_IPS,
+                FILTER
+            ))
+            .bind(name)
+            .bind(version)
+            .bind(&query.from)
+            .bind(&query.to)
+            .bind(query.exclude_bots)
+            .fetch_all(db)
+            .await?,
+        ),
+        None => None,
+    };
+
+    let versions = if query.by_version && version.is_none() {
+        Some(
+            sqlx::query_as::<_, VersionStats>(&format!(
+                "SELECT a.version, {} AS downloads, {} AS unique_ips \
+                 {} GROUP BY a.id ORDER BY downloads DESC, a.created_at DESC",
+                DOWNLOADS, UNIQUE_IPS, FILTER
+            ))
+            .bind(name)
+            .bind(version)
+            .bind(&query.from)
+            .bind(&query.to)
+            .bind(query.exclude_bots)
+            .fetch_all(db)
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    // Downloads of assets the version no longer has only count in the total.
+    let assets = if query.by_asset && version.is_some() {
+        Some(
+            sqlx::query_as::<_, AssetStats>(&format!(
+                "SELECT f.filename, {} AS downloads, {} AS unique_ips \
+                 FROM assets f JOIN (SELECT ds.* {}) ds ON ds.asset_id = f.id \
+                 GROUP BY f.id ORDER BY downloads DESC, f.filename",
+                DOWNLOADS, UNIQUE_IPS, FILTER
+            ))
+            .bind(name)
+            .bind(version)
+            .bind(&query.from)
+            .bind(&query.to)
+            .bind(query.exclude_bots)
+            .fetch_all(db)
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    Ok(Json(StatsResponse {
+        downloads,
+        unique_ips,
+        series,
+        versions,
+        assets,
+    }))
+}
+
+async fn top(
+    State(state): State<AppState>,
+    Query(query): Query<TopQuery>,
+) -> Result<Json<Vec<TopArtifact>>, AppError> {
+    validate_range(&state.db, query.from.as_deref(), query.to.as_deref()).await?;
+
+    let rows = sqlx::query_as::<_, TopArtifact>(&format!(
+        "SELECT a.name, {} AS downloads, {} AS unique_ips \
+         {} GROUP BY a.name ORDER BY downloads DESC, a.name LIMIT ?6",
+        DOWNLOADS, UNIQUE_IPS, FILTER
+    ))
+    .bind(None::<&str>)
+    .bind(None::<&str>)
+    .bind(&query.from)
+    .bind(&query.to)
+    .bind(query.exclude_bots)
+    .bind(
+        query
+            .limit
+            .unwrap_or(DEFAULT_TOP_LIMIT)
+            .clamp(1, MAX_TOP_LIMIT),
     )
-    .bind(&name)
-    .fetch_one(&state.db)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+async fn clients(
+    State(state): State<AppState>,
+    Query(query): Query<BreakdownQuery>,
+) -> Result<Json<Vec<ClientStats>>, AppError> {
+    validate_breakdown(&state.db, &query).await?;
+
+    let rows = sqlx::query_as::<_, ClientStats>(&format!(
+        "SELECT ds.client, MAX(ds.bot) AS bot, {} AS downloads, {} AS unique_ips \
+         {} GROUP BY ds.client ORDER BY downloads DESC, ds.client",
+        DOWNLOADS, UNIQUE_IPS, FILTER
+    ))
+    .bind(&query.name)
+    .bind(&query.version)
+    .bind(&query.from)
+    .bind(&query.to)
+    .bind(query.exclude_bots)
+    .fetch_all(&state.db)
     .await?;
 
-    Ok(Json(StatsResponse { downloads }))
+    Ok(Json(rows))
+}
+
+async fn referrers(
+    State(state): State<AppState>,
+    Query(query): Query<BreakdownQuery>,
+) -> Result<Json<Vec<ReferrerStats>>, AppError> {
+    validate_breakdown(&state.db, &query).await?;
+
+    let rows = sqlx::query_as::<_, ReferrerStats>(&format!(
+        "SELECT ds.referrer, {} AS downloads, {} AS unique_ips \
+         {} GROUP BY ds.referrer ORDER BY downloads DESC, ds.referrer",
+        DOWNLOADS, UNIQUE_IPS, FILTER
+    ))
+    .bind(&query.name)
+    .bind(&query.version)
+    .bind(&query.from)
+    .bind(&query.to)
+    .bind(query.exclude_bots)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+async fn validate_breakdown(db: &SqlitePool, query: &BreakdownQuery) -> Result<(), AppError> {
+    if query.version.is_some() && query.name.is_none() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            "version requires name",
+        ));
+    }
+    validate_range(db, query.from.as_deref(), query.to.as_deref()).await
+}
+
+/// SQLite expression for the start of the bucket a download falls in.
+fn bucket_expr(interval: StatsInterval) -> &'static str {
+    match interval {
+        StatsInterval::Hour => "strftime('%Y-%m-%d %H:00:00', ds.downloaded_at)",
+        StatsInterval::Day => "strftime('%Y-%m-%d 00:00:00', ds.downloaded_at)",
+        StatsInterval::Week => {
+            "strftime('%Y-%m-%d 00:00:00', ds.downloaded_at, 'weekday 0', '-6 days')"
+        }
+        StatsInterval::Month => "strftime('%Y-%m-01 00:00:00', ds.downloaded_at)",
+    }
+}
+
+/// Reject `from`/`to` values SQLite can't parse as times, which would
+/// otherwise silently match nothing.
+async fn validate_range(
+    db: &SqlitePool,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(), AppError> {
+    for (param, value) in [("from", from), ("to", to)] {
+        if let Some(value) = value {
+            validate_time(db, param, value).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Reject a time query parameter SQLite can't parse.
+pub(super) async fn validate_time(
+    db: &SqlitePool,
+    param: &str,
+    value: &str,
+) -> Result<(), AppError> {
+    let parsed = sqlx::query_scalar::<_, Option<String>>("SELECT datetime(?)")
+        .bind(value)
+        .fetch_one(db)
+        .await?;
+    if parsed.is_none() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "invalid {} time {:?}; expected e.g. 2024-01-31 or 2024-01-31T12:00:00Z",
+                param, value
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Uri;
+
+    use super::*;
+
+    async fn memory_db() -> SqlitePool {
+        SqlitePool::connect("sqlite::memory:").await.unwrap()
+    }
+
+    async fn bucket(db: &SqlitePool, interval: StatsInterval, at: &str) -> String {
+        sqlx::query_scalar(&format!(
+            "SELECT {} FROM (SELECT ? AS downloaded_at) ds",
+            bucket_expr(interval)
+        ))
+        .bind(at)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn buckets_start_at_the_interval() {
+        let db = memory_db().await;
+        // A Thursday.
+        let at = "2024-02-15 13:45:10";
+        for (interval, start) in [
+            (StatsInterval::Hour, "2024-02-15 13:00:00"),
+            (StatsInterval::Day, "2024-02-15 00:00:00"),
+            (StatsInterval::Week, "2024-02-12 00:00:00"),
+            (StatsInterval::Month, "2024-02-01 00:00:00"),
+        ] {
+            assert_eq!(bucket(&db, interval, at).await, start, "{:?}", interval);
+        }
+    }
+
+    #[tokio::test]
+    async fn weeks_start_on_monday() {
+        let db = memory_db().await;
+        for (at, start) in [
+            // Monday, Sunday and the Sunday before, across a month boundary.
+            ("2024-03-04 00:00:00", "2024-03-04 00:00:00"),
+            ("2024-03-10 23:59:59", "2024-03-04 00:00:00"),
+            ("2024-03-03 12:00:00", "2024-02-26 00:00:00"),
+        ] {
+            assert_eq!(bucket(&db, StatsInterval::Week, at).await, start, "{}", at);
+        }
+    }
+
+    #[tokio::test]
+    async fn times_are_validated() {
+        let db = memory_db().await;
+        for value in [
+            "2024-01-31",
+            "2024-01-31 12:00:00",
+            "2024-01-31T12:00:00Z",
+            "2024-01-31T12:00:00+02:00",
+        ] {
+            assert!(validate_time(&db, "from", value).await.is_ok(), "{}", value);
+        }
+        for value in ["yesterday", "2024-13-01", "31/01/2024", ""] {
+            assert!(
+                validate_time(&db, "from", value).await.is_err(),
+                "{}",
+                value
+            );
+        }
+        assert!(validate_range(&db, None, Some("soon")).await.is_err());
+        assert!(validate_range(&db, None, None).await.is_ok());
+    }
+
+    #[test]
+    fn intervals_parse_from_the_query() {
+        let parse = |query: &str| {
+            let uri: Uri = format!("/v1/artifacts/app/stats?{}", query)
+                .parse()
+                .unwrap();
+            Query::<StatsQuery>::try_from_uri(&uri).map(|q| q.0.interval)
+        };
+        assert_eq!(parse("interval=hour").unwrap(), Some(StatsInterval::Hour));
+        assert_eq!(parse("interval=week").unwrap(), Some(StatsInterval::Week));
+        assert_eq!(parse("from=2024-01-01").unwrap(), None);
+        assert!(parse("interval=fortnight").is_err());
+        assert!(parse("interval=Day").is_err());
+    }
 }
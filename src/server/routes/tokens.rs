@@ -1,13 +1,12 @@
 use axum::{
+    Json, Router,
     extract::{Path, State},
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
-    Json,
-    Router,
-    routing::{delete, post}
+    routing::{delete, post},
 };
 
-use serde::{Deserialize, Serialize};
+use cask_types::{CreateTokenRequest, CreateTokenResponse, TokenInfo};
 use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
@@ -21,31 +20,6 @@ pub fn routes() -> Router<AppState> {
         .route("/v1/tokens/{id}", delete(revoke_token))
 }
 
-#[derive(Deserialize)]
-struct CreateTokenRequest {
-    label: String,
-    #[serde(default)]
-    is_admin: bool,
-    expires_at: Option<String>,
-}
-
-#[derive(Serialize)]
-struct CreateTokenResponse {
-    id: String,
-    token: String,
-    label: String,
-    is_admin: bool,
-}
-
-#[derive(Serialize, sqlx::FromRow)]
-struct TokenInfo {
-    id: String,
-    label: String,
-    is_admin: bool,
-    expires_at: Option<String>,
-    created_at: String,
-}
-
 async fn create_token(
     State(state): State<AppState>,
     headers: HeaderMap,
@@ -70,18 +44,27 @@ async fn create_token(
     // Bootstrap token is always admin
     let is_admin = if is_bootstrap { true } else { body.is_admin };
 
-    sqlx::query(
+    // A bootstrap insert only succeeds if there are still no tokens, so two
+    // racing bootstrap requests can't both get an admin token.
+    let result = sqlx::query(
         "INSERT INTO tokens (id, token_hash, label, is_admin, expires_at) \
-         VALUES (?, ?, ?, ?, ?)",
+         SELECT ?, ?, ?, ?, ? WHERE NOT ? OR NOT EXISTS (SELECT 1 FROM tokens)",
     )
     .bind(&id)
     .bind(&token_hash)
     .bind(&body.label)
     .bind(is_admin)
     .bind(&body.expires_at)
+    .bind(is_bootstrap)
     .execute(&state.db)
     .await?;
 
+    if result.rows_affected() == 0 {
+        return Err(AppError::conflict(
+            "another token was created first; authenticate with an admin token",
+        ));
+    }
+
     Ok((
         StatusCode::CREATED,
         Json(CreateTokenResponse {
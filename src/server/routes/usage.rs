@@ -0,0 +1,26 @@
+use axum::extract::State;
+use axum::{Json, Router, routing::get};
+use cask_types::QuotaUsage;
+
+use crate::auth::RequireToken;
+use crate::error::AppError;
+use crate::quota;
+use crate::state::AppState;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/v1/usage", get(usage))
+}
+
+/// Consumption against each quota. Admins see every quota; other tokens see
+/// the prefix quotas and their own token quota.
+async fn usage(
+    State(state): State<AppState>,
+    auth: RequireToken,
+) -> Result<Json<Vec<QuotaUsage>>, AppError> {
+    let visible_to = (!auth.is_admin).then_some(auth.token_id.as_str());
+    let mut usage = Vec::new();
+    for q in quota::list(&state.db, visible_to).await? {
+        usage.push(quota::usage(&state.db, q).await?);
+    }
+    Ok(Json(usage))
+}
@@ -1,10 +1,26 @@
 use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 
+use cask_types::ReadOnlyMode;
 use sqlx::SqlitePool;
 
+use crate::metrics::Metrics;
+use crate::privacy::IpPolicy;
+use crate::recorder::DownloadRecorder;
+use crate::schema::Validators;
+
 #[derive(Clone)]
 pub struct AppState {
     pub db: SqlitePool,
     pub data_dir: PathBuf,
     pub max_upload_size: usize,
+    pub metrics: Arc<Metrics>,
+    /// Uploads are refused while free disk space would drop below this many
+    /// bytes (0 disables the check).
+    pub min_free_space: u64,
+    pub read_only: Arc<RwLock<ReadOnlyMode>>,
+    pub recorder: DownloadRecorder,
+    pub ip_policy: Arc<IpPolicy>,
+    /// Compiled metadata schemas.
+    pub schemas: Arc<Validators>,
 }
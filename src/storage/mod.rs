@@ -1,17 +1,41 @@
-use std::path::Path;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
+use nix::sys::statvfs::statvfs;
+use sha2::{Digest, Sha256};
 use tokio::fs;
+use tokio::io::AsyncReadExt;
 
-/// Save artifact bytes to disk. Returns the path written.
+/// Save artifact bytes to disk. A partially written file is removed on
+/// failure.
 pub async fn save(data_dir: &Path, artifact_id: &str, bytes: &[u8]) -> Result<()> {
     let path = data_dir.join("artifacts").join(artifact_id);
-    fs::write(&path, bytes)
-        .await
-        .with_context(|| format!("failed to write artifact to {}", path.display()))?;
+    if let Err(e) = fs::write(&path, bytes).await {
+        let _ = fs::remove_file(&path).await;
+        return Err(e).with_context(|| format!("failed to write artifact to {}", path.display()));
+    }
     Ok(())
 }
 
+/// Bytes available to unprivileged users on the filesystem holding
+/// `data_dir`.
+pub fn free_space(data_dir: &Path) -> Result<u64> {
+    let stat = statvfs(data_dir)
+        .with_context(|| format!("failed to stat filesystem of {}", data_dir.display()))?;
+    Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+/// Whether `err` was caused by the disk being full.
+pub fn is_out_of_space(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::StorageFull)
+    })
+}
+
 /// Load artifact bytes from disk.
 pub async fn load(data_dir: &Path, artifact_id: &str) -> Result<Vec<u8>> {
     let path = data_dir.join("artifacts").join(artifact_id);
@@ -31,3 +55,90 @@ pub async fn delete(data_dir: &Path, artifact_id: &str) -> Result<()> {
     }
     Ok(())
 }
+
+/// SHA-256 (hex) and size of a stored artifact, or `None` if its file is
+/// missing. Reads in chunks so large artifacts aren't loaded into memory;
+/// `bytes_per_sec` caps the read rate when set.
+pub async fn checksum(
+    data_dir: &Path,
+    artifact_id: &str,
+    bytes_per_sec: Option<u64>,
+) -> Result<Option<(String, u64)>> {
+    let path = data_dir.join("artifacts").join(artifact_id);
+    if bytes_per_sec.is_none_or(|r| r == 0) {
+        // Hash at full speed on a blocking thread, so a whole fsck pass
+        // doesn't occupy the async workers serving requests.
+        return tokio::task::spawn_blocking(move || checksum_blocking(path)).await?;
+    }
+
+    let mut file = match fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("failed to open artifact at {}", path.display()));
+        }
+    };
+
+    let started = Instant::now();
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .await
+            .with_context(|| format!("failed to read artifact from {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+
+        if let Some(rate) = bytes_per_sec.filter(|r| *r > 0) {
+            let due = Duration::from_secs_f64(size as f64 / rate as f64);
+            if let Some(ahead) = due.checked_sub(started.elapsed()) {
+                tokio::time::sleep(ahead).await;
+            }
+        }
+    }
+
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok(Some((sha256, size)))
+}
+
+fn checksum_blocking(path: PathBuf) -> Result<Option<(String, u64)>> {
+    let mut file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("failed to open artifact at {}", path.display()));
+        }
+    };
+
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .with_context(|| format!("failed to read artifact from {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok(Some((sha256, size)))
+}
@@ -0,0 +1,565 @@
+//! Exercises `cask-client` against the real router served in-process.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use cask::metrics::Metrics;
+use cask::recorder::DownloadRecorder;
+use cask::state::AppState;
+use cask::{db, server::routes};
+use cask_client::{Client, Error, ErrorKind, RetryPolicy};
+use cask_types::{
+    CreateMetadataSchema, CreateTokenRequest, KeySpec, KeyType, ListQuery, ListSort, QuotaScope,
+    SearchQuery, SetQuotaRequest, SortOrder, StatsQuery,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+
+/// Start a server on an ephemeral port. The returned `TempDir` holds the
+/// data dir and must outlive the test.
+async fn spawn_server() -> (Client, AppState, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("artifacts")).unwrap();
+
+    let db = db::create_pool(dir.path()).await.unwrap();
+    let metrics = Arc::new(Metrics::default());
+    let state = AppState {
+        recorder: DownloadRecorder::spawn(db.clone(), metrics.clone(), 1024),
+        db,
+        data_dir: dir.path().to_path_buf(),
+        max_upload_size: 1024 * 1024,
+        metrics,
+        min_free_space: 0,
+        read_only: Default::default(),
+        ip_policy: Default::default(),
+        schemas: Default::default(),
+    };
+    let app = routes::router(state.clone());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+
+    let client = Client::new(&format!("http://{}", addr))
+        .unwrap()
+        .with_retry(RetryPolicy::none());
+    (client, state, dir)
+}
+
+async fn admin_client(client: &Client) -> Client {
+    let created = client
+        .create_token(&CreateTokenRequest {
+            label: "admin".to_string(),
+            is_admin: true,
+            expires_at: None,
+        })
+        .await
+        .unwrap();
+    assert!(created.is_admin);
+    client.clone().with_token(created.token)
+}
+
+#[tokio::test]
+async fn artifact_round_trip() {
+    let (anon, state, _dir) = spawn_server().await;
+    anon.health().await.unwrap();
+    let client = admin_client(&anon).await;
+
+    let uploaded = client
+        .upload_bytes("my app", "1.0.0", Some("app.bin"), &b"hello cask"[..])
+        .await
+        .unwrap();
+    assert_eq!(uploaded.name, "my app");
+    assert_eq!(uploaded.size, 10);
+
+    let listed = anon.list_versions("my app").await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].sha256, uploaded.sha256);
+
+    let mut download = anon.download("my app", "1.0.0").await.unwrap();
+    assert_eq!(download.filename(), Some("app.bin"));
+    assert_eq!(download.sha256(), Some(uploaded.sha256.as_str()));
+    let mut body = Vec::new();
+    while let Some(chunk) = download.chunk().await.unwrap() {
+        body.extend_from_slice(&chunk);
+    }
+    assert_eq!(body, b"hello cask");
+
+    let values = HashMap::from([("branch".to_string(), "main".to_string())]);
+    client
+        .set_metadata("my app", "1.0.0", &values)
+        .await
+        .unwrap();
+    let meta = anon.get_metadata("my app", "1.0.0").await.unwrap();
+    assert_eq!(meta.sha256, uploaded.sha256);
+    assert_eq!(meta.custom["branch"], "main");
+    // Null removes a key rather than storing it.
+    client
+        .set_metadata("my app", "1.0.0", &json!({ "branch": null, "tests": 12 }))
+        .await
+        .unwrap();
+    let meta = anon.get_metadata("my app", "1.0.0").await.unwrap();
+    assert!(!meta.custom.contains_key("branch"));
+    assert_eq!(meta.custom["tests"], 12);
+    client
+        .delete_metadata("my app", "1.0.0", "tests")
+        .await
+        .unwrap();
+
+    state.recorder.flush().await;
+    assert_eq!(
+        anon.version_stats("my app", "1.0.0")
+            .await
+            .unwrap()
+            .downloads,
+        1
+    );
+    assert_eq!(anon.artifact_stats("my app").await.unwrap().downloads, 1);
+
+    assert!(
+        client
+            .yank("my app", "1.0.0")
+            .await
+            .unwrap()
+            .yanked_at
+            .is_some()
+    );
+    assert!(
+        client
+            .unyank("my app", "1.0.0")
+            .await
+            .unwrap()
+            .yanked_at
+            .is_none()
+    );
+
+    client.delete_artifact("my app", "1.0.0").await.unwrap();
+    assert!(anon.list_artifacts().await.unwrap().is_empty());
+
+    let actions: Vec<String> = client
+        .audit_log(10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|e| e.action)
+        .collect();
+    assert_eq!(actions, ["delete", "unyank", "yank"]);
+}
+
+/// Only idempotent requests are repeated after a timeout, since the server
+/// may have acted on the first attempt.
+#[tokio::test]
+async fn timed_out_posts_are_not_retried() {
+    // Accepts connections and never answers.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicU64::new(0));
+    let accepted = connections.clone();
+    tokio::spawn(async move {
+        let mut open = Vec::new();
+        while let Ok((socket, _)) = listener.accept().await {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            open.push(socket);
+        }
+    });
+
+    let http = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_millis(200))
+        .build()
+        .unwrap();
+    let client = Client::new(&format!("http://{}", addr))
+        .unwrap()
+        .with_http_client(http)
+        .with_retry(RetryPolicy {
+            max_retries: 2,
+            initial_backoff: std::time::Duration::from_millis(10),
+            max_backoff: std::time::Duration::from_millis(10),
+        });
+
+    let request = CreateTokenRequest {
+        label: "ci".to_string(),
+        is_admin: false,
+        expires_at: None,
+    };
+    assert!(client.create_token(&request).await.is_err());
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    assert!(client.health().await.is_err());
+    assert_eq!(connections.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn upload_file_reports_progress() {
+    let (anon, _state, dir) = spawn_server().await;
+    let client = admin_client(&anon).await;
+
+    let path = dir.path().join("upload.bin");
+    std::fs::write(&path, vec![7u8; 200_000]).unwrap();
+
+    let sent = Arc::new(AtomicU64::new(0));
+    let seen = Arc::clone(&sent);
+    let artifact = client
+        .upload_file_with_progress("big", "1", &path, None, move |n| {
+            seen.store(n, Ordering::Relaxed)
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(artifact.size, 200_000);
+    assert_eq!(sent.load(Ordering::Relaxed), 200_000);
+}
+
+#[tokio::test]
+async fn errors_map_to_kinds() {
+    let (anon, state, dir) = spawn_server().await;
+    let client = admin_client(&anon).await;
+
+    let err = anon.download("missing", "1").await.err().unwrap();
+    assert_eq!(err.kind(), Some(ErrorKind::NotFound));
+
+    let err = anon.upload_bytes("x", "1", None, "data").await.unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::Unauthorized));
+
+    client.upload_bytes("x", "1", None, "data").await.unwrap();
+    let err = client
+        .upload_bytes("x", "1", None, "data")
+        .await
+        .unwrap_err();
+    assert!(err.is_conflict());
+
+    sqlx::query("UPDATE assets SET broken_at = datetime('now'), broken_reason = 'file is missing'")
+        .execute(&state.db)
+        .await
+        .unwrap();
+    let err = anon.download("x", "1").await.err().unwrap();
+    assert_eq!(err.kind(), Some(ErrorKind::Damaged));
+
+    let err = anon.list_tokens().await.unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::Unauthorized));
+
+    client
+        .set_quota(&SetQuotaRequest {
+            scope: QuotaScope::Prefix,
+            target: "q".to_string(),
+            max_bytes: Some(4),
+            max_artifacts: None,
+        })
+        .await
+        .unwrap();
+    let err = client
+        .upload_bytes("q", "1", None, "too much")
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::QuotaExceeded));
+    assert!(matches!(
+        err,
+        Error::Api {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            ..
+        }
+    ));
+
+    // Adding a file is refused on its declared length too.
+    let path = dir.path().join("too-much");
+    std::fs::write(&path, "too much").unwrap();
+    let err = client
+        .upload_asset("q", "1", "extra", &path, |_| {})
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::QuotaExceeded));
+    assert!(
+        client
+            .list_assets("q", "1")
+            .await
+            .unwrap_err()
+            .is_not_found()
+    );
+}
+
+#[tokio::test]
+async fn paging_through_ties_returns_each_version_once() {
+    let (anon, state, _dir) = spawn_server().await;
+    let client = admin_client(&anon).await;
+
+    // Same size and upload time throughout, and three spellings of 1.0.0
+    // that share a semver key.
+    for version in [
+        "1.0.0",
+        "v1.0.0",
+        "1.0.0+build",
+        "2.0.0",
+        "snapshot",
+        "nightly",
+    ] {
+        client
+            .upload_bytes("tied", version, None, "same")
+            .await
+            .unwrap();
+    }
+    sqlx::query("UPDATE artifacts SET created_at = '2024-01-01 00:00:00'")
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+    for sort in [
+        ListSort::Name,
+        ListSort::Created,
+        ListSort::Size,
+        ListSort::Semver,
+    ] {
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let query = ListQuery {
+                sort: Some(sort),
+                order: Some(order),
+                ..Default::default()
+            };
+            let all = client.list_page(Some("tied"), &query).await.unwrap();
+            assert_eq!(all.items.len(), 6);
+            assert!(all.next_cursor.is_none());
+
+            let paged = client
+                .list_all(
+                    Some("tied"),
+                    &ListQuery {
+                        limit: Some(2),
+                        ..query
+                    },
+                )
+                .await
+                .unwrap();
+            let ids = |rows: &[cask_types::ArtifactRow]| {
+                rows.iter().map(|r| r.id.clone()).collect::<Vec<_>>()
+            };
+            assert_eq!(ids(&paged), ids(&all.items), "{:?} {:?}", sort, order);
+        }
+    }
+}
+
+#[tokio::test]
+async fn upload_with_metadata_is_atomic() {
+    let (anon, _state, dir) = spawn_server().await;
+    let client = admin_client(&anon).await;
+
+    client
+        .create_schema(&CreateMetadataSchema {
+            pattern: "app".to_string(),
+            keys: BTreeMap::from([(
+                "git_sha".to_string(),
+                KeySpec {
+                    kind: Some(KeyType::String),
+                    required: true,
+                },
+            )]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let path = dir.path().join("app.bin");
+    std::fs::write(&path, b"app").unwrap();
+    let upload = |metadata: serde_json::Value| {
+        let client = client.clone();
+        let path = path.clone();
+        async move {
+            let metadata = metadata.as_object().unwrap().clone();
+            client
+                .upload_file_with_metadata("app", "1", &path, None, &metadata, |_| {})
+                .await
+        }
+    };
+
+    let err = upload(json!({})).await.unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::Unprocessable));
+    let err = client
+        .upload_asset("app", "1", "notes.txt", &path, |_| {})
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::Unprocessable));
+
+    let err = upload(json!({ "git_sha": 42 })).await.unwrap_err();
+    assert_eq!(err.kind(), Some(ErrorKind::Unprocessable));
+    assert_eq!(err.violations()[0].key.as_deref(), Some("git_sha"));
+    assert!(
+        anon.get_metadata("app", "1")
+            .await
+            .unwrap_err()
+            .is_not_found()
+    );
+
+    let artifact = upload(json!({ "git_sha": "abc", "tests": 12 }))
+        .await
+        .unwrap();
+    assert_eq!(artifact.filename, "app.bin");
+    let meta = anon.get_metadata("app", "1").await.unwrap();
+    assert_eq!(meta.custom["git_sha"], "abc");
+    assert_eq!(meta.custom["tests"], 12);
+}
+
+#[tokio::test]
+async fn metadata_history_records_changes() {
+    let (anon, _state, _dir) = spawn_server().await;
+    let client = admin_client(&anon).await;
+    client
+        .upload_bytes("app", "1", None, &b"app"[..])
+        .await
+        .unwrap();
+
+    client
+        .set_metadata("app", "1", &json!({ "approved": false, "history": "x" }))
+        .await
+        .unwrap();
+    client
+        .patch_metadata("app", "1", &json!({ "approved": true }))
+        .await
+        .unwrap();
+    // Unchanged values aren't recorded.
+    client
+        .set_metadata("app", "1", &json!({ "approved": true }))
+        .await
+        .unwrap();
+    client.delete_metadata("app", "1", "history").await.unwrap();
+
+    let changes = client
+        .metadata_history("app", "1", Some("approved"), 10)
+        .await
+        .unwrap();
+    let values: Vec<_> = changes
+        .iter()
+        .map(|c| (c.old_value.clone(), c.new_value.clone()))
+        .collect();
+    assert_eq!(
+        values,
+        [
+            (Some(json!(false)), Some(json!(true))),
+            (None, Some(json!(false))),
+        ]
+    );
+
+    // The history is public, like the metadata, and doesn't shadow a key
+    // named after it.
+    let latest = &anon.metadata_history("app", "1", None, 1).await.unwrap()[0];
+    assert_eq!(latest.key, "history");
+    assert_eq!(latest.old_value, Some(json!("x")));
+    assert_eq!(latest.new_value, None);
+    let meta = anon.get_metadata("app", "1").await.unwrap();
+    assert!(!meta.custom.contains_key("history"));
+
+    client.delete_artifact("app", "1").await.unwrap();
+    let kept = client.metadata_history("app", "1", None, 10).await.unwrap();
+    assert_eq!(kept.len(), 4);
+    assert!(
+        client
+            .metadata_history("app", "2", None, 10)
+            .await
+            .unwrap_err()
+            .is_not_found()
+    );
+}
+
+#[tokio::test]
+async fn versions_hold_multiple_assets() {
+    let (anon, state, dir) = spawn_server().await;
+    let client = admin_client(&anon).await;
+    client
+        .upload_bytes("app", "1", Some("app-linux"), &b"linux"[..])
+        .await
+        .unwrap();
+
+    let path = dir.path().join("SHA256SUMS");
+    std::fs::write(&path, b"sums").unwrap();
+    let asset = client
+        .upload_asset("app", "1", "SHA256SUMS", &path, |_| {})
+        .await
+        .unwrap();
+    assert_eq!(asset.size, 4);
+    assert!(!asset.primary);
+    let err = client
+        .upload_asset("app", "1", "SHA256SUMS", &path, |_| {})
+        .await
+        .unwrap_err();
+    assert!(err.is_conflict());
+    for filename in ["bad\"name", "line\nbreak", ""] {
+        let err = client
+            .upload_bytes("app", "2", Some(filename), &b"x"[..])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, cask_client::Error::Api { status, .. } if status == 400),
+            "{:?}",
+            filename
+        );
+    }
+
+    let search = |q: &str| {
+        let anon = anon.clone();
+        let q = q.to_string();
+        async move {
+            anon.search(&SearchQuery {
+                q,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .len()
+        }
+    };
+    assert_eq!(search("sha256sums").await, 1);
+
+    let filenames: Vec<_> = anon.list_versions("app").await.unwrap()[0]
+        .assets
+        .iter()
+        .map(|a| (a.filename.clone(), a.primary))
+        .collect();
+    assert_eq!(
+        filenames,
+        [
+            ("app-linux".to_string(), true),
+            ("SHA256SUMS".to_string(), false)
+        ]
+    );
+
+    let mut download = anon.download_asset("app", "1", "SHA256SUMS").await.unwrap();
+    assert_eq!(download.filename(), Some("SHA256SUMS"));
+    let mut body = Vec::new();
+    while let Some(chunk) = download.chunk().await.unwrap() {
+        body.extend_from_slice(&chunk);
+    }
+    assert_eq!(body, b"sums");
+
+    state.recorder.flush().await;
+    let stats = anon
+        .stats(
+            "app",
+            Some("1"),
+            &StatsQuery {
+                by_asset: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let assets = stats.assets.unwrap();
+    assert_eq!(assets.len(), 1);
+    assert_eq!(assets[0].filename, "SHA256SUMS");
+    assert_eq!(assets[0].downloads, 1);
+
+    let err = client
+        .delete_asset("app", "1", "app-linux")
+        .await
+        .unwrap_err();
+    assert!(err.is_conflict());
+    client.delete_asset("app", "1", "SHA256SUMS").await.unwrap();
+    assert_eq!(anon.list_assets("app", "1").await.unwrap().len(), 1);
+    assert_eq!(search("sha256sums").await, 0);
+    assert_eq!(search("app-linux").await, 1);
+}